@@ -1,5 +1,19 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use cirno_core::Manifest;
+use tokio::fs::read_to_string;
+
+use crate::config::EnvironmentState;
 
 pub async fn exists(name: &str) -> Result<bool> {
     Ok(true)
 }
+
+/// Resolves the instance reference of a request (ID, ID prefix, application name, etc.) to an ID, see
+/// [`Manifest::resolve`].
+///
+/// The manifest is read again on each call, since the CLI may change it while the daemon is running.
+pub async fn resolve(env: &EnvironmentState, query: &str) -> Result<String> {
+    let manifest: Manifest =
+        serde_yaml::from_str(&read_to_string(&env.config_path).await.context("Failed to read config file")?)?;
+    Ok(manifest.resolve(query)?.to_string())
+}
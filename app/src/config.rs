@@ -16,6 +16,7 @@ pub struct EnvironmentState {
     pub home_yarn_dir: PathBuf,
     pub home_yarn_cache_dir: PathBuf,
     pub home_yarn_releases_dir: PathBuf,
+    pub home_node_dir: PathBuf,
     pub home_appdata_dir: PathBuf,
     pub home_appdata_local_dir: PathBuf,
     pub home_appdata_roaming_dir: PathBuf,
@@ -61,6 +62,7 @@ pub async fn load_config(exe_dir: PathBuf) -> Result<EnvironmentState> {
     let home_yarn_dir = home_dir.join(".yarn");
     let home_yarn_cache_dir = home_yarn_dir.join("cache");
     let home_yarn_releases_dir = home_yarn_dir.join("releases");
+    let home_node_dir = home_dir.join(".node");
     let home_appdata_dir = home_dir.join("AppData");
     let home_appdata_local_dir = home_appdata_dir.join("Local");
     let home_appdata_roaming_dir = home_appdata_dir.join("Roaming");
@@ -108,6 +110,7 @@ pub async fn load_config(exe_dir: PathBuf) -> Result<EnvironmentState> {
         home_yarn_dir,
         home_yarn_cache_dir,
         home_yarn_releases_dir,
+        home_node_dir,
         home_appdata_dir,
         home_appdata_local_dir,
         home_appdata_roaming_dir,
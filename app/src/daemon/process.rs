@@ -3,6 +3,8 @@ use std::ffi::OsStr;
 use std::sync::{Arc, LazyLock, Weak};
 
 use anyhow::{Context, Result, bail};
+use cirno_core::daemon::{record_exit, record_start};
+use cirno_core::env::{self, Environment};
 use log::warn;
 use thiserror::Error;
 use tokio::spawn;
@@ -71,10 +73,33 @@ impl ProcessDaemon {
 
         spawn(async move {
             loop {
-                let mut cp = CirnoProc::new_yarn(&app.env, &ARG_START, app.env.apps_dir.join(name.clone()));
+                let cwd = app.env.apps_dir.join(name.clone());
+                let environment = match env::load(&app.env.data_dir, &cwd).await {
+                    Ok(environment) => environment,
+                    Err(err) => {
+                        warn!(
+                            "Failed to load environment of app {}, starting it with the environment of the daemon: {}",
+                            name, err
+                        );
+                        Environment::host()
+                    }
+                };
+                let mut cp = CirnoProc::new_yarn(&app.env, &environment, &ARG_START, cwd);
+
+                if let Err(err) = record_start(&app.env.data_dir, &name).await {
+                    warn!("Failed to record start of app {}: {}", name, err);
+                }
 
                 let result = cp.run().await;
 
+                let exit_code = match &result {
+                    Ok(_) => Some(0),
+                    Err(err) => err.downcast_ref::<ExitStatusError>().and_then(|exit_err| exit_err.code()),
+                };
+                if let Err(err) = record_exit(&app.env.data_dir, &name, exit_code).await {
+                    warn!("Failed to record exit of app {}: {}", name, err);
+                }
+
                 match result {
                     Err(err) => match err.downcast_ref::<ExitStatusError>() {
                         Some(exit_err) => {
@@ -8,6 +8,7 @@ use axum::Router;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::routing::post;
+use cirno_core::daemon::DaemonLock;
 use clap::{Args, Parser, Subcommand};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use tap::Tap;
@@ -135,6 +136,7 @@ async fn main_async_intl(logger: Arc<CombinedLogger>) -> Result<()> {
                 .context("Failed to bind tcp port")?;
 
             // Write daemon.lock only after port bound
+            let _daemon_lock = DaemonLock::acquire(&app_state.env.data_dir).context("Failed to lock environment")?;
 
             // Start Server
             let app_state = app_state.clone();
@@ -3,6 +3,7 @@ use std::num::NonZero;
 use std::path::Path;
 
 use anyhow::{Context, Result};
+use cirno_core::env::Environment;
 use thiserror::Error;
 use tokio::process::{Child, Command};
 
@@ -39,20 +40,34 @@ impl CirnoProc {
         CirnoProc { cmd: proc, child: None }
     }
 
+    /// Runs node with the sanitized environment of the app, using its managed runtime if any, or the bundled
+    /// `bin/node`.
     pub fn new_node<IA: IntoIterator<Item = SA>, SA: AsRef<OsStr>, P: AsRef<Path>>(
         env: &EnvironmentState,
+        environment: &Environment,
         args: IA,
         cwd: P,
     ) -> CirnoProc {
-        CirnoProc::new(env.bin_dir.join("node"), args, cwd)
+        let node = match &environment.runtime {
+            Some(runtime) => runtime.node(),
+            None => env.bin_dir.join("node"),
+        };
+        let mut proc = CirnoProc::new(node, args, cwd);
+        proc.cmd.env_clear().envs(environment.iter());
+        proc
     }
 
-    pub fn new_yarn<P: AsRef<Path>>(env: &EnvironmentState, args: &[&OsStr], cwd: P) -> CirnoProc {
+    pub fn new_yarn<P: AsRef<Path>>(
+        env: &EnvironmentState,
+        environment: &Environment,
+        args: &[&OsStr],
+        cwd: P,
+    ) -> CirnoProc {
         let mut args = args.to_vec();
         let yarn_path = env.bin_dir.join("yarn.cjs");
         args.insert(0, yarn_path.as_os_str());
 
-        CirnoProc::new_node(env, args, cwd)
+        CirnoProc::new_node(env, environment, args, cwd)
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -5,7 +5,7 @@ use axum::extract::{Path, State};
 use serde::Serialize;
 
 use crate::server::{ApiJson, ServiceClaim};
-use crate::{AppError, AppState};
+use crate::{AppError, AppState, app};
 
 #[derive(Serialize)]
 pub struct Response {}
@@ -16,5 +16,7 @@ pub async fn controller_app_backup(
     claim: ServiceClaim,
     Path(id): Path<String>,
 ) -> anyhow::Result<ApiJson<Response>, AppError> {
+    app::resolve(&app_state.env, &id).await?;
+
     Ok(ApiJson(Response {}))
 }
@@ -5,7 +5,7 @@ use axum::extract::{Path, State};
 use serde::Serialize;
 
 use crate::server::ApiJson;
-use crate::{AppError, AppState};
+use crate::{AppError, AppState, app};
 
 #[derive(Serialize)]
 pub struct Response {}
@@ -15,6 +15,7 @@ pub async fn controller_app_start(
     State(app_state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> anyhow::Result<ApiJson<Response>, AppError> {
+    let id = app::resolve(&app_state.env, &id).await?;
     app_state.process_daemon.start(&id).await?;
 
     Ok(ApiJson(Response {}))
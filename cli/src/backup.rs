@@ -0,0 +1,36 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::baka::BackupOptions;
+use clap::Args;
+use owo_colors::OwoColorize;
+use uuid::Uuid;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Backup {
+    #[clap(help = "Application ID")]
+    id: Option<String>,
+    #[clap(long = "id", help = "Specify the new instance ID")]
+    new_id: Option<Uuid>,
+    #[clap(short, long, help = "Describe the backup")]
+    message: Option<String>,
+}
+
+impl EnvArgs for Backup {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "backup")?;
+        let options = BackupOptions {
+            id: self.new_id,
+            r#type: None,
+            message: self.message,
+        };
+        let backup = cirno.backup(&id, &options).await?;
+        println!(
+            "{:>12} Created backup instance {}.",
+            "Success".bold().bright_green(),
+            backup
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::instance::CloneOptions;
+use clap::Args;
+use owo_colors::OwoColorize;
+use uuid::Uuid;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Clone {
+    #[clap(help = "Instance ID, either an application or a backup")]
+    id: Option<String>,
+    #[clap(long = "id", help = "Specify the new instance ID")]
+    new_id: Option<Uuid>,
+    #[clap(long, help = "Specify the new application name")]
+    name: Option<String>,
+}
+
+impl EnvArgs for Clone {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "clone")?;
+        let options = CloneOptions {
+            id: self.new_id,
+            name: self.name,
+        };
+        let new_id = cirno.clone_instance(&id, &options).await?;
+        println!(
+            "{:>12} Cloned instance {} into {}.",
+            "Success".bold().bright_green(),
+            id,
+            new_id
+        );
+        Ok(())
+    }
+}
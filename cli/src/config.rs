@@ -0,0 +1,59 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::yarn::YarnRcSource;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Config {
+    #[clap(help = "Application ID")]
+    id: Option<String>,
+    #[clap(long, help = "Show the layer which supplied each setting")]
+    explain: bool,
+    #[clap(long, help = "Output in JSON format")]
+    json: bool,
+}
+
+fn format_source(source: &YarnRcSource) -> String {
+    match source {
+        YarnRcSource::Global => "global .yarnrc.yml".to_string(),
+        YarnRcSource::App => "app .yarnrc.yml".to_string(),
+        YarnRcSource::Env(name) => format!("env {}", name),
+        YarnRcSource::Cirno => "cirno".to_string(),
+    }
+}
+
+impl EnvArgs for Config {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "config")?;
+        let mut resolved = cirno.resolve_yarn_rc(&id).await?;
+        for (source, misspelling) in &resolved.misspellings {
+            eprintln!(
+                "{:>12} {} ({})",
+                "Warning".bold().bright_yellow(),
+                misspelling,
+                format_source(source)
+            );
+        }
+        resolved.mask_secrets();
+        match (self.json, self.explain) {
+            (true, true) => println!("{}", serde_json::to_string_pretty(&resolved)?),
+            (true, false) => println!("{}", serde_json::to_string_pretty(&resolved.to_value())?),
+            (false, false) => print!("{}", serde_yaml_ng::to_string(&resolved.to_value())?),
+            (false, true) => {
+                for (name, setting) in &resolved.settings {
+                    let value = serde_json::to_string(&setting.value)?;
+                    let mut source = format_source(&setting.source);
+                    if !setting.overrides.is_empty() {
+                        let overrides = setting.overrides.iter().map(format_source).collect::<Vec<_>>();
+                        source = format!("{}, overrides {}", source, overrides.join(", "));
+                    }
+                    println!("{}: {} {}", name.bold(), value, format!("({})", source).dimmed());
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::{Result, anyhow};
+use cirno_core::Cirno;
+use cirno_core::instance::CreateOptions;
+use clap::Args;
+use owo_colors::OwoColorize;
+use uuid::Uuid;
+
+use crate::EnvArgs;
+
+#[derive(Debug, Args)]
+pub struct Create {
+    #[clap(help = "Application name")]
+    name: Option<String>,
+    #[clap(long, help = "Specify the new instance ID")]
+    id: Option<Uuid>,
+    #[clap(long, help = "Specify the package manager, eg. yarn@4.5.0")]
+    manager: Option<String>,
+    #[clap(last = true, help = "Arguments passed to yarn instead of `install`")]
+    args: Vec<String>,
+}
+
+impl EnvArgs for Create {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let name = self
+            .name
+            .ok_or_else(|| anyhow!("Missing application name. See `cirno create --help` for usage."))?;
+        let manager = self
+            .manager
+            .ok_or_else(|| anyhow!("Missing package manager. See `cirno create --help` for usage."))?;
+        let options = CreateOptions {
+            id: self.id,
+            package_manager: manager,
+            yarn_args: self.args,
+        };
+        let id = cirno.create(&name, &options).await?;
+        println!(
+            "{:>12} Created application {} ({}).",
+            "Success".bold().bright_green(),
+            id,
+            name
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::usage::InstanceDiskUsage;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, format_size, print_field, print_table};
+
+#[derive(Debug, Args)]
+pub struct Du {
+    #[clap(help = "Only report the application of this instance")]
+    id: Option<String>,
+    #[clap(long, help = "Output in JSON format")]
+    json: bool,
+}
+
+/// Bytes freed by removing an instance: its files, its share of the backup archive and the store files only it
+/// references. Unknown for a head instance with backups, since removing it restores the last backup in its place.
+fn freed(usage: &InstanceDiskUsage, has_backups: bool) -> Option<u64> {
+    match (usage.backup, has_backups) {
+        (None, true) => None,
+        _ => Some(usage.files + usage.backups + usage.exclusive_store),
+    }
+}
+
+impl EnvArgs for Du {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let app_id = match &self.id {
+            Some(id) => {
+                let id = cirno.resolve(id)?;
+                cirno.get(&id).map(|app| app.id)
+            }
+            None => None,
+        };
+        let mut usage = cirno.disk_usage().await?;
+        if let Some(app_id) = app_id {
+            usage.apps.retain(|app| app.id == app_id);
+        }
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&usage)?);
+            return Ok(());
+        }
+
+        let headers = ["ID", "INSTANCE", "FILES", "BACKUPS", "EXCLUSIVE", "SHARED", "FREED"];
+        let mut rows = Vec::new();
+        for app in &usage.apps {
+            let total = &app.total;
+            rows.push(vec![
+                app.id.to_string()[..8].to_string(),
+                app.name.bold().to_string(),
+                format_size(total.files),
+                format_size(total.backups),
+                format_size(total.exclusive_store),
+                format_size(total.shared_store),
+                format_size(total.exclusive()).bold().to_string(),
+            ]);
+            for instance in app.instances.iter().rev() {
+                let label = match instance.backup {
+                    Some(position) => format!("  backup {}", position),
+                    None => "  head".to_string(),
+                };
+                rows.push(vec![
+                    (&instance.id.to_string()[..8]).dimmed().to_string(),
+                    label.dimmed().to_string(),
+                    format_size(instance.files),
+                    format_size(instance.backups),
+                    format_size(instance.exclusive_store),
+                    format_size(instance.shared_store),
+                    freed(instance, app.instances.len() > 1).map_or_else(|| "-".to_string(), format_size),
+                ]);
+            }
+        }
+        if rows.is_empty() {
+            println!("No applications found.");
+        } else {
+            print_table(&headers, &rows);
+        }
+
+        let environment = &usage.environment;
+        println!();
+        print_field("Cache", format_size(environment.cache));
+        print_field("Releases", format_size(environment.releases));
+        print_field(
+            "Unreferenced",
+            format!("{} (freed by `cirno gc`)", format_size(environment.unreferenced)),
+        );
+        print_field("Apps", format_size(environment.apps));
+        print_field("Backups", format_size(environment.backups));
+        print_field("Tmp", format_size(environment.tmp));
+        print_field("Logs", format_size(environment.logs));
+        print_field("Total", format_size(environment.total));
+        Ok(())
+    }
+}
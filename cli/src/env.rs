@@ -0,0 +1,73 @@
+use anyhow::{Result, anyhow};
+use cirno_core::Cirno;
+use cirno_core::env::EnvSource;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Env {
+    #[clap(help = "Application ID")]
+    id: Option<String>,
+    #[clap(long, value_name = "KEY=VALUE", help = "Declare a variable")]
+    set: Vec<String>,
+    #[clap(long, value_name = "KEY=VALUE", help = "Declare a secret variable")]
+    secret: Vec<String>,
+    #[clap(long, value_name = "KEY", help = "Remove a declared variable or secret")]
+    unset: Vec<String>,
+    #[clap(long, help = "Show the values of secrets")]
+    show_secrets: bool,
+    #[clap(long, help = "Output in JSON format")]
+    json: bool,
+}
+
+fn parse_assignment(value: &str) -> Result<(&str, &str)> {
+    value
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| anyhow!("Invalid variable: {} (expected KEY=VALUE).", value))
+}
+
+impl EnvArgs for Env {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "env")?;
+        for name in &self.unset {
+            cirno.set_env(&id, name, None).await?;
+            cirno.set_secret(&id, name, None).await?;
+        }
+        for assignment in &self.set {
+            let (name, value) = parse_assignment(assignment)?;
+            cirno.set_env(&id, name, Some(value.to_string())).await?;
+        }
+        for assignment in &self.secret {
+            let (name, value) = parse_assignment(assignment)?;
+            cirno.set_secret(&id, name, Some(value.to_string())).await?;
+        }
+
+        let cwd = cirno.cwd.join("apps").join(id.to_string());
+        let mut env = cirno.environment(&cwd).await?;
+        env.vars.sort_by(|a, b| a.name.cmp(&b.name));
+        if !self.show_secrets {
+            for var in &mut env.vars {
+                if var.source == EnvSource::Secret {
+                    var.value = "********".to_string();
+                }
+            }
+        }
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&env.vars)?);
+            return Ok(());
+        }
+        for var in &env.vars {
+            let source = match var.source {
+                EnvSource::Host => "host".dimmed().to_string(),
+                EnvSource::App => "app".bright_blue().to_string(),
+                EnvSource::Secret => "secret".bright_magenta().to_string(),
+                EnvSource::Cirno => "cirno".bright_cyan().to_string(),
+            };
+            println!("{}={} ({})", var.name.bold(), var.value, source);
+        }
+        Ok(())
+    }
+}
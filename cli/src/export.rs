@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Error, Result, bail};
+use cirno_core::Cirno;
+use cirno_core::bundle::{BundleFormat, ExportOptions, OciOptions};
+use cirno_core::sbom::SbomFormat;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, format_size, require, resolve_id};
+
+/// Output format of `cirno export`: a bundle file, or an OCI image.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Bundle(BundleFormat),
+    Oci,
+}
+
+impl Format {
+    /// Detects the format from the extension of the output path, where OCI images end with `.oci.tar`.
+    fn from_path(path: &std::path::Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        match name.ends_with(".oci.tar") {
+            true => Some(Format::Oci),
+            false => BundleFormat::from_path(path).map(Format::Bundle),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "oci" => Ok(Format::Oci),
+            _ => Ok(Format::Bundle(s.parse()?)),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct Export {
+    #[clap(help = "Instance ID")]
+    id: Option<String>,
+    #[clap(help = "Output path")]
+    dest: Option<PathBuf>,
+    #[clap(long, help = "Export as a zip file, same as --format zip")]
+    zip: bool,
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        conflicts_with = "zip",
+        help = "Export as a bundle file (zip, tar.br, tar.zst) or an OCI image tarball (oci, which requires --runtime), \
+                detected from the extension of the output path by default"
+    )]
+    format: Option<Format>,
+    #[clap(long, help = "Reference of the OCI image (defaults to <package name>:latest)")]
+    tag: Option<String>,
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        help = "Embed an SBOM in the bundle (cyclonedx-json, spdx-json)"
+    )]
+    sbom: Option<SbomFormat>,
+    #[clap(long, help = "Embed the managed Node.js runtime in the bundle")]
+    runtime: bool,
+    #[clap(
+        long,
+        value_name = "KEY",
+        help = "Sign the bundle with a private key of the environment"
+    )]
+    sign: Option<String>,
+    #[clap(
+        long,
+        value_name = "ID",
+        conflicts_with_all = ["zip", "format", "sbom", "runtime", "sign"],
+        help = "Export a delta bundle from this instance (eg. a backup) to the exported instance"
+    )]
+    base: Option<String>,
+}
+
+impl EnvArgs for Export {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "export")?;
+        let dest = std::path::absolute(require(self.dest.as_ref(), "output path", "export")?)?;
+        if let Some(base) = &self.base {
+            let base = &cirno.resolve(base)?;
+            let manifest = cirno.export_delta(&id, base, &dest).await?;
+            println!(
+                "{:>12} Exported delta from {} to {} at {} ({}): {} changed, {} deleted, {} cache files.",
+                "Success".bold().bright_green(),
+                base,
+                id,
+                dest.display(),
+                format_size(std::fs::metadata(&dest)?.len()),
+                manifest.changed.len(),
+                manifest.deleted.len(),
+                manifest.cache.len()
+            );
+            return Ok(());
+        }
+        let format = match self.zip {
+            true => Some(Format::Bundle(BundleFormat::Zip)),
+            false => self.format.or_else(|| Format::from_path(&dest)),
+        };
+        if self.tag.is_some() && !matches!(format, Some(Format::Oci)) {
+            bail!("--tag only applies to OCI images, use --format oci or a .oci.tar output path.");
+        }
+        let mut options = ExportOptions {
+            format: None,
+            sbom: self.sbom,
+            runtime: self.runtime,
+            sign: self.sign,
+        };
+        match format {
+            Some(Format::Oci) => {
+                let oci_options = OciOptions { tag: self.tag };
+                cirno.export_oci(&id, &dest, &options, &oci_options).await?;
+            }
+            Some(Format::Bundle(format)) => {
+                options.format = Some(format);
+                cirno.export(&id, &dest, &options).await?;
+            }
+            None => cirno.export(&id, &dest, &options).await?,
+        }
+        let size = if format.is_some() {
+            format!(" ({})", format_size(std::fs::metadata(&dest)?.len()))
+        } else {
+            String::new()
+        };
+        println!(
+            "{:>12} Exported instance {} to {}{}.",
+            "Success".bold().bright_green(),
+            id,
+            dest.display(),
+            size
+        );
+        Ok(())
+    }
+}
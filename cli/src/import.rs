@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::bundle::ImportOptions;
+use cirno_core::sign::SignatureStatus;
+use clap::Args;
+use owo_colors::OwoColorize;
+use uuid::Uuid;
+
+use crate::EnvArgs;
+
+#[derive(Debug, Args)]
+pub struct Import {
+    #[clap(
+        required_unless_present = "apply_delta",
+        help = "Bundle path, either a directory, a zip, tar.br or tar.zst file, or a git repository or bundle"
+    )]
+    src: Option<PathBuf>,
+    #[clap(long, help = "Specify the new instance ID")]
+    id: Option<Uuid>,
+    #[clap(long, help = "Specify the new application name")]
+    name: Option<String>,
+    #[clap(
+        long = "ref",
+        value_name = "REF",
+        help = "Branch, tag or commit to import from a git source"
+    )]
+    r#ref: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        requires = "onto",
+        conflicts_with_all = ["src", "id", "name", "ref"],
+        help = "Apply a delta bundle onto an application instead of importing a new one"
+    )]
+    apply_delta: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "ID",
+        requires = "apply_delta",
+        help = "Application to apply the delta onto"
+    )]
+    onto: Option<String>,
+}
+
+/// Prints the signer of a verified bundle. Other statuses are reported as warnings while the bundle is verified.
+pub fn print_signature(signature: &SignatureStatus) {
+    if let SignatureStatus::Verified { key } = signature {
+        println!("{:>12} Bundle is signed by {}.", "Verified".bold().bright_green(), key)
+    }
+}
+
+impl EnvArgs for Import {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        if let (Some(delta), Some(onto)) = (&self.apply_delta, &self.onto) {
+            let onto = cirno.resolve(onto)?;
+            let backup = cirno.apply_delta(&std::path::absolute(delta)?, &onto).await?;
+            println!(
+                "{:>12} Applied delta onto {}, previous head backed up as {}.",
+                "Success".bold().bright_green(),
+                onto,
+                backup
+            );
+            return Ok(());
+        }
+        let src = std::path::absolute(self.src.unwrap_or_default())?;
+        let options = ImportOptions {
+            id: self.id,
+            name: self.name,
+            r#ref: self.r#ref,
+        };
+        let report = cirno.import(&src, &options).await?;
+        print_signature(&report.signature);
+        match &report.source {
+            Some(source) => println!(
+                "{:>12} Imported instance {} from commit {}.",
+                "Success".bold().bright_green(),
+                report.id,
+                source.commit
+            ),
+            None => println!(
+                "{:>12} Imported instance {}.",
+                "Success".bold().bright_green(),
+                report.id
+            ),
+        }
+        Ok(())
+    }
+}
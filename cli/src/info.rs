@@ -0,0 +1,124 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::info::ManifestEntry;
+use clap::Args;
+use owo_colors::OwoColorize;
+use uuid::Uuid;
+
+use crate::{EnvArgs, format_labels, format_size, print_field, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Info {
+    #[clap(help = "Instance ID, either an application or a backup")]
+    id: Option<String>,
+    #[clap(long, help = "Output in JSON format")]
+    json: bool,
+}
+
+fn format_id(id: Option<Uuid>) -> String {
+    id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+impl EnvArgs for Info {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "info")?;
+        let info = cirno.info(&id).await?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&info)?);
+            return Ok(());
+        }
+
+        let timeline = &info.timeline;
+        match &info.entry {
+            ManifestEntry::App(app) => {
+                print_field("Instance", format!("{} (head instance of {})", info.id, info.name));
+                print_field("Created", &app.created);
+                let labels = format_labels(&app.tags, &app.aliases);
+                if !labels.is_empty() {
+                    print_field("Tags", labels);
+                }
+                if let Some(source) = &app.source {
+                    print_field("Source", format!("{} at {}", source.repository, source.commit));
+                }
+                if let Some(hash) = &app.release_hash {
+                    print_field("Release hash", hash);
+                }
+            }
+            ManifestEntry::Backup(backup) => {
+                print_field(
+                    "Instance",
+                    format!("{} (backup {} of {})", info.id, timeline.position, info.name),
+                );
+                print_field("Created", &backup.created);
+                if let Some(r#type) = &backup.r#type {
+                    print_field("Type", r#type);
+                }
+                if let Some(message) = &backup.message {
+                    print_field("Message", message);
+                }
+                let labels = format_labels(&backup.tags, &backup.aliases);
+                if !labels.is_empty() {
+                    print_field("Tags", labels);
+                }
+            }
+        }
+        print_field(
+            "Timeline",
+            format!(
+                "{} of {}, previous {}, next {}",
+                timeline.position,
+                timeline.len,
+                format_id(timeline.previous),
+                format_id(timeline.next)
+            ),
+        );
+
+        let meta = &info.meta;
+        print_field("Package", &meta.package.name);
+        print_field("Manager", &meta.package.package_manager);
+        print_field(
+            "Lockfile",
+            format!(
+                "version {}, cache key {}, {} packages",
+                meta.lockfile_version, meta.cache_key, meta.packages
+            ),
+        );
+
+        let usage = &info.usage;
+        if let Some(files) = usage.files {
+            print_field("Size", format_size(files));
+        }
+        print_field("Backups", format_size(usage.backups));
+        print_field(
+            "Store",
+            format!(
+                "{} files ({}), {} shared with other applications ({})",
+                usage.store_files,
+                format_size(usage.store_bytes),
+                usage.shared_files,
+                format_size(usage.shared_bytes)
+            ),
+        );
+
+        let status = match &info.process {
+            _ if info.running => "running".bright_green().to_string(),
+            Some(process) => match (&process.exited, process.exit_code) {
+                (Some(exited), Some(code)) => {
+                    format!("started {}, exited {} with code {}", process.started, exited, code)
+                }
+                (Some(exited), None) => format!("started {}, killed {}", process.started, exited),
+                (None, _) => format!("started {}, exit not recorded", process.started),
+            },
+            None => "never started by the daemon".dimmed().to_string(),
+        };
+        print_field("Status", status);
+
+        println!("{}", format!("{:>12}", "Yarn config").bold());
+        if let Some(settings) = info.yarn_rc.as_object() {
+            for (name, value) in settings {
+                println!("{:>12} {}: {}", "", name, serde_json::to_string(value)?);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 use std::process::ExitCode;
 
+use cirno_core::yarn::NodeLinker;
 use cirno_core::{Cirno, InitError};
 use clap::Args;
 use owo_colors::OwoColorize;
@@ -11,11 +12,13 @@ pub struct Init {
     pub cwd: PathBuf,
     #[arg(short, long, default_value_t = false)]
     pub force: bool,
+    #[arg(long, default_value = "pnp", help = "Default node linker (pnp, node-modules, pnpm)")]
+    pub node_linker: NodeLinker,
 }
 
 impl Init {
     pub async fn main(self) -> ExitCode {
-        match Cirno::init(&self.cwd, self.force).await {
+        match Cirno::init(&self.cwd, self.force, self.node_linker).await {
             Ok(cwd) => {
                 println!(
                     "{:>12} Cirno environment initialized at {}.",
@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::Result;
+use cirno_core::bundle::{self, BundleDescriptor, BundleReport, PlatformConstraints};
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{format_size, print_field};
+
+#[derive(Debug, Args)]
+pub struct Inspect {
+    #[clap(help = "Bundle path, either a directory or a zip, tar.br or tar.zst file")]
+    src: PathBuf,
+    #[clap(long, help = "Print the report as JSON")]
+    json: bool,
+    #[clap(
+        long,
+        default_value_t = 10,
+        help = "Number of the largest packages to list, 0 to list all of them"
+    )]
+    top: usize,
+}
+
+fn format_platform(platform: &PlatformConstraints) -> String {
+    let format_list = |list: &[String]| match list.is_empty() {
+        true => "any".to_string(),
+        false => list.join(", "),
+    };
+    format!("os: {}, cpu: {}", format_list(&platform.os), format_list(&platform.cpu))
+}
+
+fn print_descriptor(descriptor: &BundleDescriptor) {
+    if let Some(version) = &descriptor.app_version {
+        print_field("Version", version);
+    }
+    print_field("Instance", descriptor.instance.to_string());
+    print_field(
+        "Created",
+        format!("{} by Cirno {}", descriptor.created, descriptor.cirno_version),
+    );
+    print_field(
+        "Yarn",
+        format!("{} ({})", descriptor.yarn.version, descriptor.yarn.hash),
+    );
+    print_field("Cache key", &descriptor.cache_key);
+    print_field("Platform", format_platform(&descriptor.platform));
+    let runtime = &descriptor.runtime;
+    let requirement = runtime.requirement.as_deref().unwrap_or("any");
+    match &runtime.bundled {
+        Some(bundled) => print_field("Runtime", format!("{} (bundled {})", requirement, bundled)),
+        None => print_field("Runtime", requirement),
+    }
+}
+
+fn print_report(report: &BundleReport, top: usize) {
+    print_field("Name", &report.name);
+    print_field("Manager", &report.package_manager);
+    print_field(
+        "Format",
+        report
+            .format
+            .map_or("directory".to_string(), |format| format.to_string()),
+    );
+    match &report.descriptor {
+        Some(descriptor) => print_descriptor(descriptor),
+        None => print_field("Descriptor", "none (exported by an older version)"),
+    }
+    print_field("Lockfile", format!("v{}", report.lockfile_version));
+    print_field(
+        "Packages",
+        format!("{} ({})", report.packages.len(), format_size(report.cache_size)),
+    );
+    print_field("Size", format_size(report.total_size));
+
+    let mut packages = report.packages.iter().collect::<Vec<_>>();
+    packages.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    let count = if top == 0 {
+        packages.len()
+    } else {
+        top.min(packages.len())
+    };
+    if count > 0 {
+        println!();
+        for package in &packages[..count] {
+            match package.cache_file {
+                Some(_) => println!("{:>12} {}", format_size(package.size), package.name),
+                None => println!("{:>12} {}", "missing".bright_red(), package.name),
+            }
+        }
+        if count < packages.len() {
+            println!("{:>12} {} more", "...".dimmed(), packages.len() - count);
+        }
+    }
+
+    println!();
+    if report.self_contained {
+        println!("{:>12} Bundle is self-contained.", "Success".bold().bright_green());
+    } else {
+        for issue in &report.issues {
+            println!("{:>12} {}", "Error".bold().bright_red(), issue);
+        }
+    }
+}
+
+impl Inspect {
+    async fn run(self) -> Result<bool> {
+        let report = bundle::inspect(&self.src).await?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_report(&report, self.top);
+        }
+        Ok(report.self_contained)
+    }
+
+    pub async fn main(self) -> ExitCode {
+        match self.run().await {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => ExitCode::FAILURE,
+            Err(error) => {
+                println!(
+                    "{:>12} Failed to inspect bundle: {}",
+                    "Error".bold().bright_red(),
+                    error
+                );
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
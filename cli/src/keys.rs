@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use cirno_core::Cirno;
+use clap::{Args, Subcommand};
+use owo_colors::OwoColorize;
+
+use crate::EnvArgs;
+
+#[derive(Debug, Args)]
+pub struct Keys {
+    #[command(subcommand)]
+    command: KeysCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum KeysCommand {
+    /// Generate a signing key pair, and trust its public key
+    Generate {
+        #[clap(help = "Key name")]
+        name: String,
+    },
+    /// Trust a public key
+    Import {
+        #[clap(help = "Key name")]
+        name: String,
+        #[clap(help = "Public key in hex")]
+        public_key: Option<String>,
+        #[clap(long, conflicts_with = "public_key", help = "Read the public key from a file")]
+        file: Option<PathBuf>,
+    },
+    /// List the trusted keys
+    #[command(alias = "ls")]
+    List {
+        #[clap(long, help = "Output in JSON format")]
+        json: bool,
+    },
+}
+
+impl EnvArgs for Keys {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        match self.command {
+            KeysCommand::Generate { name } => {
+                let key = cirno.generate_key(&name).await?;
+                println!(
+                    "{:>12} Generated key {}, public key: {}",
+                    "Success".bold().bright_green(),
+                    key.name,
+                    key.public_key
+                );
+            }
+            KeysCommand::Import { name, public_key, file } => {
+                let public_key = match (public_key, file) {
+                    (Some(public_key), _) => public_key,
+                    (None, Some(file)) => std::fs::read_to_string(file)?,
+                    (None, None) => bail!("Missing public key. See `cirno keys import --help` for usage."),
+                };
+                cirno.import_key(&name, &public_key).await?;
+                println!("{:>12} Trusted key {}.", "Success".bold().bright_green(), name);
+            }
+            KeysCommand::List { json } => {
+                let keys = cirno.keys().await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&keys)?);
+                } else {
+                    println!(
+                        "Trust policy: {}",
+                        serde_json::to_value(cirno.trust().policy)?.as_str().unwrap_or_default()
+                    );
+                    for key in keys {
+                        let private = if key.private { " (private)" } else { "" };
+                        println!("{}\t{}{}", key.name, key.public_key, private.dimmed());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
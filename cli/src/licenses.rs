@@ -0,0 +1,48 @@
+use anyhow::{Result, bail};
+use cirno_core::Cirno;
+use cirno_core::license::LicenseStatus;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Licenses {
+    #[clap(help = "Instance ID")]
+    id: Option<String>,
+    #[clap(long, help = "Output in JSON format")]
+    json: bool,
+}
+
+impl EnvArgs for Licenses {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "licenses")?;
+        let licenses = cirno.licenses(&id).await?;
+        let flagged = licenses.iter().filter(|license| license.status.is_flagged()).count();
+        if self.json {
+            let json = serde_json::to_string(&licenses)?;
+            println!("{json}");
+        } else {
+            for license in &licenses {
+                let status = match license.status {
+                    LicenseStatus::Approved => format!("{:>12}", "Approved").bright_green().to_string(),
+                    LicenseStatus::Denied => format!("{:>12}", "Denied").bright_red().to_string(),
+                    LicenseStatus::NotApproved => format!("{:>12}", "Not approved").bright_yellow().to_string(),
+                    LicenseStatus::Missing => format!("{:>12}", "Missing").bright_red().to_string(),
+                };
+                println!(
+                    "{} {}@{}\t{}",
+                    status.bold(),
+                    license.name,
+                    license.version,
+                    license.license.as_deref().unwrap_or("-")
+                );
+            }
+            println!("Found {} packages, {} flagged.", licenses.len(), flagged);
+        }
+        if flagged > 0 {
+            bail!("{} packages have missing or non-approved licenses", flagged);
+        }
+        Ok(())
+    }
+}
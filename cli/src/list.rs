@@ -1,36 +1,268 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
-use cirno_core::Cirno;
-use clap::Args;
+use cirno_core::daemon::DaemonStatus;
+use cirno_core::usage::AppUsage;
+use cirno_core::yarn::PackageManager;
+use cirno_core::{App, Cirno, Meta};
+use clap::{Args, ValueEnum};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{EnvArgs, format_labels, format_size, print_table};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// One row per application, with its sizes and status.
+    #[default]
+    Table,
+    /// Applications with their backups.
+    Tree,
+    Json,
+    Yaml,
+}
 
-use crate::EnvArgs;
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Sort {
+    /// By name, then by creation time.
+    Name,
+    /// Oldest first.
+    Created,
+    /// Largest exclusive size first.
+    Size,
+    /// Most backups first.
+    Backups,
+}
 
 #[derive(Debug, Args)]
 pub struct List {
-    #[clap(long, help = "Output in JSON format")]
+    #[clap(long, value_enum, default_value_t, help = "Output format")]
+    format: Format,
+    #[clap(
+        long,
+        conflicts_with = "format",
+        help = "Output in JSON format, same as --format json"
+    )]
     json: bool,
+    #[clap(
+        long,
+        value_enum,
+        help = "Sort applications, in the order of the manifest by default"
+    )]
+    sort: Option<Sort>,
+    #[clap(long, help = "Only list applications whose name contains this text")]
+    name: Option<String>,
+    #[clap(
+        long = "tag",
+        value_name = "TAG",
+        help = "Only list instances with this tag or alias, can be repeated"
+    )]
+    tags: Vec<String>,
+    #[clap(
+        long,
+        value_name = "SPEC",
+        help = "Only list applications whose packageManager starts with this value (eg. yarn@4)"
+    )]
+    package_manager: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    #[serde(flatten)]
+    app: App,
+    package_manager: Option<String>,
+    yarn_version: Option<String>,
+    linker: Option<String>,
+    usage: AppUsage,
+    /// Status of the application in the daemon, only set while a daemon runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+}
+
+/// Whether an instance has every tag of the filter, as a tag or an alias.
+fn is_selected(filter: &[String], tags: &[String], aliases: &[String]) -> bool {
+    filter.iter().all(|tag| tags.contains(tag) || aliases.contains(tag))
+}
+
+/// Status of an application in the daemon: `running`, `exited`, `exited (<code>)`, `killed` or `stopped`.
+fn status(daemon: &DaemonStatus, id: &str) -> String {
+    match daemon.processes.get(id) {
+        _ if daemon.is_running(id) => "running".to_string(),
+        Some(record) if record.exited.is_some() => match record.exit_code {
+            Some(0) => "exited".to_string(),
+            Some(code) => format!("exited ({})", code),
+            None => "killed".to_string(),
+        },
+        _ => "stopped".to_string(),
+    }
+}
+
+fn format_status(status: &str) -> String {
+    match status {
+        "running" => status.bright_green().to_string(),
+        "stopped" => status.dimmed().to_string(),
+        "exited" => status.to_string(),
+        _ => status.bright_red().to_string(),
+    }
+}
+
+/// Formats the creation time of an instance to the minute, eg. `2025-01-31 12:34`.
+fn format_created(created: &str) -> String {
+    created.get(..16).unwrap_or(created).replace('T', " ")
+}
+
+impl List {
+    async fn entries(&self, cirno: &mut Cirno, daemon: &DaemonStatus) -> Result<Vec<Entry>> {
+        // the tree shows no sizes, so they are only computed to sort by size
+        let format = if self.json { Format::Json } else { self.format };
+        let mut usage = match format != Format::Tree || matches!(self.sort, Some(Sort::Size)) {
+            true => cirno.app_usage().await?,
+            false => HashMap::new(),
+        };
+        let mut entries = Vec::new();
+        for app in std::mem::take(&mut cirno.manifest.apps) {
+            if let Some(name) = &self.name
+                && !app.name.contains(name.as_str())
+            {
+                continue;
+            }
+            // an application is listed if any of its instances is selected
+            if !is_selected(&self.tags, &app.tags, &app.aliases)
+                && !app
+                    .backups
+                    .iter()
+                    .any(|backup| is_selected(&self.tags, &backup.tags, &backup.aliases))
+            {
+                continue;
+            }
+            // a broken application is still listed, without the details read from its files
+            let meta = Meta::load(&cirno.cwd.join("apps").join(app.id.to_string())).await.ok();
+            let package_manager = meta.as_ref().map(|meta| meta.package.package_manager.clone());
+            if let Some(filter) = &self.package_manager
+                && !package_manager
+                    .as_ref()
+                    .is_some_and(|value| value.starts_with(filter.as_str()))
+            {
+                continue;
+            }
+            let linker = match &meta {
+                Some(meta) => cirno.node_linker(&meta.yarn_rc).await.ok(),
+                None => None,
+            };
+            let id = app.id.to_string();
+            entries.push(Entry {
+                yarn_version: package_manager
+                    .as_deref()
+                    .and_then(|value| PackageManager::yarn(value).ok())
+                    .map(|package_manager| package_manager.version),
+                package_manager,
+                linker: linker.map(|linker| linker.to_string()),
+                usage: usage.remove(&app.id).unwrap_or_default(),
+                status: daemon.running.then(|| status(daemon, &id)),
+                app,
+            });
+        }
+        match self.sort {
+            Some(Sort::Name) => {
+                entries.sort_by(|a, b| (&a.app.name, &a.app.created).cmp(&(&b.app.name, &b.app.created)))
+            }
+            Some(Sort::Created) => entries.sort_by(|a, b| a.app.created.cmp(&b.app.created)),
+            Some(Sort::Size) => entries.sort_by_key(|entry| std::cmp::Reverse(entry.usage.exclusive())),
+            Some(Sort::Backups) => entries.sort_by_key(|entry| std::cmp::Reverse(entry.app.backups.len())),
+            None => {}
+        }
+        Ok(entries)
+    }
 }
 
 impl EnvArgs for List {
-    async fn main(self, cirno: Cirno) -> Result<()> {
-        if self.json {
-            let json = serde_json::to_string(&cirno.manifest.apps)?;
-            println!("{json}");
-            return Ok(());
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let format = if self.json { Format::Json } else { self.format };
+        let daemon = cirno.daemon_status().await?;
+        let entries = self.entries(&mut cirno, &daemon).await?;
+        match format {
+            Format::Json => {
+                println!("{}", serde_json::to_string(&entries)?);
+                return Ok(());
+            }
+            Format::Yaml => {
+                print!("{}", serde_yaml_ng::to_string(&entries)?);
+                return Ok(());
+            }
+            _ => {}
         }
-        if cirno.manifest.apps.is_empty() {
+        if entries.is_empty() {
             println!("No applications found.");
             return Ok(());
         }
-        let len = cirno.manifest.apps.len();
-        println!("Found {len} applications:");
-        for (i, app) in cirno.manifest.apps.iter().enumerate() {
-            let prefix = if i == len - 1 { "└" } else { "├" };
-            println!("{}── {}\t{}", prefix, app.id, app.name);
-            for (j, backup) in app.backups.iter().enumerate() {
-                let prefix = if j == app.backups.len() - 1 { "└" } else { "├" };
-                println!("    {}── {}", prefix, backup.id);
+        if format == Format::Tree {
+            let len = entries.len();
+            println!("Found {len} applications:");
+            for (i, Entry { app, .. }) in entries.iter().enumerate() {
+                let prefix = if i == len - 1 { "└" } else { "├" };
+                let labels = format_labels(&app.tags, &app.aliases);
+                match labels.is_empty() {
+                    true => println!("{}── {}\t{}", prefix, app.id, app.name),
+                    false => println!("{}── {}\t{}\t[{}]", prefix, app.id, app.name, labels),
+                }
+                // only the selected backups are shown in the tree, while the counts and sizes cover every backup
+                let backups = app
+                    .backups
+                    .iter()
+                    .filter(|backup| is_selected(&self.tags, &backup.tags, &backup.aliases))
+                    .collect::<Vec<_>>();
+                for (j, backup) in backups.iter().enumerate() {
+                    let prefix = if j == backups.len() - 1 { "└" } else { "├" };
+                    let labels = format_labels(&backup.tags, &backup.aliases);
+                    match labels.is_empty() {
+                        true => println!("    {}── {}", prefix, backup.id),
+                        false => println!("    {}── {}\t[{}]", prefix, backup.id, labels),
+                    }
+                }
             }
+            return Ok(());
+        }
+
+        let mut headers = vec![
+            "ID",
+            "NAME",
+            "YARN",
+            "LINKER",
+            "SIZE",
+            "SHARED",
+            "BACKUPS",
+            "BACKUP SIZE",
+            "CREATED",
+        ];
+        if daemon.running {
+            headers.push("STATUS");
         }
+        headers.push("TAGS");
+        let rows = entries
+            .iter()
+            .map(|entry| {
+                let app = &entry.app;
+                let id = app.id.to_string();
+                let mut row = vec![
+                    id[..8].to_string(),
+                    app.name.clone(),
+                    entry.yarn_version.clone().unwrap_or_else(|| "-".to_string()),
+                    entry.linker.clone().unwrap_or_else(|| "-".to_string()),
+                    format_size(entry.usage.files + entry.usage.exclusive_store),
+                    format_size(entry.usage.shared_store),
+                    app.backups.len().to_string(),
+                    format_size(entry.usage.backups),
+                    format_created(&app.created),
+                ];
+                if let Some(status) = &entry.status {
+                    row.push(format_status(status));
+                }
+                row.push(format_labels(&app.tags, &app.aliases));
+                row
+            })
+            .collect::<Vec<_>>();
+        print_table(&headers, &rows);
         Ok(())
     }
 }
@@ -1,37 +1,87 @@
 use std::path::PathBuf;
 use std::process::ExitCode;
 
-use anyhow::Result;
-use cirno_core::{Cirno, OpenError};
+use anyhow::{Result, anyhow};
+use cirno_core::{CancellationToken, Cirno, OpenError};
 use clap::{Args, Parser, Subcommand};
 use owo_colors::OwoColorize;
+use uuid::Uuid;
 
+mod backup;
+mod clone;
+mod config;
+mod create;
+mod du;
+mod env;
+mod export;
 mod gc;
+mod import;
+mod info;
 mod init;
+mod inspect;
+mod keys;
+mod licenses;
 mod list;
+mod migrate_cache;
+mod progress;
+mod remove;
+mod restore;
+mod runtime;
+mod sbom;
+mod tag;
+mod untag;
+mod update;
+mod verify;
+mod yarn;
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     Init(init::Init),
+    #[command(alias = "new")]
+    Create(EnvCommand<create::Create>),
     #[command(alias = "prune")]
     Gc(EnvCommand<gc::Gc>),
     #[command(alias = "ls", alias = "tree")]
     List(EnvCommand<list::List>),
+    Export(EnvCommand<export::Export>),
+    Import(EnvCommand<import::Import>),
+    Clone(EnvCommand<clone::Clone>),
+    #[command(alias = "rm")]
+    Remove(EnvCommand<remove::Remove>),
+    Backup(EnvCommand<backup::Backup>),
+    Restore(EnvCommand<restore::Restore>),
+    Tag(EnvCommand<tag::Tag>),
+    Untag(EnvCommand<untag::Untag>),
+    Yarn(EnvCommand<yarn::Yarn>),
+    Inspect(inspect::Inspect),
+    Info(EnvCommand<info::Info>),
+    Du(EnvCommand<du::Du>),
+    Licenses(EnvCommand<licenses::Licenses>),
+    Sbom(EnvCommand<sbom::Sbom>),
+    MigrateCache(EnvCommand<migrate_cache::MigrateCache>),
+    Verify(EnvCommand<verify::Verify>),
+    Runtime(EnvCommand<runtime::Runtime>),
+    Env(EnvCommand<env::Env>),
+    Config(EnvCommand<config::Config>),
+    Keys(EnvCommand<keys::Keys>),
+    Update(EnvCommand<update::Update>),
 }
 
 #[derive(Debug, Args)]
 struct EnvCommand<T: EnvArgs> {
     #[command(flatten)]
     inner: T,
-    #[arg(long, default_value = ".")]
+    #[arg(long, global = true, default_value = ".")]
     pub cwd: PathBuf,
-    #[arg(long, default_value_t = false)]
+    #[arg(long, global = true, default_value_t = false)]
     pub verbose: bool,
+    #[arg(long, global = true, value_enum, default_value_t, help = "Progress output on stderr")]
+    pub progress: progress::ProgressFormat,
 }
 
 impl<T: EnvArgs> EnvCommand<T> {
     async fn main(self) -> ExitCode {
-        let cirno = match Cirno::open(&self.cwd).await {
+        let mut cirno = match Cirno::open(&self.cwd).await {
             Ok(cirno) => cirno,
             Err(OpenError::Empty) => {
                 println!(
@@ -66,8 +116,16 @@ impl<T: EnvArgs> EnvCommand<T> {
                 return ExitCode::FAILURE;
             }
         };
+        cirno.set_reporter(self.progress.reporter());
+        let cancel = CancellationToken::new();
+        cirno.set_cancellation_token(cancel.clone());
+        tokio::spawn(cancel_on_interrupt(cancel.clone()));
         match self.inner.main(cirno).await {
             Ok(()) => ExitCode::SUCCESS,
+            Err(_) if cancel.is_cancelled() => {
+                println!("{:>12} Operation cancelled.", "Cancelled".bold().bright_yellow());
+                ExitCode::from(130)
+            }
             Err(error) => {
                 if self.verbose {
                     println!("{:>12} Operation failed: {:?}", "Error".bold().bright_red(), error);
@@ -85,10 +143,102 @@ impl<T: EnvArgs> EnvCommand<T> {
     }
 }
 
+/// Cancels the running operation on Ctrl-C, so that it can clean up after itself. A second Ctrl-C exits immediately.
+async fn cancel_on_interrupt(cancel: CancellationToken) {
+    if tokio::signal::ctrl_c().await.is_ok() {
+        cancel.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    }
+}
+
 trait EnvArgs: Args {
     async fn main(self, cirno: Cirno) -> Result<()>;
 }
 
+/// Resolves the instance argument of a command, see [`cirno_core::resolve`]. The argument is optional so that a missing
+/// instance gets its own message.
+fn resolve_id(cirno: &Cirno, id: Option<&str>, command: &str) -> Result<Uuid> {
+    Ok(cirno.resolve(require(id, "instance ID", command)?)?)
+}
+
+/// Returns a positional argument. Since the instance ID is optional, so that its absence gets a clear message, clap
+/// cannot require the positional arguments which follow it either.
+fn require<T>(value: Option<T>, name: &str, command: &str) -> Result<T> {
+    value.ok_or_else(|| anyhow!("Missing {}. See `cirno {} --help` for usage.", name, command))
+}
+
+fn format_size(size: u64) -> String {
+    let units = ["B", "KB", "MB", "GB"];
+    let mut size = size as f64;
+    for unit in &units[..units.len() - 1] {
+        if size <= 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} {}", size, units[units.len() - 1])
+}
+
+/// Prints rows aligned in columns. Cells may contain colors, which are not counted in the width of columns.
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let width = |cell: &str| strip_ansi(cell).chars().count();
+    let widths = (0..headers.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| width(&row[i]))
+                .chain([headers[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let line = headers
+        .iter()
+        .zip(&widths)
+        .map(|(header, width)| format!("{:<width$}", header, width = width))
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{}", line.trim_end().bold());
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &column)| format!("{}{}", cell, " ".repeat(column - width(cell))))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Prints a labelled value of a report, with labels aligned on the right.
+fn print_field(label: &str, value: impl std::fmt::Display) {
+    println!("{} {}", format!("{:>12}", label).bold(), value);
+}
+
+/// Formats the tags and aliases of an instance, aliases first and in bold, or an empty string if it has none.
+fn format_labels(tags: &[String], aliases: &[String]) -> String {
+    aliases
+        .iter()
+        .map(|alias| alias.bold().to_string())
+        .chain(tags.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut output = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
 #[derive(Debug, Parser)]
 struct Cli {
     #[command(subcommand)]
@@ -99,8 +249,30 @@ impl Cli {
     async fn main(self) -> ExitCode {
         match self.command {
             Commands::Init(args) => args.main().await,
+            Commands::Create(args) => args.main().await,
             Commands::Gc(args) => args.main().await,
             Commands::List(args) => args.main().await,
+            Commands::Export(args) => args.main().await,
+            Commands::Import(args) => args.main().await,
+            Commands::Clone(args) => args.main().await,
+            Commands::Remove(args) => args.main().await,
+            Commands::Backup(args) => args.main().await,
+            Commands::Restore(args) => args.main().await,
+            Commands::Tag(args) => args.main().await,
+            Commands::Untag(args) => args.main().await,
+            Commands::Yarn(args) => args.main().await,
+            Commands::Inspect(args) => args.main().await,
+            Commands::Info(args) => args.main().await,
+            Commands::Du(args) => args.main().await,
+            Commands::Licenses(args) => args.main().await,
+            Commands::Sbom(args) => args.main().await,
+            Commands::MigrateCache(args) => args.main().await,
+            Commands::Verify(args) => args.main().await,
+            Commands::Runtime(args) => args.main().await,
+            Commands::Env(args) => args.main().await,
+            Commands::Config(args) => args.main().await,
+            Commands::Keys(args) => args.main().await,
+            Commands::Update(args) => args.main().await,
         }
     }
 }
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::cache::MigrateCacheOptions;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct MigrateCache {
+    #[clap(help = "Application ID")]
+    id: Option<String>,
+    #[clap(long, help = "Only migrate entries from this cache key")]
+    from: Option<String>,
+    #[clap(
+        long,
+        help = "Migrate entries to this cache key and update the lockfile, instead of the cache key of the lockfile"
+    )]
+    to: Option<String>,
+    #[clap(long, help = "Output in JSON format")]
+    json: bool,
+}
+
+impl EnvArgs for MigrateCache {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "migrate-cache")?;
+        let options = MigrateCacheOptions {
+            from: self.from,
+            to: self.to,
+        };
+        let report = cirno.migrate_cache(&id, &options).await?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        for prefix in &report.missing {
+            println!("{:>12} Cache not found: {}", "Warning".bold().bright_yellow(), prefix);
+        }
+        println!(
+            "{:>12} Migrated cache to key {}: {} renamed, {} copied, {} missing.",
+            "Success".bold().bright_green(),
+            report.cache_key,
+            report.renamed,
+            report.copied,
+            report.missing.len()
+        );
+        if report.lockfile_updated {
+            println!(
+                "{:>12} Updated the cache key and checksums of yarn.lock.",
+                "Info".bold().bright_blue()
+            );
+        }
+        Ok(())
+    }
+}
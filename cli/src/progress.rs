@@ -0,0 +1,133 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cirno_core::report::{Event, Phase, Reporter};
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use owo_colors::OwoColorize;
+
+use crate::format_size;
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum ProgressFormat {
+    /// Progress bars, shown when stderr is a terminal.
+    #[default]
+    Bar,
+    /// One JSON event per line on stderr.
+    Json,
+}
+
+impl ProgressFormat {
+    pub fn reporter(self) -> Arc<dyn Reporter> {
+        match self {
+            ProgressFormat::Bar => Arc::new(BarReporter::default()),
+            ProgressFormat::Json => Arc::new(JsonReporter),
+        }
+    }
+}
+
+fn phase_label(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Download => "Downloading",
+        Phase::Clone => "Cloning",
+        Phase::Pack => "Packing",
+        Phase::Write => "Writing",
+        Phase::Unpack => "Unpacking",
+        Phase::Verify => "Verifying",
+        Phase::Unbundle => "Unbundling",
+        Phase::Install => "Installing",
+        Phase::Backup => "Backing up",
+        Phase::Restore => "Restoring",
+        Phase::Collect => "Collecting",
+    }
+}
+
+/// What the bar of a phase measures.
+enum Unit {
+    Bytes,
+    Files,
+    /// Neither total is known, so the bar is a spinner.
+    None,
+}
+
+#[derive(Default)]
+struct BarReporter {
+    current: Mutex<Option<(ProgressBar, Unit)>>,
+}
+
+impl BarReporter {
+    /// Prints a line above the current bar.
+    fn println(&self, line: String) {
+        match &*self.current.lock().unwrap() {
+            Some((bar, _)) => bar.suspend(|| eprintln!("{}", line)),
+            None => eprintln!("{}", line),
+        }
+    }
+}
+
+impl Reporter for BarReporter {
+    fn report(&self, event: &Event) {
+        match event {
+            Event::PhaseStarted {
+                phase,
+                total_files,
+                total_bytes,
+            } => {
+                let (bar, unit, template) = match (total_bytes, total_files) {
+                    (Some(total), _) => (
+                        ProgressBar::new(*total),
+                        Unit::Bytes,
+                        "{prefix:>12.cyan.bold} [{bar:30}] {bytes}/{total_bytes}",
+                    ),
+                    (None, Some(total)) => (
+                        ProgressBar::new(*total),
+                        Unit::Files,
+                        "{prefix:>12.cyan.bold} [{bar:30}] {pos}/{len} files",
+                    ),
+                    (None, None) => (
+                        ProgressBar::new_spinner(),
+                        Unit::None,
+                        "{prefix:>12.cyan.bold} {spinner} {msg}",
+                    ),
+                };
+                bar.set_draw_target(ProgressDrawTarget::stderr());
+                bar.set_style(ProgressStyle::with_template(template).unwrap().progress_chars("=> "));
+                bar.set_prefix(phase_label(*phase));
+                if let Unit::None = unit {
+                    bar.enable_steady_tick(Duration::from_millis(100));
+                }
+                if let Some((previous, _)) = self.current.lock().unwrap().replace((bar, unit)) {
+                    previous.finish_and_clear();
+                }
+            }
+            Event::Progress { files, bytes, .. } => {
+                if let Some((bar, unit)) = &*self.current.lock().unwrap() {
+                    match unit {
+                        Unit::Bytes => bar.set_position(*bytes),
+                        Unit::Files => bar.set_position(*files),
+                        Unit::None => bar.set_message(format!("{} files, {}", files, format_size(*bytes))),
+                    }
+                }
+            }
+            Event::PhaseFinished { .. } => {
+                if let Some((bar, _)) = self.current.lock().unwrap().take() {
+                    bar.finish_and_clear();
+                }
+            }
+            Event::Warning { message } => {
+                self.println(format!("{:>12} {}", "Warning".bold().bright_yellow(), message));
+            }
+            Event::YarnOutput { line, .. } => self.println(format!("{:>12} {}", "yarn".dimmed(), line)),
+        }
+    }
+}
+
+struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, event: &Event) {
+        if let Ok(line) = serde_json::to_string(event) {
+            eprintln!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::instance::RemoveOptions;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Remove {
+    #[clap(help = "Instance ID, either an application or a backup")]
+    id: Option<String>,
+    #[clap(
+        short,
+        long,
+        help = "Also remove the earlier backups, or every backup of an application"
+    )]
+    recursive: bool,
+    #[clap(short, long, help = "Also remove tagged backups, or replace a tagged head instance")]
+    force: bool,
+}
+
+impl EnvArgs for Remove {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "remove")?;
+        let options = RemoveOptions {
+            recursive: self.recursive,
+            force: self.force,
+        };
+        let report = cirno.remove(&id, &options).await?;
+        if let Some(backup) = report.restored {
+            println!(
+                "{:>12} Backup {} is now the head instance of {}.",
+                "Restored".bold().bright_green(),
+                backup,
+                id
+            );
+        }
+        let removed = report.removed.iter().map(ToString::to_string).collect::<Vec<_>>();
+        match removed.as_slice() {
+            [id] => println!("{:>12} Removed instance {}.", "Success".bold().bright_green(), id),
+            ids => println!(
+                "{:>12} Removed {} instances: {}.",
+                "Success".bold().bright_green(),
+                ids.len(),
+                ids.join(", ")
+            ),
+        }
+        Ok(())
+    }
+}
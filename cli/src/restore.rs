@@ -0,0 +1,32 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Restore {
+    #[clap(help = "Backup ID")]
+    backup: Option<String>,
+    #[clap(
+        short,
+        long,
+        help = "Also replace a tagged head instance and remove later backups which are tagged"
+    )]
+    force: bool,
+}
+
+impl EnvArgs for Restore {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let backup = resolve_id(&cirno, self.backup.as_deref(), "restore")?;
+        let id = cirno.restore(&backup, self.force).await?;
+        println!(
+            "{:>12} Restored application {} to backup {}.",
+            "Success".bold().bright_green(),
+            id,
+            backup
+        );
+        Ok(())
+    }
+}
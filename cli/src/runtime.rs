@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use cirno_core::Cirno;
+use clap::{Args, Subcommand};
+use owo_colors::OwoColorize;
+use semver::Version;
+
+use crate::EnvArgs;
+
+#[derive(Debug, Args)]
+pub struct Runtime {
+    #[command(subcommand)]
+    command: RuntimeCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum RuntimeCommand {
+    /// Install a runtime from an official Node.js archive (.tar.gz or .zip)
+    Install {
+        #[clap(help = "Path to the archive")]
+        archive: PathBuf,
+    },
+    /// List the installed runtimes
+    #[command(alias = "ls")]
+    List {
+        #[clap(long, help = "Output in JSON format")]
+        json: bool,
+    },
+    /// Remove an installed runtime
+    #[command(alias = "rm")]
+    Remove {
+        #[clap(help = "Runtime version")]
+        version: Version,
+        #[clap(long, help = "Remove the runtime even if applications use it")]
+        force: bool,
+    },
+}
+
+impl EnvArgs for Runtime {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        match self.command {
+            RuntimeCommand::Install { archive } => {
+                let runtime = cirno.install_runtime(&archive).await?;
+                println!(
+                    "{:>12} Installed Node.js {}.",
+                    "Success".bold().bright_green(),
+                    runtime.version
+                );
+            }
+            RuntimeCommand::List { json } => {
+                let runtimes = cirno.runtimes().await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&runtimes)?);
+                } else {
+                    for runtime in runtimes {
+                        println!("{}", runtime.version);
+                    }
+                }
+            }
+            RuntimeCommand::Remove { version, force } => {
+                cirno.remove_runtime(&version, force).await?;
+                println!("{:>12} Removed Node.js {}.", "Success".bold().bright_green(), version);
+            }
+        }
+        Ok(())
+    }
+}
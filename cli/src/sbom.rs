@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::sbom::SbomFormat;
+use clap::Args;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Sbom {
+    #[clap(help = "Instance ID")]
+    id: Option<String>,
+    #[clap(long, help = "SBOM format (cyclonedx-json, spdx-json)")]
+    format: SbomFormat,
+    #[clap(short, long, help = "Write to a file instead of stdout")]
+    output: Option<PathBuf>,
+}
+
+impl EnvArgs for Sbom {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "sbom")?;
+        let sbom = serde_json::to_string_pretty(&cirno.sbom(&id, self.format).await?)?;
+        match self.output {
+            Some(path) => std::fs::write(path, sbom)?,
+            None => println!("{sbom}"),
+        }
+        Ok(())
+    }
+}
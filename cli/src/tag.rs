@@ -0,0 +1,35 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, require, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Tag {
+    #[clap(help = "Instance ID, either an application or a backup")]
+    id: Option<String>,
+    #[clap(help = "Tags to add")]
+    tags: Vec<String>,
+    #[clap(
+        long,
+        help = "Add aliases, which name a single instance of the environment, instead of tags"
+    )]
+    alias: bool,
+}
+
+impl EnvArgs for Tag {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "tag")?;
+        require(Some(&self.tags).filter(|tags| !tags.is_empty()), "tags", "tag")?;
+        cirno.tag(&id, &self.tags, self.alias).await?;
+        println!(
+            "{:>12} Added {} {} to instance {}.",
+            "Success".bold().bright_green(),
+            if self.alias { "aliases" } else { "tags" },
+            self.tags.join(", "),
+            id
+        );
+        Ok(())
+    }
+}
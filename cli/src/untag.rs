@@ -0,0 +1,29 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, require, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Untag {
+    #[clap(help = "Instance ID, either an application or a backup")]
+    id: Option<String>,
+    #[clap(help = "Tags or aliases to remove")]
+    tags: Vec<String>,
+}
+
+impl EnvArgs for Untag {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "untag")?;
+        require(Some(&self.tags).filter(|tags| !tags.is_empty()), "tags", "untag")?;
+        cirno.untag(&id, &self.tags).await?;
+        println!(
+            "{:>12} Removed {} from instance {}.",
+            "Success".bold().bright_green(),
+            self.tags.join(", "),
+            id
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use cirno_core::bundle::UpdateReport;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::import::print_signature;
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Update {
+    #[clap(help = "Application imported from a git repository")]
+    id: Option<String>,
+}
+
+impl EnvArgs for Update {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "update")?;
+        match cirno.update(&id).await? {
+            UpdateReport::UpToDate { commit } => println!(
+                "{:>12} Instance {} is up to date (commit {}).",
+                "Success".bold().bright_green(),
+                id,
+                commit
+            ),
+            UpdateReport::Updated {
+                previous,
+                commit,
+                backup,
+                signature,
+            } => {
+                print_signature(&signature);
+                println!(
+                    "{:>12} Updated instance {} from commit {} to {}, previous head backed up as {}.",
+                    "Success".bold().bright_green(),
+                    id,
+                    previous,
+                    commit,
+                    backup
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use cirno_core::Cirno;
+use clap::Args;
+use owo_colors::OwoColorize;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Verify {
+    #[clap(help = "Application ID")]
+    id: Option<String>,
+}
+
+impl EnvArgs for Verify {
+    async fn main(self, mut cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "verify")?;
+        match cirno.verify_release(&id).await? {
+            Some(hash) => println!("{:>12} Yarn release matches {}.", "Success".bold().bright_green(), hash),
+            None => println!(
+                "{:>12} No release hash is declared in packageManager.",
+                "Skipped".bold().bright_yellow()
+            ),
+        }
+        Ok(())
+    }
+}
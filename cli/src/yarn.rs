@@ -0,0 +1,31 @@
+use anyhow::{Result, anyhow, bail};
+use cirno_core::Cirno;
+use clap::Args;
+
+use crate::{EnvArgs, resolve_id};
+
+#[derive(Debug, Args)]
+pub struct Yarn {
+    #[clap(help = "Application ID")]
+    id: Option<String>,
+    #[clap(last = true, help = "Arguments passed to yarn")]
+    args: Vec<String>,
+}
+
+impl EnvArgs for Yarn {
+    async fn main(self, cirno: Cirno) -> Result<()> {
+        let id = resolve_id(&cirno, self.id.as_deref(), "yarn")?;
+        let app = cirno.get(&id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        if app.id != id {
+            bail!("Cannot run yarn in a base instance. Restore or clone it first.");
+        }
+        let status = cirno
+            .yarn(&cirno.cwd.join("apps").join(id.to_string()), &self.args)
+            .await?;
+        // the exit code of yarn is passed through, as if it was run directly
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        Ok(())
+    }
+}
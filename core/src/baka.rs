@@ -0,0 +1,382 @@
+//! Backup archives.
+//!
+//! All backups of an application are stored in a single brotli-compressed tarball `baka/<id>.tar.br`, where the
+//! content of each backup instance lives under a top-level folder named after its ID.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use brotli::{CompressorWriter, Decompressor};
+use tar::{Archive, Builder, HeaderMode};
+use uuid::Uuid;
+
+use crate::bundle::append_file;
+use crate::report::{Phase, PhaseProgress};
+use crate::{App, Backup, Cirno, Meta, fs};
+
+const BUFFER_SIZE: usize = 4096;
+const BROTLI_QUALITY: u32 = 9;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Debug, Default)]
+pub struct BackupOptions {
+    /// ID of the backup instance, generated if not set.
+    pub id: Option<Uuid>,
+    /// Kind of backup, eg. `manual` or `delta` for backups taken before applying a delta.
+    pub r#type: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Extracts the content of backup `id` from `archive` into `dest`, reporting each file extracted.
+pub fn extract(archive: &Path, id: &Uuid, dest: &Path, progress: &PhaseProgress) -> Result<()> {
+    let file = File::open(archive).with_context(|| format!("Failed to open backup archive: {}", archive.display()))?;
+    let mut tar = Archive::new(Decompressor::new(file, BUFFER_SIZE));
+    let prefix = id.to_string();
+    std::fs::create_dir_all(dest).with_context(|| format!("Failed to create directory: {}", dest.display()))?;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut components = path.components();
+        if components.next() != Some(Component::Normal(prefix.as_ref())) {
+            continue;
+        }
+        let mut target = PathBuf::from(dest);
+        for component in components {
+            match component {
+                Component::Normal(part) => target.push(part),
+                Component::CurDir => {}
+                _ => bail!("Invalid entry in backup archive: {}", path.display()),
+            }
+        }
+        if target == dest {
+            continue;
+        }
+        entry
+            .unpack(&target)
+            .with_context(|| format!("Failed to extract {}", path.display()))?;
+        if entry.header().entry_type().is_file() {
+            progress.advance(1, entry.size())?;
+        }
+    }
+    Ok(())
+}
+
+/// Recursively adds the content of `root` to a tarball under `base`, skipping the `node_modules` folders if requested.
+fn append_dir<W: Write>(
+    tar: &mut Builder<W>,
+    root: &Path,
+    base: &Path,
+    skip_node_modules: bool,
+    progress: &PhaseProgress,
+) -> Result<()> {
+    let mut entries = std::fs::read_dir(root)
+        .with_context(|| format!("Failed to read directory: {}", root.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let is_dir = entry.file_type()?.is_dir();
+        if is_dir && skip_node_modules && entry.file_name() == "node_modules" {
+            continue;
+        }
+        let name = base.join(entry.file_name());
+        if entry.file_type()?.is_file() {
+            append_file(tar, &entry.path(), &name, HeaderMode::Complete, progress)?;
+            continue;
+        }
+        tar.append_path_with_name(entry.path(), &name)
+            .with_context(|| format!("Failed to pack {}", entry.path().display()))?;
+        if is_dir {
+            append_dir(tar, &entry.path(), &name, skip_node_modules, progress)?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates a backup archive at `path`.
+fn create_archive(path: &Path) -> Result<Builder<CompressorWriter<BufWriter<File>>>> {
+    let file = File::create(path).with_context(|| format!("Failed to create file: {}", path.display()))?;
+    let writer = CompressorWriter::new(BufWriter::new(file), BUFFER_SIZE, BROTLI_QUALITY, BROTLI_WINDOW);
+    let mut tar = Builder::new(writer);
+    tar.follow_symlinks(false);
+    Ok(tar)
+}
+
+fn finish_archive(tar: Builder<CompressorWriter<BufWriter<File>>>) -> Result<()> {
+    let mut writer = tar.into_inner()?;
+    writer.flush()?;
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+/// Copies the entries of `archive` into `tar`, for the backups whose ID is accepted by `filter`.
+fn copy_backups<W: Write>(
+    tar: &mut Builder<W>,
+    archive: &Path,
+    filter: impl Fn(&str) -> bool,
+    progress: &PhaseProgress,
+) -> Result<()> {
+    let file = File::open(archive).with_context(|| format!("Failed to open backup archive: {}", archive.display()))?;
+    let mut backups = Archive::new(Decompressor::new(file, BUFFER_SIZE));
+    for entry in backups.entries()? {
+        progress.check()?;
+        let mut entry = entry?;
+        let mut header = entry.header().clone();
+        let path = entry.path()?.into_owned();
+        match path.components().next() {
+            Some(Component::Normal(name)) if filter(&name.to_string_lossy()) => {}
+            _ => continue,
+        }
+        match entry.link_name()? {
+            Some(target) => {
+                let target = target.into_owned();
+                tar.append_link(&mut header, &path, target)?;
+            }
+            None => tar.append_data(&mut header, &path, &mut entry)?,
+        }
+    }
+    Ok(())
+}
+
+/// Adds the content of `src` to `archive` as backup `id`.
+///
+/// Brotli streams cannot be appended to, so the existing backups are copied with the new one into `temp`, which then
+/// replaces the archive. Only the files of the new backup are reported.
+pub fn append(
+    archive: &Path,
+    temp: &Path,
+    id: &Uuid,
+    src: &Path,
+    skip_node_modules: bool,
+    progress: &PhaseProgress,
+) -> Result<()> {
+    let mut tar = create_archive(temp)?;
+    if archive.exists() {
+        copy_backups(&mut tar, archive, |_| true, progress)?;
+    }
+    append_dir(&mut tar, src, Path::new(&id.to_string()), skip_node_modules, progress)?;
+    finish_archive(tar)?;
+    std::fs::rename(temp, archive).with_context(|| format!("Failed to write backup archive: {}", archive.display()))
+}
+
+/// Writes the backups of `archive` listed in `keep` into a new archive at `temp`, which is meant to replace it.
+pub fn retain(archive: &Path, temp: &Path, keep: &[Uuid], progress: &PhaseProgress) -> Result<()> {
+    let keep = keep.iter().map(Uuid::to_string).collect::<HashSet<_>>();
+    let mut tar = create_archive(temp)?;
+    copy_backups(&mut tar, archive, |name| keep.contains(name), progress)?;
+    finish_archive(tar)
+}
+
+/// Returns the uncompressed size of the files of each backup of `archive`, by backup ID.
+pub fn backup_sizes(archive: &Path) -> Result<HashMap<String, u64>> {
+    let file = File::open(archive).with_context(|| format!("Failed to open backup archive: {}", archive.display()))?;
+    let mut tar = Archive::new(Decompressor::new(file, BUFFER_SIZE));
+    let mut sizes = HashMap::<String, u64>::new();
+    for entry in tar.entries()? {
+        let entry = entry?;
+        if let Some(Component::Normal(name)) = entry.path()?.components().next() {
+            *sizes.entry(name.to_string_lossy().to_string()).or_default() += entry.size();
+        }
+    }
+    Ok(sizes)
+}
+
+impl Cirno {
+    /// Backs up the head instance of an application, and returns the ID of the backup instance.
+    ///
+    /// The `node_modules` folders of applications using the `node-modules` or `pnpm` linker are not backed up, since
+    /// they can be rebuilt from the cache.
+    pub async fn backup(&mut self, id: &Uuid, options: &BackupOptions) -> Result<Uuid> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        if &app.id != id {
+            bail!("Cannot backup a base instance.");
+        }
+        let backup_id = options.id.unwrap_or_else(Uuid::new_v4);
+        if self.get(&backup_id).is_some() {
+            bail!("Instance {} already exists.", backup_id);
+        }
+        let src = self.cwd.join("apps").join(id.to_string());
+        let meta = Meta::load(&src).await?;
+        let skip_node_modules = self.node_linker(&meta.yarn_rc).await?.uses_node_modules();
+        let archive = self.cwd.join("baka").join(format!("{}.tar.br", id));
+        let temp = self.temp_dir().await?;
+        let progress = self.start_phase(Phase::Backup, None, None)?;
+        let (temp_file, append_progress) = (temp.path().with_extension("baka"), progress.clone());
+        let result = tokio::task::spawn_blocking(move || {
+            append(
+                &archive,
+                &temp_file,
+                &backup_id,
+                &src,
+                skip_node_modules,
+                &append_progress,
+            )
+        })
+        .await?;
+        progress.finish();
+        temp.remove().await?;
+        result?;
+
+        self.state
+            .entry(id.to_string())
+            .or_default()
+            .insert(backup_id.to_string(), meta);
+        let app = self
+            .manifest
+            .apps
+            .iter_mut()
+            .find(|app| &app.id == id)
+            .ok_or_else(|| anyhow!("Application {} not found.", id))?;
+        app.backups.push(Backup {
+            id: backup_id,
+            r#type: Some(options.r#type.clone().unwrap_or_else(|| "manual".to_string())),
+            message: options.message.clone(),
+            created: crate::get_timestamp(),
+            tags: vec![],
+            aliases: vec![],
+        });
+        self.save().await?;
+        Ok(backup_id)
+    }
+
+    /// Restores an application to one of its backups, and returns the ID of the application.
+    ///
+    /// The head instance is replaced by the content of the backup, which is removed from the timeline along with every
+    /// later backup. The tags and aliases of the backup replace those of the head instance, which would otherwise
+    /// label different content. A head instance or later backups which are tagged are not discarded unless `force` is
+    /// set. The dependencies are installed again, see [`install`](Self::install).
+    pub async fn restore(&mut self, id: &Uuid, force: bool) -> Result<Uuid> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        if &app.id == id {
+            bail!("Cannot restore to a head instance.");
+        }
+        let app_id = app.id;
+        let index = app
+            .backups
+            .iter()
+            .position(|backup| &backup.id == id)
+            .ok_or_else(|| anyhow!("Backup {} not found.", id))?;
+        let keep = app.backups[..index].iter().map(|backup| backup.id).collect::<Vec<_>>();
+        let removed = app.backups[index..].iter().map(|backup| backup.id).collect::<Vec<_>>();
+        if !force {
+            self.check_untagged(&app_id, &[&[app_id], &removed[1..]].concat())?;
+        }
+        let snapshot = (app.clone(), self.state.get(&app_id.to_string()).cloned());
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let result = async {
+            let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+            self.clone(app, id, temp).await?;
+            self.install(temp).await?;
+            let archive_temp = self.write_retained(&app_id, &keep, temp).await?;
+            self.check_cancelled()?;
+            let head = self.cwd.join("apps").join(app_id.to_string());
+            let old = temp.with_extension("old");
+            fs::rename(&head, &old).await?;
+            let swapped = async {
+                fs::rename(temp, &head).await?;
+                if let Some(app) = self.manifest.apps.iter_mut().find(|app| app.id == app_id) {
+                    let backup = &mut app.backups[index];
+                    app.tags = std::mem::take(&mut backup.tags);
+                    app.aliases = std::mem::take(&mut backup.aliases);
+                }
+                self.commit_retained(&app_id, archive_temp, &removed).await
+            }
+            .await;
+            if swapped.is_err() {
+                // the former head is a sibling of the temporary directory, so it must be put back before the
+                // directory is removed
+                if tokio::fs::try_exists(&head).await? {
+                    fs::rename(&head, temp).await?;
+                }
+                fs::rename(&old, &head).await?;
+                self.rollback_app(snapshot).await;
+            }
+            swapped
+        }
+        .await;
+        temp_dir.remove().await?;
+        result?;
+        Ok(app_id)
+    }
+
+    /// Puts back the manifest entry and the backup metadata of an application after a failed operation, and saves
+    /// them on a best-effort basis, since saving may be what failed.
+    pub(crate) async fn rollback_app(&mut self, (app, metas): (App, Option<HashMap<String, Meta>>)) {
+        let key = app.id.to_string();
+        if let Some(entry) = self.manifest.apps.iter_mut().find(|entry| entry.id == app.id) {
+            *entry = app;
+        }
+        match metas {
+            Some(metas) => self.state.insert(key, metas),
+            None => self.state.remove(&key),
+        };
+        let _ = self.save().await;
+    }
+
+    /// Removes backups of an application, without touching its head instance.
+    pub(crate) async fn remove_backups(&mut self, app_id: &Uuid, ids: &[Uuid]) -> Result<()> {
+        let app = self
+            .manifest
+            .apps
+            .iter()
+            .find(|app| &app.id == app_id)
+            .ok_or_else(|| anyhow!("Application {} not found.", app_id))?;
+        let keep = app
+            .backups
+            .iter()
+            .map(|backup| backup.id)
+            .filter(|id| !ids.contains(id))
+            .collect::<Vec<_>>();
+        let temp_dir = self.temp_dir().await?;
+        let result = async {
+            let archive_temp = self.write_retained(app_id, &keep, temp_dir.path()).await?;
+            self.check_cancelled()?;
+            self.commit_retained(app_id, archive_temp, ids).await
+        }
+        .await;
+        temp_dir.remove().await?;
+        result
+    }
+
+    /// Writes the backups of `keep` into a new archive next to `temp`, and returns its path, or `None` if no backup
+    /// is kept.
+    async fn write_retained(&self, app_id: &Uuid, keep: &[Uuid], temp: &Path) -> Result<Option<PathBuf>> {
+        if keep.is_empty() {
+            return Ok(None);
+        }
+        let archive = self.cwd.join("baka").join(format!("{}.tar.br", app_id));
+        let archive_temp = temp.with_extension("baka");
+        let progress = self.start_phase(Phase::Restore, None, None)?;
+        let (keep, dest, retain_progress) = (keep.to_vec(), archive_temp.clone(), progress.clone());
+        tokio::task::spawn_blocking(move || retain(&archive, &dest, &keep, &retain_progress)).await??;
+        progress.finish();
+        Ok(Some(archive_temp))
+    }
+
+    /// Removes the backups of `removed` from the timeline, and replaces the archive of an application with the one
+    /// written by [`write_retained`](Self::write_retained).
+    ///
+    /// The manifest is saved first, so that a failure leaves at worst entries of the archive which are no longer
+    /// listed, never listed backups which are missing from the archive.
+    async fn commit_retained(&mut self, app_id: &Uuid, archive_temp: Option<PathBuf>, removed: &[Uuid]) -> Result<()> {
+        if let Some(app) = self.manifest.apps.iter_mut().find(|app| &app.id == app_id) {
+            app.backups.retain(|backup| !removed.contains(&backup.id));
+        }
+        if let Some(metas) = self.state.get_mut(&app_id.to_string()) {
+            for id in removed {
+                metas.remove(&id.to_string());
+            }
+        }
+        self.save().await?;
+        let archive = self.cwd.join("baka").join(format!("{}.tar.br", app_id));
+        match archive_temp {
+            Some(archive_temp) => fs::rename(archive_temp, &archive).await?,
+            None if tokio::fs::try_exists(&archive).await? => fs::remove_file(&archive).await?,
+            None => {}
+        }
+        Ok(())
+    }
+}
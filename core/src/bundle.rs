@@ -0,0 +1,562 @@
+use std::path::{Component, Path};
+use std::sync::LazyLock;
+
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::baka::BackupOptions;
+use crate::cleanup::Rollback;
+use crate::git::{self, GitSource};
+use crate::report::Phase;
+use crate::runtime::{BUNDLE_RUNTIME_DIR, RUNTIME_DIR};
+use crate::sbom::{self, SbomFormat};
+use crate::sign::{self, MANIFEST_FILE, SIGNATURE_FILE, SignatureStatus, TrustPolicy};
+use crate::yarn::{PackageManager, ReleaseHash};
+use crate::{App, Cirno, Meta, fs};
+
+mod descriptor;
+mod entry;
+mod format;
+mod inspect;
+mod oci;
+
+pub use descriptor::*;
+pub(crate) use entry::unpack_tar;
+pub use format::*;
+pub use inspect::*;
+pub use oci::OciOptions;
+
+/// Cache file of a bundle. Slugs end with a 10-char locator hash, followed by either the 10-char checksum (local
+/// cache) or the cache key (global cache).
+///
+/// Shared with the TypeScript import, see `tests/fixtures/bundle-cache-names.json`.
+pub static BUNDLE_CACHE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.+)-([0-9a-f]{10})-([0-9a-f]+)\.zip$").unwrap());
+
+#[derive(Debug, Default)]
+pub struct ExportOptions {
+    /// Pack the bundle into a file of this format instead of a directory.
+    pub format: Option<BundleFormat>,
+    /// Embed a software bill of materials in the bundle.
+    pub sbom: Option<SbomFormat>,
+    /// Embed the managed Node.js runtime of the application in the bundle.
+    pub runtime: bool,
+    /// Sign the bundle with the private key of this name, see [`sign`].
+    pub sign: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    /// ID of the new application, generated if not set.
+    pub id: Option<Uuid>,
+    /// Name of the new application, defaults to the name of the exported application, or the name of its
+    /// `package.json` for bundles without a descriptor.
+    pub name: Option<String>,
+    /// Branch, tag or commit to import from a git repository or bundle, `HEAD` if not set.
+    pub r#ref: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ImportReport {
+    pub id: Uuid,
+    /// Signature of the bundle, verified according to the trust policy of the environment.
+    pub signature: SignatureStatus,
+    /// Descriptor of the bundle, if it has one.
+    pub descriptor: Option<BundleDescriptor>,
+    /// Git repository the application was imported from, if any.
+    pub source: Option<GitSource>,
+}
+
+#[derive(Debug)]
+pub enum UpdateReport {
+    /// The recorded ref still points to the imported commit.
+    UpToDate { commit: String },
+    Updated {
+        previous: String,
+        commit: String,
+        /// Backup of the previous head.
+        backup: Uuid,
+        signature: SignatureStatus,
+    },
+}
+
+/// Unpacked bundle ready to be moved to the environment, see [`Cirno::prepare`].
+struct Prepared {
+    signature: SignatureStatus,
+    descriptor: Option<BundleDescriptor>,
+    release_hash: Option<ReleaseHash>,
+}
+
+/// Resolves a path read from a bundle, which must stay inside the bundle.
+pub(crate) fn join_relative(root: &Path, path: &str) -> Option<std::path::PathBuf> {
+    let path = Path::new(path);
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        .then(|| root.join(path))
+}
+
+/// Removes the `node_modules` folders of a bundle, which may contain artifacts built for another platform.
+fn remove_node_modules(root: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if entry.file_name() == "node_modules" {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            remove_node_modules(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+impl Cirno {
+    /// Turns an instance into a zero-install bundle: the yarn release and every cache file are copied into the bundle,
+    /// and the global cache is disabled.
+    async fn pack(&self, id: &Uuid, temp: &Path, options: &ExportOptions) -> Result<()> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        self.clone(app, id, temp).await?;
+        let mut meta = Meta::load(temp).await?;
+        let cache_files = meta.yarn_lock.get_cache_files()?;
+        let progress = self.start_phase(Phase::Pack, Some(cache_files.len() as u64 + 1), None)?;
+
+        // yarnPath
+        let package_manager = PackageManager::yarn(&meta.package.package_manager)?;
+        let releases = self.cwd.join("home/.yarn/releases");
+        package_manager.verify(&releases).await?;
+        let yarn_path = format!(".yarn/releases/{}", package_manager.release_name());
+        fs::create_dir_all(temp.join(".yarn/releases")).await?;
+        let size = fs::copy(releases.join(package_manager.release_name()), temp.join(&yarn_path)).await?;
+        progress.advance(1, size)?;
+        meta.yarn_rc.yarn_path = Some(yarn_path);
+
+        if options.runtime {
+            let runtime = self
+                .resolve_runtime(temp)
+                .await?
+                .ok_or_else(|| anyhow!("No managed runtime satisfies the requirement of instance {}.", id))?;
+            let dest = temp.join(BUNDLE_RUNTIME_DIR).join(runtime.version.to_string());
+            fs::create_dir_all(temp.join(BUNDLE_RUNTIME_DIR)).await?;
+            fs::copy_dir_all(&runtime.path, dest).await?;
+        }
+
+        // enableGlobalCache
+        let metadata = &meta.yarn_lock.metadata;
+        metadata.check_version()?;
+        fs::create_dir_all(temp.join(".yarn/cache")).await?;
+        let cache = self.load_cache().await?;
+        for (prefix, name) in meta.yarn_lock.find_cache_files(&cache)? {
+            let name = name.ok_or_else(|| anyhow!("Cache not found: {}", prefix))?;
+            let size = fs::copy(
+                self.cwd.join("home/.yarn/cache").join(name),
+                temp.join(".yarn/cache").join(name),
+            )
+            .await?;
+            progress.advance(1, size)?;
+        }
+        meta.yarn_rc.enable_global_cache = Some(false);
+        let original = tokio::fs::read_to_string(temp.join(".yarnrc.yml")).await.ok();
+        fs::write(temp.join(".yarnrc.yml"), meta.yarn_rc.to_yaml(original.as_deref())?).await?;
+
+        if let Some(format) = options.sbom {
+            let sbom = sbom::generate(&meta, format)?;
+            fs::write(temp.join(format.file_name()), serde_json::to_string_pretty(&sbom)?).await?;
+        }
+
+        let descriptor = BundleDescriptor::new(temp, &app.name, id, &meta).await?;
+        fs::write(temp.join(DESCRIPTOR_FILE), serde_json::to_string_pretty(&descriptor)?).await?;
+
+        // the signature covers every other file, so it must come last
+        if let Some(name) = &options.sign {
+            let key = self.load_signing_key(name).await?;
+            let temp = temp.to_path_buf();
+            tokio::task::spawn_blocking(move || sign::sign_dir(&temp, &key)).await??;
+        }
+        progress.finish();
+        Ok(())
+    }
+
+    /// Exports an instance as a bundle at `dest`, either a directory or a file of the given format.
+    pub async fn export(&self, id: &Uuid, dest: &Path, options: &ExportOptions) -> Result<()> {
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let result = async {
+            self.pack(id, temp, options).await?;
+            if let Some(format) = options.format {
+                let progress = self.start_phase(Phase::Write, None, None)?;
+                let (temp, dest, write_progress) = (temp.to_path_buf(), dest.to_path_buf(), progress.clone());
+                tokio::task::spawn_blocking(move || format.write(&temp, &dest, &write_progress)).await??;
+                progress.finish();
+            } else {
+                self.check_cancelled()?;
+                fs::rename(temp, dest).await?;
+            }
+            Ok(())
+        }
+        .await;
+        temp_dir.remove().await?;
+        result
+    }
+
+    /// Exports an instance as an OCI image layout tarball at `dest`, which can be loaded by `docker load` or
+    /// `podman load` without a registry.
+    ///
+    /// The bundle is split into layers, see [`oci::write`]. Identical cache files map to identical layers, which are
+    /// only stored once by container runtimes. Since the image has no base layer, the managed runtime of the
+    /// application is required, see [`ExportOptions::runtime`].
+    pub async fn export_oci(
+        &self,
+        id: &Uuid,
+        dest: &Path,
+        options: &ExportOptions,
+        oci_options: &OciOptions,
+    ) -> Result<()> {
+        if !options.runtime {
+            bail!(
+                "OCI images have no base layer, so they must embed the runtime of the application: export with `--runtime`."
+            );
+        }
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let result = async {
+            self.pack(id, temp, options).await?;
+            let meta = Meta::load(temp).await?;
+            let yarn_path = meta.yarn_rc.yarn_path.unwrap_or_default();
+            let tag = match &oci_options.tag {
+                Some(tag) => tag.clone(),
+                None => oci::default_tag(&meta.package.name),
+            };
+            let progress = self.start_phase(Phase::Write, None, None)?;
+            let (temp, dest, write_progress) = (temp.to_path_buf(), dest.to_path_buf(), progress.clone());
+            tokio::task::spawn_blocking(move || oci::write(&temp, &dest, &yarn_path, &tag, &write_progress)).await??;
+            progress.finish();
+            Ok(())
+        }
+        .await;
+        temp_dir.remove().await?;
+        result
+    }
+
+    /// Copies a bundle, either a directory or a file of any [`BundleFormat`], into `temp`.
+    ///
+    /// Git repositories and bundle files are checked out at `reference` instead, and their source is returned.
+    async fn unpack(&self, src: &Path, temp: &Path, reference: Option<&str>) -> Result<Option<GitSource>> {
+        if git::is_git_source(src) {
+            let progress = self.start_phase(Phase::Unpack, None, None)?;
+            let work = temp.with_extension("git");
+            let commit = git::checkout(src, reference, &work, temp, &progress).await;
+            if tokio::fs::try_exists(&work).await.unwrap_or_default() {
+                fs::remove_dir_all(&work).await?;
+            }
+            progress.finish();
+            return Ok(Some(GitSource {
+                repository: src.to_string_lossy().to_string(),
+                r#ref: reference.map(str::to_string),
+                commit: commit?,
+            }));
+        }
+        if reference.is_some() {
+            bail!("A ref can only be imported from a git repository or bundle.");
+        }
+        let metadata = fs::metadata(src).await?;
+        if metadata.is_dir() {
+            let progress = self.start_phase(Phase::Unpack, None, None)?;
+            fs::copy_dir_all(src, temp).await?;
+            progress.finish();
+        } else {
+            let progress = self.start_phase(Phase::Unpack, None, Some(metadata.len()))?;
+            let (src, temp, extract_progress) = (src.to_path_buf(), temp.to_path_buf(), progress.clone());
+            tokio::task::spawn_blocking(move || BundleFormat::detect(&src)?.extract(&src, &temp, &extract_progress))
+                .await??;
+            progress.finish();
+        }
+        Ok(None)
+    }
+
+    /// Verifies the signature of a bundle according to the trust policy, and removes the signature files which no
+    /// longer apply once the bundle is imported.
+    async fn verify_bundle(&self, temp: &Path) -> Result<SignatureStatus> {
+        let trust = self.trust();
+        let policy = trust.policy;
+        let status = match policy {
+            TrustPolicy::Off => SignatureStatus::Skipped,
+            _ => {
+                let temp = temp.to_path_buf();
+                tokio::task::spawn_blocking(move || sign::verify_dir(&temp, &trust)).await??
+            }
+        };
+        if policy == TrustPolicy::Warn {
+            match &status {
+                SignatureStatus::Unsigned => self.warn("Bundle is not signed."),
+                SignatureStatus::Untrusted { public_key } => {
+                    self.warn(format!("Bundle is signed by an untrusted key: {}", public_key))
+                }
+                SignatureStatus::Invalid { reason } => self.warn(format!("Invalid bundle signature: {}.", reason)),
+                SignatureStatus::Verified { .. } | SignatureStatus::Skipped => {}
+            }
+        }
+        if policy == TrustPolicy::Require {
+            match &status {
+                SignatureStatus::Verified { .. } | SignatureStatus::Skipped => {}
+                SignatureStatus::Unsigned => bail!("Bundle is not signed, which is required by the trust policy."),
+                SignatureStatus::Untrusted { public_key } => {
+                    bail!("Bundle is signed by an untrusted key: {}", public_key)
+                }
+                SignatureStatus::Invalid { reason } => bail!("Invalid bundle signature: {}.", reason),
+            }
+        }
+        for name in [MANIFEST_FILE, SIGNATURE_FILE] {
+            if tokio::fs::try_exists(temp.join(name)).await? {
+                fs::remove_file(temp.join(name)).await?;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Reverts [`pack`](Self::pack): the yarn release, runtimes and cache files of the bundle are moved to the
+    /// environment, and the global cache is enabled again.
+    ///
+    /// Returns the verified hash of the yarn release. The files added to the environment are recorded in `rollback`.
+    async fn unbundle(&self, temp: &Path, rollback: &mut Rollback) -> Result<Option<ReleaseHash>> {
+        let mut meta = Meta::load(temp).await?;
+        let progress = self.start_phase(Phase::Unbundle, None, None)?;
+
+        // yarnPath
+        let package_manager = PackageManager::yarn(&meta.package.package_manager)?;
+        let releases = self.cwd.join("home/.yarn/releases");
+        let release_path = releases.join(package_manager.release_name());
+        let release_hash = match meta.yarn_rc.yarn_path.take() {
+            Some(yarn_path) => {
+                let yarn_path = join_relative(temp, &yarn_path)
+                    .ok_or_else(|| anyhow!("Invalid yarnPath in bundle: {}", yarn_path))?;
+                // verify the bundled release before it replaces the shared one
+                let hash = package_manager.verify_file(&yarn_path).await?;
+                fs::create_dir_all(&releases).await?;
+                if !tokio::fs::try_exists(&release_path).await? {
+                    rollback.push(release_path.clone());
+                }
+                fs::rename(&yarn_path, &release_path).await?;
+                if tokio::fs::try_exists(temp.join(".yarn/releases")).await? {
+                    fs::remove_dir_all(temp.join(".yarn/releases")).await?;
+                }
+                hash
+            }
+            None => {
+                if !tokio::fs::try_exists(&release_path).await? {
+                    bail!(
+                        "Yarn release {} is not installed and the bundle does not include it.",
+                        package_manager.version
+                    );
+                }
+                package_manager.verify(&releases).await?
+            }
+        };
+
+        // bundled node runtime
+        let bundle_runtimes = temp.join(BUNDLE_RUNTIME_DIR);
+        if tokio::fs::try_exists(&bundle_runtimes).await? {
+            fs::create_dir_all(self.cwd.join(RUNTIME_DIR)).await?;
+            let mut dir = fs::read_dir(&bundle_runtimes).await?;
+            while let Some(entry) = dir.next_entry().await? {
+                let dest = self.cwd.join(RUNTIME_DIR).join(entry.file_name());
+                if !tokio::fs::try_exists(&dest).await? {
+                    rollback.push(dest.clone());
+                    fs::rename(entry.path(), dest).await?;
+                }
+            }
+            fs::remove_dir_all(&bundle_runtimes).await?;
+        }
+
+        // cacheFolder, enableGlobalCache
+        let metadata = &meta.yarn_lock.metadata;
+        metadata.check_version()?;
+        if meta.yarn_rc.enable_global_cache != Some(true) {
+            let cache_folder = meta.yarn_rc.cache_folder.as_deref().unwrap_or(".yarn/cache");
+            if let Some(cache_folder) = join_relative(temp, cache_folder)
+                && tokio::fs::try_exists(&cache_folder).await?
+            {
+                let cache_dir = self.cwd.join("home/.yarn/cache");
+                let mut dir = fs::read_dir(&cache_folder).await?;
+                while let Some(entry) = dir.next_entry().await? {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let Some(captures) = BUNDLE_CACHE_REGEX.captures(&name) else {
+                        continue;
+                    };
+                    let dest = cache_dir.join(format!("{}-{}-{}.zip", &captures[1], &captures[2], metadata.cache_key));
+                    let size = entry.metadata().await?.len();
+                    if !tokio::fs::try_exists(&dest).await? {
+                        rollback.push(dest.clone());
+                    }
+                    fs::rename(entry.path(), dest).await?;
+                    progress.advance(1, size)?;
+                }
+                fs::remove_dir_all(&cache_folder).await?;
+            }
+        }
+        meta.yarn_rc.cache_folder = None;
+        meta.yarn_rc.enable_global_cache = None;
+
+        // node_modules may contain artifacts built for another platform, so we always rebuild them
+        if self.node_linker(&meta.yarn_rc).await?.uses_node_modules() {
+            let temp = temp.to_path_buf();
+            tokio::task::spawn_blocking(move || remove_node_modules(&temp)).await??;
+        }
+
+        let original = tokio::fs::read_to_string(temp.join(".yarnrc.yml")).await.ok();
+        fs::write(temp.join(".yarnrc.yml"), meta.yarn_rc.to_yaml(original.as_deref())?).await?;
+        progress.finish();
+        Ok(release_hash)
+    }
+
+    /// Verifies an unpacked bundle, moves its yarn release, runtimes and cache files to the environment, and
+    /// installs it. The files added to the environment are recorded in `rollback`.
+    async fn prepare(&self, temp: &Path, rollback: &mut Rollback) -> Result<Prepared> {
+        let progress = self.start_phase(Phase::Verify, None, None)?;
+        let signature = self.verify_bundle(temp).await?;
+        let descriptor = read_descriptor(temp).await?;
+        if let Some(descriptor) = &descriptor {
+            descriptor.validate(temp, &Meta::load(temp).await?).await?;
+            fs::remove_file(temp.join(DESCRIPTOR_FILE)).await?;
+        }
+        progress.finish();
+        let release_hash = self.unbundle(temp, rollback).await?;
+        self.yarn_install(temp).await?;
+        Ok(Prepared {
+            signature,
+            descriptor,
+            release_hash,
+        })
+    }
+
+    /// Imports a bundle as a new application, and returns its ID.
+    ///
+    /// The bundle is either a directory or a file of any [`BundleFormat`], whose format is detected from its content.
+    /// It may also be a local git repository or a git bundle file, whose tree is imported at [`ImportOptions::r#ref`];
+    /// the source is then recorded so that the application can be updated, see [`update`](Self::update).
+    ///
+    /// Its signature is verified before anything is moved to the environment, and the import fails if the trust policy
+    /// requires a valid signature. The bundle is then checked against its descriptor, if any.
+    pub async fn import(&mut self, src: &Path, options: &ImportOptions) -> Result<ImportReport> {
+        let id = options.id.unwrap_or_else(Uuid::new_v4);
+        if self.get(&id).is_some() {
+            bail!("Instance {} already exists.", id);
+        }
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let mut rollback = Rollback::default();
+        let result = async {
+            let source = self.unpack(src, temp, options.r#ref.as_deref()).await?;
+            let prepared = self.prepare(temp, &mut rollback).await?;
+            let meta = Meta::load(temp).await?;
+            self.check_cancelled()?;
+            fs::rename(temp, self.cwd.join("apps").join(id.to_string())).await?;
+            self.manifest.apps.push(App {
+                id,
+                name: options
+                    .name
+                    .clone()
+                    .or_else(|| prepared.descriptor.as_ref().map(|descriptor| descriptor.name.clone()))
+                    .unwrap_or(meta.package.name),
+                created: crate::get_timestamp(),
+                backups: vec![],
+                release_hash: prepared.release_hash.as_ref().map(ReleaseHash::to_string),
+                env: Default::default(),
+                source: source.clone(),
+                tags: vec![],
+                aliases: vec![],
+            });
+            self.state.insert(id.to_string(), Default::default());
+            self.save().await?;
+            Ok(ImportReport {
+                id,
+                signature: prepared.signature,
+                descriptor: prepared.descriptor,
+                source,
+            })
+        }
+        .await;
+        if result.is_err() {
+            rollback.revert().await?;
+        }
+        temp_dir.remove().await?;
+        result
+    }
+
+    /// Re-imports an application from the git repository it was imported from, at the recorded ref.
+    ///
+    /// If the ref points to a new commit, the new tree replaces the head instance, which is backed up first.
+    pub async fn update(&mut self, id: &Uuid) -> Result<UpdateReport> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        if &app.id != id {
+            bail!("Cannot update a base instance.");
+        }
+        let source = app
+            .source
+            .clone()
+            .ok_or_else(|| anyhow!("Instance {} was not imported from a git repository.", id))?;
+        let head = self.cwd.join("apps").join(id.to_string());
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let mut rollback = Rollback::default();
+        let result = async {
+            let repository = Path::new(&source.repository);
+            let new_source = self
+                .unpack(repository, temp, source.r#ref.as_deref())
+                .await?
+                .ok_or_else(|| anyhow!("{} is no longer a git repository.", source.repository))?;
+            if new_source.commit == source.commit {
+                return Ok(UpdateReport::UpToDate {
+                    commit: source.commit.clone(),
+                });
+            }
+            let prepared = self.prepare(temp, &mut rollback).await?;
+
+            self.check_cancelled()?;
+            let options = BackupOptions {
+                r#type: Some("update".to_string()),
+                message: Some(format!("Before updating to {}", new_source.commit)),
+                ..Default::default()
+            };
+            let backup = self.backup(id, &options).await?;
+            let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+            let snapshot = (app.clone(), self.state.get(&id.to_string()).cloned());
+            let old = temp.with_extension("old");
+            fs::rename(&head, &old).await?;
+            let swapped = async {
+                fs::rename(temp, &head).await?;
+                if let Some(app) = self.manifest.apps.iter_mut().find(|app| &app.id == id) {
+                    app.release_hash = prepared.release_hash.as_ref().map(ReleaseHash::to_string);
+                    app.source = Some(new_source.clone());
+                }
+                self.save().await
+            }
+            .await;
+            if let Err(error) = swapped {
+                // the former head is a sibling of the temporary directory, so it must be put back before the
+                // directory is removed
+                if tokio::fs::try_exists(&head).await? {
+                    fs::rename(&head, temp).await?;
+                }
+                fs::rename(&old, &head).await?;
+                self.rollback_app(snapshot).await;
+                return Err(error);
+            }
+            Ok(UpdateReport::Updated {
+                previous: source.commit.clone(),
+                commit: new_source.commit,
+                backup,
+                signature: prepared.signature,
+            })
+        }
+        .await;
+        if result.is_err()
+            && let Err(error) = rollback.revert().await
+        {
+            self.warn(format!("Failed to revert the update: {:#}", error));
+        }
+        temp_dir.remove().await?;
+        result
+    }
+}
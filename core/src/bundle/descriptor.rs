@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{BundleFormat, join_relative};
+use crate::yarn::{PackageManager, ReleaseHash};
+use crate::{Meta, fs, runtime};
+
+/// Descriptor of a bundle, at the root of the bundle.
+pub const DESCRIPTOR_FILE: &str = "cirno-bundle.json";
+const DESCRIPTOR_VERSION: u32 = 1;
+
+/// Describes where a bundle comes from and what it needs, so that it can be checked before it is imported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleDescriptor {
+    pub version: u32,
+    /// Name of the exported application.
+    pub name: String,
+    /// `version` of the `package.json`.
+    pub app_version: Option<String>,
+    /// Instance the bundle was exported from.
+    pub instance: Uuid,
+    /// Version of Cirno which exported the bundle.
+    pub cirno_version: String,
+    pub yarn: YarnDescriptor,
+    /// Cache key of the lockfile.
+    pub cache_key: String,
+    pub platform: PlatformConstraints,
+    pub runtime: RuntimeDescriptor,
+    pub created: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YarnDescriptor {
+    pub version: String,
+    /// Hash of the bundled release, as `<algorithm>.<hex>`.
+    pub hash: String,
+}
+
+/// Platforms the bundle runs on, with the values and syntax of the `os` and `cpu` fields of a `package.json`: names
+/// of allowed platforms, or names prefixed by `!` of blocked ones. Empty lists allow any platform.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct PlatformConstraints {
+    pub os: Vec<String>,
+    pub cpu: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeDescriptor {
+    /// Node.js version requirement declared by the application.
+    pub requirement: Option<String>,
+    /// Version of the Node.js runtime embedded in the bundle.
+    pub bundled: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PackagePlatform {
+    version: Option<String>,
+    os: Vec<String>,
+    cpu: Vec<String>,
+}
+
+/// Name of the host platform in Node.js (`process.platform`).
+pub fn host_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    }
+}
+
+/// Name of the host architecture in Node.js (`process.arch`).
+pub fn host_cpu() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        "x86" => "ia32",
+        "powerpc64" => "ppc64",
+        arch => arch,
+    }
+}
+
+/// Checks a value against a list of allowed and blocked (`!`-prefixed) values, as npm does.
+fn is_allowed(list: &[String], value: &str) -> bool {
+    let mut allowed = list.iter().filter(|item| !item.starts_with('!')).peekable();
+    let mut blocked = list.iter().filter_map(|item| item.strip_prefix('!'));
+    (allowed.peek().is_none() || allowed.any(|item| item == value)) && !blocked.any(|item| item == value)
+}
+
+impl PlatformConstraints {
+    /// Fails if the host does not satisfy the constraints.
+    pub fn check(&self) -> Result<()> {
+        if !is_allowed(&self.os, host_os()) {
+            bail!("Bundle does not support {} (os: {}).", host_os(), self.os.join(", "));
+        }
+        if !is_allowed(&self.cpu, host_cpu()) {
+            bail!("Bundle does not support {} (cpu: {}).", host_cpu(), self.cpu.join(", "));
+        }
+        Ok(())
+    }
+}
+
+impl BundleDescriptor {
+    /// Describes a packed bundle at `root`. A bundle which embeds a runtime is restricted to the host platform, which
+    /// the runtime is built for.
+    pub(super) async fn new(root: &Path, name: &str, instance: &Uuid, meta: &Meta) -> Result<Self> {
+        let package_manager = PackageManager::yarn(&meta.package.package_manager)?;
+        let yarn_path = meta
+            .yarn_rc
+            .yarn_path
+            .as_deref()
+            .ok_or_else(|| anyhow!("Bundle has no yarnPath."))?;
+        let path =
+            join_relative(root, yarn_path).ok_or_else(|| anyhow!("Invalid yarnPath in bundle: {}", yarn_path))?;
+        let release = fs::read(path).await?;
+        let algorithm = package_manager
+            .hash
+            .as_ref()
+            .map_or("sha512", |hash| hash.algorithm.as_str());
+        let hash = ReleaseHash::compute(algorithm, &release)?;
+
+        let package: PackagePlatform = serde_json::from_str(&fs::read_to_string(root.join("package.json")).await?)?;
+        let mut bundled = None;
+        let runtimes = root.join(runtime::BUNDLE_RUNTIME_DIR);
+        if tokio::fs::try_exists(&runtimes).await?
+            && let Some(entry) = fs::read_dir(&runtimes).await?.next_entry().await?
+        {
+            bundled = Some(entry.file_name().to_string_lossy().to_string());
+        }
+        let platform = match bundled {
+            Some(_) => PlatformConstraints {
+                os: vec![host_os().to_string()],
+                cpu: vec![host_cpu().to_string()],
+            },
+            None => PlatformConstraints {
+                os: package.os,
+                cpu: package.cpu,
+            },
+        };
+
+        Ok(Self {
+            version: DESCRIPTOR_VERSION,
+            name: name.to_string(),
+            app_version: package.version,
+            instance: *instance,
+            cirno_version: env!("CARGO_PKG_VERSION").to_string(),
+            yarn: YarnDescriptor {
+                version: package_manager.version,
+                hash: hash.to_string(),
+            },
+            cache_key: meta.yarn_lock.metadata.cache_key.clone(),
+            platform,
+            runtime: RuntimeDescriptor {
+                requirement: runtime::declared_requirement(root).await?,
+                bundled,
+            },
+            created: crate::get_timestamp(),
+        })
+    }
+
+    /// Checks an unpacked bundle at `root` against its descriptor, and the host against the platform constraints.
+    pub(super) async fn validate(&self, root: &Path, meta: &Meta) -> Result<()> {
+        if self.version > DESCRIPTOR_VERSION {
+            bail!(
+                "Unsupported bundle descriptor version: {} (exported by Cirno {}).",
+                self.version,
+                self.cirno_version
+            );
+        }
+        self.platform.check()?;
+        let package_manager = PackageManager::yarn(&meta.package.package_manager)?;
+        if package_manager.version != self.yarn.version {
+            bail!(
+                "Bundle uses yarn {}, but its descriptor declares yarn {}.",
+                package_manager.version,
+                self.yarn.version
+            );
+        }
+        if meta.yarn_lock.metadata.cache_key != self.cache_key {
+            bail!(
+                "Lockfile of the bundle has cache key {}, but its descriptor declares {}.",
+                meta.yarn_lock.metadata.cache_key,
+                self.cache_key
+            );
+        }
+        if let Some(yarn_path) = &meta.yarn_rc.yarn_path {
+            let path =
+                join_relative(root, yarn_path).ok_or_else(|| anyhow!("Invalid yarnPath in bundle: {}", yarn_path))?;
+            let (algorithm, _) = self
+                .yarn
+                .hash
+                .split_once('.')
+                .ok_or_else(|| anyhow!("Invalid yarn hash in bundle descriptor: {}", self.yarn.hash))?;
+            let actual = ReleaseHash::compute(algorithm, &fs::read(&path).await?)?;
+            if actual.to_string() != self.yarn.hash {
+                bail!(
+                    "Yarn release of the bundle does not match its descriptor: expected {}, found {}.",
+                    self.yarn.hash,
+                    actual
+                );
+            }
+        }
+        self.runtime.validate(root).await
+    }
+}
+
+impl RuntimeDescriptor {
+    /// Checks the requirement and the embedded runtime of an unpacked bundle at `root` against the descriptor.
+    async fn validate(&self, root: &Path) -> Result<()> {
+        let requirement = runtime::declared_requirement(root).await?;
+        if requirement != self.requirement {
+            bail!(
+                "Bundle requires node {}, but its descriptor declares {}.",
+                requirement.as_deref().unwrap_or("(none)"),
+                self.requirement.as_deref().unwrap_or("(none)")
+            );
+        }
+        let runtimes = root.join(runtime::BUNDLE_RUNTIME_DIR);
+        let embedded = runtime::list(&runtimes).await?;
+        match &self.bundled {
+            Some(bundled) => {
+                if !embedded.iter().any(|runtime| runtime.version.to_string() == *bundled) {
+                    bail!(
+                        "Bundle does not embed the runtime declared by its descriptor: {}.",
+                        bundled
+                    );
+                }
+                if self.requirement.is_some() && runtime::resolve(&runtimes, root).await?.is_none() {
+                    bail!(
+                        "Runtime {} embedded in the bundle does not satisfy its requirement: {}.",
+                        bundled,
+                        requirement.unwrap_or_default()
+                    );
+                }
+            }
+            None if !embedded.is_empty() => bail!("Bundle embeds a runtime, but its descriptor declares none."),
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reads the descriptor of a bundle, either a directory or a file of any [`BundleFormat`], without unpacking it.
+/// Returns `None` for bundles exported before descriptors were introduced.
+pub async fn read_descriptor(src: &Path) -> Result<Option<BundleDescriptor>> {
+    let content = if fs::metadata(src).await?.is_dir() {
+        match tokio::fs::read(src.join(DESCRIPTOR_FILE)).await {
+            Ok(content) => Some(content),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error).with_context(|| format!("Failed to read file: {}", DESCRIPTOR_FILE)),
+        }
+    } else {
+        let src = src.to_path_buf();
+        tokio::task::spawn_blocking(move || BundleFormat::detect(&src)?.read_file(&src, DESCRIPTOR_FILE)).await??
+    };
+    content
+        .map(|content| serde_json::from_slice(&content).context("Invalid bundle descriptor"))
+        .transpose()
+}
@@ -0,0 +1,321 @@
+//! Entries of bundle files.
+//!
+//! Bundle files are extracted entry by entry rather than by the zip and tar crates, so that both formats get the same
+//! guarantees: paths use `/` separators and must stay inside the bundle, symlinks must point inside the bundle, and the
+//! unix modes, empty directories and modification times of the entries are restored.
+
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow, bail};
+use jiff::Timestamp;
+use jiff::tz::TimeZone;
+use tar::EntryType;
+use zip::ZipArchive;
+
+use crate::report::PhaseProgress;
+
+/// Maximum number of symlinks followed to resolve a path, as `SYMLOOP_MAX` on Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Whether a path starts with a Windows drive, such as `C:`.
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Normalizes the path of an entry into a relative path, or `None` for the root of the bundle. Fails for absolute paths
+/// and paths with `..` components, which would be extracted outside the bundle (zip slip).
+pub(crate) fn entry_path(name: &str) -> Result<Option<PathBuf>> {
+    let name = name.replace('\\', "/");
+    if name.starts_with('/') || has_drive(&name) {
+        bail!("Unsafe path in bundle: {}", name);
+    }
+    let mut path = PathBuf::new();
+    for part in name.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => bail!("Unsafe path in bundle: {}", name),
+            _ => path.push(part),
+        }
+    }
+    Ok((!path.as_os_str().is_empty()).then_some(path))
+}
+
+/// Normalizes the target of a symlink at `path`, relative to the root of the bundle. Fails if the target is absolute or
+/// points outside the bundle.
+pub(crate) fn symlink_target(path: &Path, target: &str) -> Result<String> {
+    let target = target.replace('\\', "/");
+    let error = || anyhow!("Symlink {} points outside the bundle: {}", path.display(), target);
+    if target.starts_with('/') || has_drive(&target) {
+        return Err(error());
+    }
+    let mut depth = path.components().count().saturating_sub(1);
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => depth = depth.checked_sub(1).ok_or_else(error)?,
+            _ => depth += 1,
+        }
+    }
+    Ok(target)
+}
+
+/// Resolves every symlink of `path`, including dangling ones and those below missing components.
+fn resolve(path: &Path, links: usize) -> Result<PathBuf> {
+    if links > MAX_SYMLINKS {
+        bail!("Too many levels of symlinks: {}", path.display());
+    }
+    if let Ok(resolved) = path.canonicalize() {
+        return Ok(resolved);
+    }
+    if let Ok(target) = std::fs::read_link(path) {
+        return resolve(&path.parent().unwrap_or(path).join(target), links + 1);
+    }
+    let Some(parent) = path.parent() else {
+        return Ok(path.to_path_buf());
+    };
+    let parent = resolve(parent, links)?;
+    Ok(match path.components().next_back() {
+        Some(Component::ParentDir) => parent.parent().unwrap_or(&parent).to_path_buf(),
+        Some(Component::Normal(name)) => parent.join(name),
+        _ => parent,
+    })
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &str, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &str, link: &Path) -> std::io::Result<()> {
+    let target = target.replace('/', "\\");
+    match link.parent().is_some_and(|parent| parent.join(&target).is_dir()) {
+        true => std::os::windows::fs::symlink_dir(target, link),
+        false => std::os::windows::fs::symlink_file(target, link),
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o777))
+        .with_context(|| format!("Failed to set the mode of {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Unix mode of a file to be written to a bundle, if the platform has one.
+pub(crate) fn unix_mode(metadata: &Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Converts a modification time into a zip timestamp, which is stored in UTC with a precision of two seconds. Zip
+/// timestamps cannot represent times before 1980.
+pub(crate) fn to_zip_time(time: SystemTime) -> Option<zip::DateTime> {
+    let datetime = Timestamp::try_from(time).ok()?.to_zoned(TimeZone::UTC).datetime();
+    zip::DateTime::from_date_and_time(
+        u16::try_from(datetime.year()).ok()?,
+        datetime.month() as u8,
+        datetime.day() as u8,
+        datetime.hour() as u8,
+        datetime.minute() as u8,
+        datetime.second() as u8,
+    )
+    .ok()
+}
+
+fn from_zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    let datetime = jiff::civil::DateTime::new(
+        time.year() as i16,
+        time.month() as i8,
+        time.day() as i8,
+        time.hour() as i8,
+        time.minute() as i8,
+        time.second() as i8,
+        0,
+    )
+    .ok()?;
+    Some(datetime.to_zoned(TimeZone::UTC).ok()?.timestamp().into())
+}
+
+/// Extracts entries into a directory, reporting each file.
+///
+/// Symlinks are created after every other entry, so that no entry is written through a symlink, and the modes and
+/// times of directories are set last, so that read-only directories can be filled. Directories always stay writable by
+/// their owner, so that the extracted bundle can be removed.
+struct Unpacker<'a> {
+    dest: &'a Path,
+    progress: &'a PhaseProgress,
+    dirs: Vec<(PathBuf, Option<u32>, Option<SystemTime>)>,
+    symlinks: Vec<(PathBuf, String)>,
+}
+
+impl<'a> Unpacker<'a> {
+    fn new(dest: &'a Path, progress: &'a PhaseProgress) -> Result<Self> {
+        std::fs::create_dir_all(dest).with_context(|| format!("Failed to create directory: {}", dest.display()))?;
+        Ok(Self {
+            dest,
+            progress,
+            dirs: vec![],
+            symlinks: vec![],
+        })
+    }
+
+    /// Creates the missing parents of an entry, and fails if one of them is a symlink, including those which are not
+    /// created yet.
+    fn create_parents(&self, path: &Path) -> Result<()> {
+        let mut current = self.dest.to_path_buf();
+        for ancestor in path.ancestors().skip(1) {
+            if self.symlinks.iter().any(|(link, _)| link == ancestor) {
+                bail!("Entry {} is not inside a directory.", path.display());
+            }
+        }
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            current.push(component);
+            match std::fs::symlink_metadata(&current) {
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => bail!("Entry {} is not inside a directory.", path.display()),
+                Err(_) => std::fs::create_dir(&current)
+                    .with_context(|| format!("Failed to create directory: {}", current.display()))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn dir(&mut self, path: &Path, mode: Option<u32>, mtime: Option<SystemTime>) -> Result<()> {
+        self.create_parents(path)?;
+        let target = self.dest.join(path);
+        if !target.is_dir() {
+            std::fs::create_dir(&target)
+                .with_context(|| format!("Failed to create directory: {}", target.display()))?;
+        }
+        self.dirs.push((target, mode, mtime));
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        reader: &mut impl Read,
+        mode: Option<u32>,
+        mtime: Option<SystemTime>,
+    ) -> Result<()> {
+        self.create_parents(path)?;
+        let target = self.dest.join(path);
+        let mut file = File::create(&target).with_context(|| format!("Failed to create file: {}", target.display()))?;
+        std::io::copy(reader, &mut file).with_context(|| format!("Failed to extract {}", path.display()))?;
+        if let Some(mtime) = mtime {
+            file.set_modified(mtime)?;
+        }
+        drop(file);
+        if let Some(mode) = mode {
+            set_mode(&target, mode)?;
+        }
+        self.progress.advance(1, 0)?;
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &str) -> Result<()> {
+        let target = symlink_target(path, target)?;
+        self.symlinks.push((path.to_path_buf(), target));
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        for (path, target) in &self.symlinks {
+            self.create_parents(path)?;
+            let link = self.dest.join(path);
+            create_symlink(target, &link).with_context(|| format!("Failed to create link: {}", link.display()))?;
+        }
+        // targets were checked lexically, but a symlink may point through another one
+        let root = self.dest.canonicalize()?;
+        for (path, target) in &self.symlinks {
+            if !resolve(&self.dest.join(path), 0)?.starts_with(&root) {
+                bail!("Symlink {} points outside the bundle: {}", path.display(), target);
+            }
+        }
+        self.dirs.sort_by(|a, b| b.0.cmp(&a.0));
+        for (path, mode, mtime) in &self.dirs {
+            if let Some(mode) = mode {
+                set_mode(path, mode | 0o700)?;
+            }
+            // directories cannot be opened on every platform, so their times are best effort
+            if let (Some(mtime), Ok(dir)) = (mtime, File::open(path)) {
+                let _ = dir.set_modified(*mtime);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extracts a tarball into `dest`. Hard links and special files are rejected.
+pub(crate) fn unpack_tar<R: Read>(reader: R, dest: &Path, progress: &PhaseProgress) -> Result<()> {
+    let mut unpacker = Unpacker::new(dest, progress)?;
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        if entry_type == EntryType::XGlobalHeader {
+            continue;
+        }
+        let mode = header.mode().ok();
+        let mtime = header.mtime().ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let Some(path) = entry_path(&name)? else {
+            continue;
+        };
+        match entry_type {
+            EntryType::Directory => unpacker.dir(&path, mode, mtime)?,
+            EntryType::Regular | EntryType::Continuous => unpacker.file(&path, &mut entry, mode, mtime)?,
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name_bytes()
+                    .ok_or_else(|| anyhow!("Symlink {} has no target.", name))?;
+                unpacker.symlink(&path, &String::from_utf8_lossy(&target))?;
+            }
+            _ => bail!("Unsupported entry in bundle: {} ({:?})", name, entry_type),
+        }
+    }
+    unpacker.finish()
+}
+
+/// Extracts a zip archive into `dest`.
+pub(crate) fn unpack_zip<R: Read + Seek>(reader: R, dest: &Path, progress: &PhaseProgress) -> Result<()> {
+    let mut zip = ZipArchive::new(reader)?;
+    let mut unpacker = Unpacker::new(dest, progress)?;
+    for index in 0..zip.len() {
+        let mut file = zip.by_index(index)?;
+        let name = file.name().to_string();
+        let Some(path) = entry_path(&name)? else {
+            continue;
+        };
+        let mode = file.unix_mode();
+        let mtime = file.last_modified().and_then(from_zip_time);
+        if file.is_symlink() {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            unpacker.symlink(&path, &target)?;
+        } else if file.is_dir() {
+            unpacker.dir(&path, mode, mtime)?;
+        } else {
+            unpacker.file(&path, &mut file, mode, mtime)?;
+        }
+    }
+    unpacker.finish()
+}
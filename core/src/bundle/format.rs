@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result, anyhow, bail};
+use brotli::{CompressorWriter, Decompressor};
+use serde::{Deserialize, Serialize};
+use tar::HeaderMode;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::entry::{self, symlink_target, to_zip_time, unpack_tar, unpack_zip};
+use crate::report::PhaseProgress;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Brotli quality of tar.br bundles. The maximum quality (11) is too slow for bundles of several gigabytes.
+const BROTLI_QUALITY: u32 = 9;
+const BROTLI_WINDOW: u32 = 22;
+
+const ZIP_MAGIC: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+/// Offset and value of the magic of ustar headers, which also matches GNU tar headers.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Files of a bundle, see [`BundleFormat::scan`].
+#[derive(Debug, Default)]
+pub struct BundleContent {
+    /// Size of each regular file by path, with paths separated by `/`.
+    pub sizes: BTreeMap<String, u64>,
+    /// Content of the requested files found in the bundle.
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+/// Archive formats of bundle files. Bundles may also be plain directories.
+///
+/// Every format is written and read as a stream, so that the memory usage does not depend on the size of the bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BundleFormat {
+    /// Deflate-compressed zip archive, as written by previous versions.
+    Zip,
+    /// Brotli-compressed tarball.
+    TarBr,
+    /// Zstandard-compressed tarball.
+    TarZst,
+}
+
+impl BundleFormat {
+    pub const ALL: [BundleFormat; 3] = [BundleFormat::Zip, BundleFormat::TarBr, BundleFormat::TarZst];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "zip",
+            BundleFormat::TarBr => "tar.br",
+            BundleFormat::TarZst => "tar.zst",
+        }
+    }
+
+    /// Guesses the format of a bundle to be written from the extension of its path.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| name.ends_with(&format!(".{}", format.extension())))
+    }
+
+    /// Detects the format of a bundle file from its first bytes, regardless of its extension.
+    ///
+    /// Brotli streams have no magic number, so a file is considered a tar.br bundle if its first block decompresses
+    /// into a tar header.
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut header = vec![];
+        File::open(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?
+            .take(BUFFER_SIZE as u64)
+            .read_to_end(&mut header)?;
+        if ZIP_MAGIC.iter().any(|magic| header.starts_with(magic)) {
+            return Ok(BundleFormat::Zip);
+        }
+        if header.starts_with(ZSTD_MAGIC) {
+            return Ok(BundleFormat::TarZst);
+        }
+        let mut block = vec![];
+        let _ = Decompressor::new(header.as_slice(), BUFFER_SIZE)
+            .take(512)
+            .read_to_end(&mut block);
+        if block.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+            return Ok(BundleFormat::TarBr);
+        }
+        bail!("Unrecognized bundle format: {}", path.display())
+    }
+
+    /// Packs the content of the directory `src` into a bundle file at `dest`, reporting each file packed. The file is
+    /// removed if packing fails or is cancelled.
+    pub fn write(&self, src: &Path, dest: &Path, progress: &PhaseProgress) -> Result<()> {
+        let file = File::create(dest).with_context(|| format!("Failed to create file: {}", dest.display()))?;
+        let writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+        let result = (|| {
+            match self {
+                BundleFormat::Zip => {
+                    let mut zip = ZipWriter::new(writer);
+                    write_zip_dir(&mut zip, src, "", progress)?;
+                    zip.finish()?.flush()?;
+                }
+                BundleFormat::TarBr => {
+                    let writer = CompressorWriter::new(writer, BUFFER_SIZE, BROTLI_QUALITY, BROTLI_WINDOW);
+                    let mut writer = write_tar(writer, src, progress)?;
+                    writer.flush()?;
+                    writer.into_inner().flush()?;
+                }
+                BundleFormat::TarZst => {
+                    let writer = zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+                    write_tar(writer, src, progress)?.finish()?.flush()?;
+                }
+            }
+            Ok(())
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(dest);
+        }
+        result
+    }
+
+    /// Extracts a bundle file into the directory `dest`, which is created if needed, reporting the bytes read from
+    /// the bundle file and each file extracted.
+    ///
+    /// Unix modes, symlinks, empty directories and modification times are restored. Entries with an absolute path or a
+    /// `..` component, symlinks pointing outside `dest`, hard links and special files are rejected.
+    pub fn extract(&self, src: &Path, dest: &Path, progress: &PhaseProgress) -> Result<()> {
+        let file = File::open(src).with_context(|| format!("Failed to read file: {}", src.display()))?;
+        let file = ProgressReader::new(file, progress);
+        let reader = BufReader::with_capacity(BUFFER_SIZE, file);
+        match self {
+            BundleFormat::Zip => unpack_zip(reader, dest, progress),
+            BundleFormat::TarBr => unpack_tar(Decompressor::new(reader, BUFFER_SIZE), dest, progress),
+            BundleFormat::TarZst => unpack_tar(zstd::Decoder::with_buffer(reader)?, dest, progress),
+        }
+    }
+
+    /// Reads a single file of a bundle file without extracting it, or returns `None` if the bundle does not contain
+    /// it. Tarballs have no index, so they are read up to the file.
+    pub fn read_file(&self, src: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        let file = File::open(src).with_context(|| format!("Failed to read file: {}", src.display()))?;
+        let reader = BufReader::with_capacity(BUFFER_SIZE, file);
+        match self {
+            BundleFormat::Zip => {
+                let mut zip = ZipArchive::new(reader)?;
+                let mut file = match zip.by_name(name) {
+                    Ok(file) => file,
+                    Err(ZipError::FileNotFound) => return Ok(None),
+                    Err(error) => return Err(error.into()),
+                };
+                let mut content = vec![];
+                file.read_to_end(&mut content)?;
+                Ok(Some(content))
+            }
+            BundleFormat::TarBr => read_tar_file(Decompressor::new(reader, BUFFER_SIZE), name),
+            BundleFormat::TarZst => read_tar_file(zstd::Decoder::with_buffer(reader)?, name),
+        }
+    }
+
+    /// Lists the files of a bundle file with their uncompressed size, and reads the files of `names`, in a single pass
+    /// over the bundle.
+    pub fn scan(&self, src: &Path, names: &[&str]) -> Result<BundleContent> {
+        let file = File::open(src).with_context(|| format!("Failed to read file: {}", src.display()))?;
+        let reader = BufReader::with_capacity(BUFFER_SIZE, file);
+        match self {
+            BundleFormat::Zip => {
+                let mut content = BundleContent::default();
+                let mut zip = ZipArchive::new(reader)?;
+                for index in 0..zip.len() {
+                    let mut file = zip.by_index(index)?;
+                    if !file.is_file() || file.is_symlink() {
+                        continue;
+                    }
+                    let name = file.name().to_string();
+                    content.sizes.insert(name.clone(), file.size());
+                    if names.contains(&name.as_str()) {
+                        let mut data = vec![];
+                        file.read_to_end(&mut data)?;
+                        content.files.insert(name, data);
+                    }
+                }
+                Ok(content)
+            }
+            BundleFormat::TarBr => scan_tar(Decompressor::new(reader, BUFFER_SIZE), names),
+            BundleFormat::TarZst => scan_tar(zstd::Decoder::with_buffer(reader)?, names),
+        }
+    }
+}
+
+impl Display for BundleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for BundleFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == s)
+            .ok_or_else(|| anyhow!("Unsupported bundle format: {} (expected zip, tar.br or tar.zst)", s))
+    }
+}
+
+/// Reports the bytes read through it. Reads fail once the operation is cancelled, so that large files do not delay
+/// cancellation.
+pub(crate) struct ProgressReader<R> {
+    inner: R,
+    progress: PhaseProgress,
+}
+
+impl<R> ProgressReader<R> {
+    pub(crate) fn new(inner: R, progress: &PhaseProgress) -> Self {
+        Self {
+            inner,
+            progress: progress.clone(),
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.progress.advance(0, len as u64).map_err(std::io::Error::other)?;
+        Ok(len)
+    }
+}
+
+impl<R: Seek> Seek for ProgressReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Adds a regular file to a tarball under `name`, reporting its bytes as they are read and the file once it is added.
+pub(crate) fn append_file<W: Write>(
+    tar: &mut tar::Builder<W>,
+    path: &Path,
+    name: &Path,
+    mode: HeaderMode,
+    progress: &PhaseProgress,
+) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata_in_mode(&file.metadata()?, mode);
+    tar.append_data(&mut header, name, ProgressReader::new(file, progress))
+        .with_context(|| format!("Failed to pack {}", path.display()))?;
+    progress.advance(1, 0)?;
+    Ok(())
+}
+
+fn read_tar_file<R: Read>(reader: R, name: &str) -> Result<Option<Vec<u8>>> {
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_ref() == Path::new(name) {
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            return Ok(Some(content));
+        }
+    }
+    Ok(None)
+}
+
+fn scan_tar<R: Read>(reader: R, names: &[&str]) -> Result<BundleContent> {
+    let mut content = BundleContent::default();
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().replace('\\', "/");
+        content.sizes.insert(name.clone(), entry.size());
+        if names.contains(&name.as_str()) {
+            let mut data = vec![];
+            entry.read_to_end(&mut data)?;
+            content.files.insert(name, data);
+        }
+    }
+    Ok(content)
+}
+
+/// Options of a zip entry with the modification time and unix mode of a file.
+fn zip_options(metadata: &std::fs::Metadata) -> SimpleFileOptions {
+    let mut options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(false);
+    if let Some(time) = metadata.modified().ok().and_then(to_zip_time) {
+        options = options.last_modified_time(time);
+    }
+    if let Some(mode) = entry::unix_mode(metadata) {
+        options = options.unix_permissions(mode & 0o777);
+    }
+    options
+}
+
+/// Recursively adds the content of `root` to a zip archive.
+fn write_zip_dir<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    root: &Path,
+    base: &str,
+    progress: &PhaseProgress,
+) -> Result<()> {
+    for entry in read_dir_sorted(root)? {
+        let name = format!("{}{}", base, entry.file_name().to_string_lossy());
+        let file_type = entry.file_type()?;
+        let metadata = std::fs::symlink_metadata(entry.path())?;
+        let options = zip_options(&metadata);
+        if file_type.is_dir() {
+            zip.add_directory(&name, options)?;
+            write_zip_dir(zip, &entry.path(), &format!("{}/", name), progress)?;
+        } else if file_type.is_symlink() {
+            let target = std::fs::read_link(entry.path())?;
+            let target = symlink_target(Path::new(&name), &target.to_string_lossy())?;
+            zip.add_symlink(&name, target, options)?;
+        } else {
+            // files over 4 GiB need the zip64 extension, which must be known before writing them
+            let size = metadata.len();
+            zip.start_file(&name, options.large_file(size >= u32::MAX as u64))?;
+            let file =
+                File::open(entry.path()).with_context(|| format!("Failed to read file: {}", entry.path().display()))?;
+            std::io::copy(&mut ProgressReader::new(file, progress), zip)?;
+            progress.advance(1, 0)?;
+        }
+    }
+    Ok(())
+}
+
+/// Packs the content of `root` into a tarball written to `writer`, and returns the writer once the tarball is
+/// complete so that the caller can finish the compression stream.
+pub(super) fn write_tar<W: Write>(writer: W, root: &Path, progress: &PhaseProgress) -> Result<W> {
+    let mut tar = tar::Builder::new(writer);
+    tar.follow_symlinks(false);
+    write_tar_dir(&mut tar, root, Path::new(""), progress)?;
+    Ok(tar.into_inner()?)
+}
+
+fn write_tar_dir<W: Write>(
+    tar: &mut tar::Builder<W>,
+    root: &Path,
+    base: &Path,
+    progress: &PhaseProgress,
+) -> Result<()> {
+    for entry in read_dir_sorted(root)? {
+        let name = base.join(entry.file_name());
+        if entry.file_type()?.is_file() {
+            append_file(tar, &entry.path(), &name, HeaderMode::Complete, progress)?;
+            continue;
+        }
+        if entry.file_type()?.is_symlink() {
+            symlink_target(&name, &std::fs::read_link(entry.path())?.to_string_lossy())?;
+        }
+        tar.append_path_with_name(entry.path(), &name)
+            .with_context(|| format!("Failed to pack {}", entry.path().display()))?;
+        if entry.file_type()?.is_dir() {
+            write_tar_dir(tar, &entry.path(), &name, progress)?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists a directory sorted by name, so that bundles of identical directories are identical.
+pub(super) fn read_dir_sorted(root: &Path) -> Result<Vec<std::fs::DirEntry>> {
+    let mut entries = std::fs::read_dir(root)
+        .with_context(|| format!("Failed to read directory: {}", root.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    Ok(entries)
+}
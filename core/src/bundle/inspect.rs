@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+
+use super::format::read_dir_sorted;
+use super::{BundleContent, BundleDescriptor, BundleFormat, DESCRIPTOR_FILE};
+use crate::Package;
+use crate::yarn::{YarnLock, YarnRc};
+
+/// Report of [`inspect`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleReport {
+    /// Format of the bundle file, or `None` for a directory.
+    pub format: Option<BundleFormat>,
+    /// Name of the `package.json`.
+    pub name: String,
+    pub package_manager: String,
+    pub lockfile_version: u32,
+    pub descriptor: Option<BundleDescriptor>,
+    /// Packages stored in the cache, sorted by name.
+    pub packages: Vec<PackageReport>,
+    /// Uncompressed size of every file of the bundle.
+    pub total_size: u64,
+    /// Uncompressed size of the cache files of the packages.
+    pub cache_size: u64,
+    /// Whether the bundle can be installed without network access, i.e. it has no issues.
+    pub self_contained: bool,
+    /// Reasons why the bundle is not self-contained.
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageReport {
+    /// Name and reference of the package (eg. `lodash@npm:4.17.21`).
+    pub name: String,
+    /// Path of the cache file in the bundle, or `None` if it is missing.
+    pub cache_file: Option<String>,
+    pub size: u64,
+}
+
+/// Lists the files of a bundle directory, the same way [`BundleFormat::scan`] does for bundle files.
+fn scan_dir(root: &Path, base: &str, names: &[&str], content: &mut BundleContent) -> Result<()> {
+    for entry in read_dir_sorted(root)? {
+        let name = format!("{}{}", base, entry.file_name().to_string_lossy());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            scan_dir(&entry.path(), &format!("{}/", name), names, content)?;
+        } else if file_type.is_file() {
+            content.sizes.insert(name.clone(), entry.metadata()?.len());
+            if names.contains(&name.as_str()) {
+                let data = std::fs::read(entry.path())
+                    .with_context(|| format!("Failed to read file: {}", entry.path().display()))?;
+                content.files.insert(name, data);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_file<'a>(content: &'a BundleContent, name: &str) -> Result<&'a [u8]> {
+    content
+        .files
+        .get(name)
+        .map(Vec::as_slice)
+        .ok_or_else(|| anyhow!("Bundle has no {}.", name))
+}
+
+/// Inspects a bundle, either a directory or a file of any [`BundleFormat`], without unpacking it.
+///
+/// Besides its metadata and the size of its packages, the bundle is checked to be self-contained: it must carry the
+/// yarn release at its `yarnPath` and a cache file for every package of its lockfile, and must not use the global
+/// cache.
+pub async fn inspect(src: &Path) -> Result<BundleReport> {
+    const NAMES: [&str; 4] = ["package.json", ".yarnrc.yml", "yarn.lock", DESCRIPTOR_FILE];
+    let src = src.to_path_buf();
+    let (format, content) = tokio::task::spawn_blocking(move || -> Result<_> {
+        if std::fs::metadata(&src)
+            .with_context(|| format!("Failed to read bundle: {}", src.display()))?
+            .is_dir()
+        {
+            let mut content = BundleContent::default();
+            scan_dir(&src, "", &NAMES, &mut content)?;
+            Ok((None, content))
+        } else {
+            let format = BundleFormat::detect(&src)?;
+            Ok((Some(format), format.scan(&src, &NAMES)?))
+        }
+    })
+    .await??;
+
+    let package: Package =
+        serde_json::from_slice(get_file(&content, "package.json")?).context("Failed to parse package.json")?;
+    let yarn_rc: YarnRc =
+        serde_yaml_ng::from_slice(get_file(&content, ".yarnrc.yml")?).context("Failed to parse .yarnrc.yml")?;
+    let yarn_lock: YarnLock =
+        serde_yaml_ng::from_slice(get_file(&content, "yarn.lock")?).context("Failed to parse yarn.lock")?;
+    let descriptor = content
+        .files
+        .get(DESCRIPTOR_FILE)
+        .map(|data| serde_json::from_slice(data).context("Invalid bundle descriptor"))
+        .transpose()?;
+
+    let mut issues = vec![];
+    match &yarn_rc.yarn_path {
+        Some(yarn_path) => {
+            let yarn_path = yarn_path.trim_start_matches("./");
+            if !content.sizes.contains_key(yarn_path) {
+                issues.push(format!("Yarn release is missing at {}.", yarn_path));
+            }
+        }
+        None => issues.push("yarnPath is not set, so the yarn release is not included.".to_string()),
+    }
+    if yarn_rc.enable_global_cache != Some(false) {
+        issues.push("enableGlobalCache is not disabled, so the cache files of the bundle are ignored.".to_string());
+    }
+
+    let cache_folder = yarn_rc.cache_folder.as_deref().unwrap_or(".yarn/cache");
+    let cache_folder = format!("{}/", cache_folder.trim_start_matches("./").trim_end_matches('/'));
+    let mut packages = vec![];
+    for (locator, _) in yarn_lock.get_cache_entries()? {
+        let prefix = format!("{}{}-", cache_folder, locator.slugify());
+        let cache_file = content
+            .sizes
+            .range(prefix.clone()..)
+            .next()
+            .filter(|(path, _)| path.starts_with(&prefix) && path.ends_with(".zip"));
+        packages.push(PackageReport {
+            name: format!("{}@{}", locator.stringify_ident(), locator.reference),
+            cache_file: cache_file.map(|(path, _)| path.clone()),
+            size: cache_file.map_or(0, |(_, size)| *size),
+        });
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    for package in packages.iter().filter(|package| package.cache_file.is_none()) {
+        issues.push(format!("Cache file of {} is missing.", package.name));
+    }
+
+    Ok(BundleReport {
+        format,
+        name: package.name,
+        package_manager: package.package_manager,
+        lockfile_version: yarn_lock.metadata.version,
+        descriptor,
+        cache_size: packages.iter().map(|package| package.size).sum(),
+        packages,
+        total_size: content.sizes.values().sum(),
+        self_contained: issues.is_empty(),
+        issues,
+    })
+}
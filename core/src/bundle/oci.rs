@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tar::{EntryType, Header, HeaderMode};
+
+use super::BUNDLE_CACHE_REGEX;
+use super::format::{append_file, read_dir_sorted, write_tar};
+use crate::report::{Phase, PhaseProgress};
+use crate::runtime::BUNDLE_RUNTIME_DIR;
+
+const MEDIA_TYPE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const MEDIA_TYPE_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+const MEDIA_TYPE_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+const MEDIA_TYPE_LAYER: &str = "application/vnd.oci.image.layer.v1.tar";
+
+/// Working directory of the image, where the bundle is unpacked.
+const IMAGE_APP_DIR: &str = "app";
+/// Directory of the Node.js runtime in the image.
+const IMAGE_RUNTIME_DIR: &str = "opt/node";
+const IMAGE_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Number of layers the cache files are spread over. Cache files are assigned to a layer by their locator hash, so
+/// that a layer is shared by every image whose packages of that layer are identical, while keeping the number of
+/// layers well below the limit of container runtimes (about 127).
+const CACHE_LAYERS: u8 = 32;
+
+/// Options of an OCI image export.
+#[derive(Debug, Default)]
+pub struct OciOptions {
+    /// Reference of the image (eg. `my-app:1.0.0`), which `docker load` tags the image with. Defaults to the package
+    /// name with the `latest` tag.
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: &'static str,
+    digest: String,
+    size: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, String>,
+}
+
+/// Computes the SHA-256 digest of the data written through it.
+struct HashWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.size += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes blobs into the `blobs/sha256` directory of an image layout.
+struct Blobs {
+    dir: PathBuf,
+}
+
+impl Blobs {
+    fn create(&self) -> Result<(PathBuf, HashWriter<BufWriter<File>>)> {
+        let path = self.dir.join(format!(".{}", uuid::Uuid::new_v4()));
+        let file = File::create(&path).with_context(|| format!("Failed to create file: {}", path.display()))?;
+        let writer = HashWriter {
+            inner: BufWriter::new(file),
+            hasher: Sha256::new(),
+            size: 0,
+        };
+        Ok((path, writer))
+    }
+
+    fn commit(&self, path: &Path, writer: HashWriter<BufWriter<File>>, media_type: &'static str) -> Result<Descriptor> {
+        let HashWriter {
+            mut inner,
+            hasher,
+            size,
+        } = writer;
+        inner.flush()?;
+        let digest = hex::encode(hasher.finalize());
+        std::fs::rename(path, self.dir.join(&digest))?;
+        Ok(Descriptor {
+            media_type,
+            digest: format!("sha256:{}", digest),
+            size,
+            annotations: Default::default(),
+        })
+    }
+
+    fn write_json(&self, value: &impl Serialize, media_type: &'static str) -> Result<Descriptor> {
+        let (path, mut writer) = self.create()?;
+        serde_json::to_writer(&mut writer, value)?;
+        self.commit(&path, writer, media_type)
+    }
+}
+
+/// Builds a layer whose content only depends on the names, content and modes of its files, so that identical files
+/// always produce the same layer digest.
+struct Layer {
+    path: PathBuf,
+    tar: tar::Builder<HashWriter<BufWriter<File>>>,
+    dirs: BTreeSet<PathBuf>,
+    progress: PhaseProgress,
+}
+
+impl Layer {
+    fn new(blobs: &Blobs, progress: &PhaseProgress) -> Result<Self> {
+        let (path, writer) = blobs.create()?;
+        let mut tar = tar::Builder::new(writer);
+        tar.mode(HeaderMode::Deterministic);
+        tar.follow_symlinks(false);
+        Ok(Self {
+            path,
+            tar,
+            dirs: Default::default(),
+            progress: progress.clone(),
+        })
+    }
+
+    /// Adds the missing parent directories of `name`.
+    fn add_parents(&mut self, name: &Path) -> Result<()> {
+        let parents = name.ancestors().skip(1).filter(|parent| !parent.as_os_str().is_empty());
+        for parent in parents.collect::<Vec<_>>().into_iter().rev() {
+            if !self.dirs.insert(parent.to_path_buf()) {
+                continue;
+            }
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            self.tar.append_data(&mut header, parent, std::io::empty())?;
+        }
+        Ok(())
+    }
+
+    /// Adds a file, symlink or directory (recursively) under `name`.
+    fn add(&mut self, src: &Path, name: &Path) -> Result<()> {
+        self.add_parents(name)?;
+        let metadata = std::fs::symlink_metadata(src)?;
+        if metadata.is_file() {
+            return append_file(&mut self.tar, src, name, HeaderMode::Deterministic, &self.progress);
+        }
+        self.tar
+            .append_path_with_name(src, name)
+            .with_context(|| format!("Failed to pack {}", src.display()))?;
+        if metadata.is_dir() {
+            self.dirs.insert(name.to_path_buf());
+            for entry in read_dir_sorted(src)? {
+                self.add(&entry.path(), &name.join(entry.file_name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, blobs: &Blobs) -> Result<Descriptor> {
+        let writer = self.tar.into_inner()?;
+        blobs.commit(&self.path, writer, MEDIA_TYPE_LAYER)
+    }
+}
+
+/// Turns a package name into an image reference, eg. `@scope/My-App` into `scope/my-app:latest`.
+pub fn default_tag(name: &str) -> String {
+    let name = name
+        .trim_start_matches('@')
+        .to_lowercase()
+        .replace(|c: char| !c.is_ascii_alphanumeric() && !"._/-".contains(c), "-");
+    format!("{}:latest", name)
+}
+
+/// Maps the architecture of the host to its name in image configurations, which distinguishes the byte order of
+/// 64-bit PowerPC.
+fn get_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+        "powerpc64" => "ppc64",
+        "loongarch64" => "loong64",
+        arch => arch,
+    }
+}
+
+/// Writes a packed bundle at `src` as an OCI image layout tarball at `dest`, with the layers ordered from the least to
+/// the most frequently updated: the runtime, the yarn release, the cache files, and the application itself.
+///
+/// The image has no base layer, and runs `yarn start` in `/app` with the runtime of the bundle, which is required and
+/// installed at `/opt/node`. Each file added to a layer is reported.
+pub fn write(src: &Path, dest: &Path, yarn_path: &str, tag: &str, progress: &PhaseProgress) -> Result<()> {
+    let layout = src.with_extension("oci");
+    let blobs = Blobs {
+        dir: layout.join("blobs/sha256"),
+    };
+    std::fs::create_dir_all(&blobs.dir)?;
+    let result = (|| {
+        let app = Path::new(IMAGE_APP_DIR);
+        let mut layers = vec![];
+        let env = vec![format!("PATH=/{}/bin:{}", IMAGE_RUNTIME_DIR, IMAGE_PATH)];
+
+        // runtime
+        let runtimes = src.join(BUNDLE_RUNTIME_DIR);
+        if !runtimes.exists() {
+            bail!("Images have no base layer, so the bundle must embed a runtime.");
+        }
+        if !cfg!(target_os = "linux") {
+            bail!("Images can only embed a Linux runtime.");
+        }
+        let mut layer = Layer::new(&blobs, progress)?;
+        for entry in read_dir_sorted(&runtimes)? {
+            layer.add(&entry.path(), Path::new(IMAGE_RUNTIME_DIR))?;
+        }
+        layers.push(layer.finish(&blobs)?);
+        std::fs::remove_dir_all(&runtimes)?;
+
+        // yarn release
+        let mut layer = Layer::new(&blobs, progress)?;
+        layer.add(&src.join(yarn_path), &app.join(yarn_path))?;
+        layers.push(layer.finish(&blobs)?);
+        std::fs::remove_file(src.join(yarn_path))?;
+
+        // cache files
+        let cache = src.join(".yarn/cache");
+        let mut buckets = BTreeMap::<u8, Vec<_>>::new();
+        for entry in read_dir_sorted(&cache)? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let bucket = BUNDLE_CACHE_REGEX
+                .captures(&name)
+                .and_then(|captures| u8::from_str_radix(&captures[2][..2], 16).ok())
+                .map(|hash| hash % CACHE_LAYERS)
+                .unwrap_or_default();
+            buckets.entry(bucket).or_default().push(entry);
+        }
+        for entries in buckets.into_values() {
+            let mut layer = Layer::new(&blobs, progress)?;
+            for entry in entries {
+                layer.add(&entry.path(), &app.join(".yarn/cache").join(entry.file_name()))?;
+                std::fs::remove_file(entry.path())?;
+            }
+            layers.push(layer.finish(&blobs)?);
+        }
+
+        // application
+        let mut layer = Layer::new(&blobs, progress)?;
+        layer.add(src, app)?;
+        layers.push(layer.finish(&blobs)?);
+
+        let config = json!({
+            "architecture": get_architecture(),
+            "os": "linux",
+            "config": {
+                "Env": env,
+                "Entrypoint": [
+                    format!("/{}/bin/node", IMAGE_RUNTIME_DIR),
+                    format!("/{}/{}", IMAGE_APP_DIR, yarn_path),
+                ],
+                "Cmd": ["start"],
+                "WorkingDir": format!("/{}", IMAGE_APP_DIR),
+            },
+            "rootfs": {
+                "type": "layers",
+                // layers are not compressed, so their digests are also their diff IDs
+                "diff_ids": layers.iter().map(|layer| &layer.digest).collect::<Vec<_>>(),
+            },
+        });
+        let config = blobs.write_json(&config, MEDIA_TYPE_CONFIG)?;
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": MEDIA_TYPE_MANIFEST,
+            "config": config,
+            "layers": layers,
+        });
+        let mut manifest = blobs.write_json(&manifest, MEDIA_TYPE_MANIFEST)?;
+        manifest
+            .annotations
+            .insert("org.opencontainers.image.ref.name".into(), tag.to_string());
+        let index = json!({
+            "schemaVersion": 2,
+            "mediaType": MEDIA_TYPE_INDEX,
+            "manifests": [manifest],
+        });
+        std::fs::write(layout.join("index.json"), serde_json::to_vec(&index)?)?;
+        std::fs::write(layout.join("oci-layout"), r#"{"imageLayoutVersion":"1.0.0"}"#)?;
+
+        // older versions of `docker load` only understand the manifest of `docker save`
+        let blob_path = |descriptor: &Descriptor| format!("blobs/sha256/{}", &descriptor.digest["sha256:".len()..]);
+        let docker_manifest = json!([{
+            "Config": blob_path(&config),
+            "RepoTags": [tag],
+            "Layers": layers.iter().map(blob_path).collect::<Vec<_>>(),
+        }]);
+        std::fs::write(layout.join("manifest.json"), serde_json::to_vec(&docker_manifest)?)?;
+
+        let file = File::create(dest).with_context(|| format!("Failed to create file: {}", dest.display()))?;
+        // the files were already reported as they were added to the layers
+        write_tar(BufWriter::new(file), &layout, &PhaseProgress::silent(Phase::Write))?.flush()?;
+        Ok(())
+    })();
+    std::fs::remove_dir_all(&layout)?;
+    result
+}
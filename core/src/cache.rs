@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{Result, anyhow, bail};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use sha2::{Digest, Sha512};
+use uuid::Uuid;
+
+use crate::cleanup::Rollback;
+use crate::{Cirno, Meta, fs};
+
+static CACHE_KEY_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^(\s+cacheKey:\s*).*$").unwrap());
+
+static CHECKSUM_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^(\s+checksum:\s*)(.*)$").unwrap());
+
+#[derive(Debug, Default)]
+pub struct MigrateCacheOptions {
+    /// Only migrate entries from this cache key.
+    pub from: Option<String>,
+    /// Cache key to migrate to, which defaults to the cache key of the lockfile. The lockfile is updated to it.
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrateCacheReport {
+    /// Cache key which the cache entries were migrated to.
+    pub cache_key: String,
+    /// Entries that were renamed, because no other instance references them.
+    pub renamed: usize,
+    /// Entries that were copied, because other instances still reference them under their former cache key.
+    pub copied: usize,
+    /// Entries that are found under no cache key at all.
+    pub missing: Vec<String>,
+    /// Whether the cache key or checksums of the lockfile were updated.
+    pub lockfile_updated: bool,
+}
+
+/// Checksum of a cache file, in the format of the former checksum: prefixed with the cache key for Yarn 4 (eg.
+/// `10c0/<hex>`), or not for Yarn 3.
+async fn checksum(path: &Path, cache_key: &str, former: &str) -> Result<String> {
+    let hash = hex::encode(Sha512::digest(fs::read(path).await?));
+    Ok(match former.contains('/') {
+        true => format!("{}/{}", cache_key, hash),
+        false => hash,
+    })
+}
+
+impl Cirno {
+    /// Moves the shared cache entries of an application to the cache key of its lockfile, or to another cache key.
+    ///
+    /// Cache entries are named after the cache key of the Yarn release that produced them (eg. `-8.zip` for Yarn 3,
+    /// `-10c0.zip` for Yarn 4), or after the first 10 characters of their checksum. When an application moves to a
+    /// Yarn release with a new cache key, its entries would otherwise be fetched anew and the former ones collected by
+    /// `gc`. Entries which are already present under the new key are left untouched.
+    ///
+    /// Since the files of another cache key have other checksums, the checksums of the migrated entries are updated in
+    /// the lockfile, along with its cache key. If the migration fails, the entries are moved back and the copies removed.
+    /// Backups are not supported, since their lockfile is archived.
+    pub async fn migrate_cache(&self, id: &Uuid, options: &MigrateCacheOptions) -> Result<MigrateCacheReport> {
+        if !self.manifest.apps.iter().any(|app| &app.id == id) {
+            match self.get(id) {
+                Some(_) => bail!("Cannot migrate the cache of backup {}. Restore it first.", id),
+                None => bail!("Application {} not found.", id),
+            }
+        }
+        let app_dir = self.cwd.join("apps").join(id.to_string());
+        let meta = Meta::load(&app_dir).await?;
+        let metadata = &meta.yarn_lock.metadata;
+        metadata.check_version()?;
+        let cache_key = options.to.clone().unwrap_or_else(|| metadata.cache_key.clone());
+        let cache = self.load_cache().await?;
+        let cache_dir = self.cwd.join("home/.yarn/cache");
+
+        let mut referenced = HashSet::new();
+        for (_, other, other_meta) in self.load_instance_metas().await? {
+            if &other == id {
+                continue;
+            }
+            for (prefix, name) in other_meta.yarn_lock.find_cache_files(&cache)? {
+                if let Some(name) = name {
+                    referenced.insert((prefix, name.clone()));
+                }
+            }
+        }
+
+        let mut rollback = Rollback::default();
+        let result = async {
+            let mut report = MigrateCacheReport {
+                cache_key: cache_key.clone(),
+                ..Default::default()
+            };
+            // former and new checksums of the migrated entries
+            let mut checksums = Vec::new();
+            for (locator, entry) in meta.yarn_lock.get_cache_entries()? {
+                self.check_cancelled()?;
+                let prefix = locator.slugify();
+                let dest = cache_dir.join(format!("{}-{}.zip", prefix, cache_key));
+                let existing = cache
+                    .get(&cache_key)
+                    .is_some_and(|entries| entries.contains_key(&prefix));
+                if !existing {
+                    let mut keys = meta.yarn_lock.cache_keys(entry);
+                    keys.extend(cache.keys().cloned());
+                    let source = keys
+                        .iter()
+                        .filter(|key| *key != &cache_key && options.from.as_ref().is_none_or(|from| *key == from))
+                        .find_map(|key| cache.get(key)?.get(&prefix));
+                    let Some(name) = source else {
+                        report.missing.push(prefix);
+                        continue;
+                    };
+                    if referenced.contains(&(prefix.clone(), name.clone())) {
+                        rollback.push(dest.clone());
+                        fs::copy(cache_dir.join(name), &dest).await?;
+                        report.copied += 1;
+                    } else {
+                        rollback.moved(cache_dir.join(name), dest.clone());
+                        fs::rename(cache_dir.join(name), &dest).await?;
+                        report.renamed += 1;
+                    }
+                }
+                if let Some(former) = &entry.checksum
+                    && (!existing || cache_key != metadata.cache_key)
+                {
+                    checksums.push((former.clone(), checksum(&dest, &cache_key, former).await?));
+                }
+            }
+
+            let lockfile = fs::read_to_string(app_dir.join("yarn.lock")).await?;
+            let mut updated = CHECKSUM_REGEX
+                .replace_all(&lockfile, |captures: &Captures| {
+                    let value = captures[2].trim().trim_matches('"');
+                    match checksums.iter().find(|(former, _)| former == value) {
+                        Some((_, checksum)) => format!("{}{}", &captures[1], checksum),
+                        None => captures[0].to_string(),
+                    }
+                })
+                .into_owned();
+            if cache_key != metadata.cache_key {
+                let captures = CACHE_KEY_REGEX
+                    .captures(&updated)
+                    .ok_or_else(|| anyhow!("Failed to find the cache key of the lockfile."))?;
+                let range = captures.get(0).unwrap().range();
+                let line = format!("{}{}", &captures[1], cache_key);
+                updated.replace_range(range, &line);
+            }
+            if updated != lockfile {
+                fs::write(app_dir.join("yarn.lock"), updated).await?;
+                report.lockfile_updated = true;
+            }
+            Ok(report)
+        }
+        .await;
+        if result.is_err()
+            && let Err(error) = rollback.revert().await
+        {
+            self.warn(format!("Failed to revert the cache migration: {:#}", error));
+        }
+        result
+    }
+}
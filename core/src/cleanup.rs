@@ -0,0 +1,155 @@
+//! Cleanup of interrupted operations.
+//!
+//! Operations work in `tmp/.cirno/<uuid>` (and siblings such as `tmp/.cirno/<uuid>.git`), and hold an exclusive lock
+//! on `tmp/.cirno/<uuid>.lock` until they are done. Entries whose lock is not held were left by a crashed or killed
+//! process, and are removed by [`sweep`] when the environment is opened. `tmp/` itself is shared with the TypeScript
+//! CLI and is the `TMP` of running applications, so nothing outside of `tmp/.cirno` is ever removed.
+
+use std::fs::{File, TryLockError};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+/// Folder of `tmp/` which holds the temporary directories of operations.
+const OPERATIONS_DIR: &str = ".cirno";
+
+/// Temporary directory of an operation. The directory itself is not created.
+pub(crate) struct TempDir {
+    path: PathBuf,
+    lock: File,
+}
+
+impl TempDir {
+    pub(crate) async fn new(tmp: &Path) -> Result<Self> {
+        let tmp = tmp.join(OPERATIONS_DIR);
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&tmp).with_context(|| format!("Failed to create directory: {}", tmp.display()))?;
+            let name = Uuid::new_v4().to_string();
+            let lock_path = tmp.join(format!("{}.lock", name));
+            let lock = File::create_new(&lock_path)
+                .with_context(|| format!("Failed to create file: {}", lock_path.display()))?;
+            lock.lock()?;
+            Ok(Self {
+                path: tmp.join(name),
+                lock,
+            })
+        })
+        .await?
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Removes the directory, its siblings and the lock.
+    pub(crate) async fn remove(self) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            let (Some(tmp), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+                return Ok(());
+            };
+            remove_entries(tmp, &name.to_string_lossy())?;
+            drop(self.lock);
+            remove_path(&self.path.with_extension("lock"))
+        })
+        .await?
+    }
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    let result = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(error) => Err(error),
+    };
+    match result {
+        Err(error) if error.kind() != ErrorKind::NotFound => {
+            Err(error).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Removes `tmp/<name>` and `tmp/<name>.*`, except the lock.
+fn remove_entries(tmp: &Path, name: &str) -> Result<()> {
+    for entry in std::fs::read_dir(tmp).with_context(|| format!("Failed to read directory: {}", tmp.display()))? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let (stem, extension) = file_name.split_once('.').unwrap_or((&file_name, ""));
+        if stem == name && extension != "lock" {
+            remove_path(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the entries of `tmp/.cirno` left by operations which are no longer running. Entries which cannot be removed
+/// are skipped.
+pub(crate) async fn sweep(tmp: &Path) -> Result<()> {
+    let tmp = tmp.join(OPERATIONS_DIR);
+    tokio::task::spawn_blocking(move || {
+        let entries = match std::fs::read_dir(&tmp) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error).with_context(|| format!("Failed to read directory: {}", tmp.display())),
+        };
+        let mut names = entries
+            .filter_map(|entry| {
+                let file_name = entry.ok()?.file_name().to_string_lossy().to_string();
+                let stem = file_name.split('.').next()?;
+                Uuid::parse_str(stem).ok().map(|_| stem.to_string())
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let lock_path = tmp.join(format!("{}.lock", name));
+            match File::open(&lock_path).map(|lock| lock.try_lock()) {
+                Ok(Err(TryLockError::WouldBlock)) => continue,
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(_) => continue,
+            }
+            if remove_entries(&tmp, &name).is_ok() {
+                let _ = remove_path(&lock_path);
+            }
+        }
+        Ok(())
+    })
+    .await?
+}
+
+/// Files and directories added to the shared stores of the environment (yarn releases, runtimes and cache files) by
+/// an operation, or moved within them, so that they can be removed or moved back if it fails. Files which already
+/// existed must not be recorded as added.
+#[derive(Debug, Default)]
+pub(crate) struct Rollback(Vec<Change>);
+
+#[derive(Debug)]
+enum Change {
+    Added(PathBuf),
+    Moved { from: PathBuf, to: PathBuf },
+}
+
+impl Rollback {
+    pub(crate) fn push(&mut self, path: PathBuf) {
+        self.0.push(Change::Added(path));
+    }
+
+    pub(crate) fn moved(&mut self, from: PathBuf, to: PathBuf) {
+        self.0.push(Change::Moved { from, to });
+    }
+
+    /// Undoes the changes, the latest first.
+    pub(crate) async fn revert(self) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            self.0.iter().rev().try_for_each(|change| match change {
+                Change::Added(path) => remove_path(path),
+                Change::Moved { from, to } => std::fs::rename(to, from)
+                    .with_context(|| format!("Failed to move {} back to {}", to.display(), from.display())),
+            })
+        })
+        .await?
+    }
+}
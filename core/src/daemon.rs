@@ -0,0 +1,129 @@
+//! State shared by the daemon with commands.
+//!
+//! The daemon holds an exclusive lock on [`DAEMON_LOCK`] while it runs, and records the start and exit of the
+//! applications it runs in [`STATUS_FILE`]. A record whose process has not exited is only meaningful while the daemon
+//! runs, since a daemon which crashed could not record the exit.
+
+use std::collections::HashMap;
+use std::fs::{File, TryLockError};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{Cirno, fs};
+
+/// Lock file held by a running daemon, in the environment.
+pub const DAEMON_LOCK: &str = "daemon.lock";
+
+/// Last start and exit of each application run by the daemon, in the environment.
+pub const STATUS_FILE: &str = "daemon-status.json";
+
+/// Serializes the updates of the status file by the tasks of the daemon.
+static STATUS_MUTEX: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessRecord {
+    /// Time of the last start, in the format of `Date.prototype.toISOString`.
+    pub started: String,
+    /// Time of the last exit, or `None` if the process has not exited since its last start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exited: Option<String>,
+    /// Exit code of the last exit, or `None` if the process has not exited or was terminated by a signal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonStatus {
+    /// Whether a daemon is running on the environment.
+    pub running: bool,
+    /// Records of the applications, by instance ID.
+    pub processes: HashMap<String, ProcessRecord>,
+}
+
+impl DaemonStatus {
+    /// Whether the daemon is running an application.
+    pub fn is_running(&self, id: &str) -> bool {
+        self.running && self.processes.get(id).is_some_and(|record| record.exited.is_none())
+    }
+}
+
+/// Lock held by the daemon while it runs, released when dropped.
+pub struct DaemonLock {
+    _file: File,
+}
+
+impl DaemonLock {
+    /// Acquires the lock of the environment, which fails if another daemon runs on it.
+    pub fn acquire(cwd: &Path) -> Result<Self> {
+        let path = cwd.join(DAEMON_LOCK);
+        let file = File::create(&path).with_context(|| format!("Failed to create file: {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => bail!("Another daemon is running on this environment."),
+            Err(TryLockError::Error(error)) => Err(error).with_context(|| format!("Failed to lock {}", path.display())),
+        }
+    }
+}
+
+/// Whether a daemon holds the lock of the environment.
+fn is_daemon_running(cwd: &Path) -> bool {
+    let Ok(file) = File::open(cwd.join(DAEMON_LOCK)) else {
+        return false;
+    };
+    matches!(file.try_lock(), Err(TryLockError::WouldBlock))
+}
+
+async fn load_records(cwd: &Path) -> Result<HashMap<String, ProcessRecord>> {
+    let path = cwd.join(STATUS_FILE);
+    if !tokio::fs::try_exists(&path).await? {
+        return Ok(HashMap::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(&path).await?)?)
+}
+
+async fn update_record(
+    cwd: &Path,
+    id: &str,
+    update: impl FnOnce(Option<ProcessRecord>) -> ProcessRecord,
+) -> Result<()> {
+    let _guard = STATUS_MUTEX.lock().await;
+    let mut records = load_records(cwd).await?;
+    let record = update(records.remove(id));
+    records.insert(id.to_string(), record);
+    fs::write(cwd.join(STATUS_FILE), serde_json::to_string_pretty(&records)?).await
+}
+
+/// Records the start of an application, called by the daemon.
+pub async fn record_start(cwd: &Path, id: &str) -> Result<()> {
+    update_record(cwd, id, |_| ProcessRecord {
+        started: crate::get_timestamp(),
+        exited: None,
+        exit_code: None,
+    })
+    .await
+}
+
+/// Records the exit of an application, called by the daemon.
+pub async fn record_exit(cwd: &Path, id: &str, exit_code: Option<i32>) -> Result<()> {
+    update_record(cwd, id, |record| ProcessRecord {
+        started: record.map(|record| record.started).unwrap_or_default(),
+        exited: Some(crate::get_timestamp()),
+        exit_code,
+    })
+    .await
+}
+
+impl Cirno {
+    /// Returns whether a daemon runs on the environment, and what it recorded of the applications it runs.
+    pub async fn daemon_status(&self) -> Result<DaemonStatus> {
+        Ok(DaemonStatus {
+            running: is_daemon_running(&self.cwd),
+            processes: load_records(&self.cwd).await?,
+        })
+    }
+}
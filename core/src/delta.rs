@@ -0,0 +1,334 @@
+//! Delta bundles.
+//!
+//! A delta bundle (`.cirnodelta`) turns an instance of an application into another one, such as a backup into the
+//! current head. It contains the files and cache files that differ from the base instance, the paths deleted from it,
+//! and the fingerprints of both instances, so that it can only be applied onto an instance identical to its base.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::baka::BackupOptions;
+use crate::bundle::{BundleFormat, join_relative};
+use crate::cleanup::Rollback;
+use crate::report::Phase;
+use crate::yarn::{PackageManager, ReleaseHash};
+use crate::{Cirno, Meta, fs};
+
+/// Manifest of a delta bundle, at the root of the bundle.
+pub const DELTA_FILE: &str = "cirno-delta.json";
+const DELTA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaManifest {
+    pub version: u32,
+    /// Fingerprint of the instance the delta applies onto.
+    pub base: String,
+    /// Fingerprint of the instance the delta produces.
+    pub target: String,
+    /// Files and directories (ending with `/`) of the base instance removed from the target instance.
+    pub deleted: Vec<String>,
+    /// Files added or modified, stored under `files/`, and directories (ending with `/`) added.
+    pub changed: Vec<String>,
+    /// Cache files referenced by the target instance but not by the base one, stored under `cache/`.
+    pub cache: Vec<String>,
+    /// Yarn release of the target instance, stored under `releases/` if it differs from the one of the base.
+    pub release: Option<String>,
+}
+
+/// Content of an instance: the digest of each file (or the target of each symlink) by path, and the cache files it
+/// references. Directories are listed with a trailing `/`, so that empty ones are part of the content.
+#[derive(Debug, Default)]
+struct Snapshot {
+    files: BTreeMap<String, String>,
+    cache: BTreeSet<String>,
+}
+
+impl Snapshot {
+    fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (path, digest) in &self.files {
+            hasher.update(format!("{}\t{}\n", path, digest));
+        }
+        for name in &self.cache {
+            hasher.update(format!("cache\t{}\n", name));
+        }
+        format!("sha256:{}", hex::encode(hasher.finalize()))
+    }
+}
+
+/// Hashes the files of `root`, with paths separated by `/` so that fingerprints do not depend on the platform.
+fn scan_dir(root: &Path, base: &str, skip_node_modules: bool, files: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in std::fs::read_dir(root).with_context(|| format!("Failed to read directory: {}", root.display()))? {
+        let entry = entry?;
+        let name = format!("{}{}", base, entry.file_name().to_string_lossy());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !(skip_node_modules && entry.file_name() == "node_modules") {
+                files.insert(format!("{}/", name), "dir".to_string());
+                scan_dir(&entry.path(), &format!("{}/", name), skip_node_modules, files)?;
+            }
+        } else if file_type.is_symlink() {
+            let target = std::fs::read_link(entry.path())?;
+            files.insert(name, format!("link:{}", target.to_string_lossy()));
+        } else {
+            let mut hasher = Sha256::new();
+            let mut file =
+                File::open(entry.path()).with_context(|| format!("Failed to read file: {}", entry.path().display()))?;
+            std::io::copy(&mut file, &mut hasher)?;
+            files.insert(name, hex::encode(hasher.finalize()));
+        }
+    }
+    Ok(())
+}
+
+/// Copies a file or a symlink, creating the parent directories of `dest`.
+async fn copy_entry(src: &Path, dest: &Path) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    if tokio::fs::symlink_metadata(src).await?.is_symlink() {
+        fs::copy_symlink(src, dest).await
+    } else {
+        fs::copy(src, dest).await.map(|_| ())
+    }
+}
+
+impl Cirno {
+    /// Names of the cache files referenced by an instance.
+    async fn get_cache_names(&self, meta: &Meta) -> Result<BTreeSet<String>> {
+        let cache = self.load_cache().await?;
+        meta.yarn_lock
+            .find_cache_files(&cache)?
+            .into_iter()
+            .map(|(prefix, name)| name.cloned().ok_or_else(|| anyhow!("Cache not found: {}", prefix)))
+            .collect()
+    }
+
+    /// Takes a snapshot of an instance copied to `root`. The `node_modules` folders of applications that do not use
+    /// PnP are ignored, since they are never copied nor backed up.
+    async fn snapshot(&self, root: &Path) -> Result<(Meta, Snapshot)> {
+        let meta = Meta::load(root).await?;
+        let skip_node_modules = self.node_linker(&meta.yarn_rc).await?.uses_node_modules();
+        let root = root.to_path_buf();
+        let files = tokio::task::spawn_blocking(move || {
+            let mut files = BTreeMap::new();
+            scan_dir(&root, "", skip_node_modules, &mut files).map(|()| files)
+        })
+        .await??;
+        let cache = self.get_cache_names(&meta).await?;
+        Ok((meta, Snapshot { files, cache }))
+    }
+
+    /// Exports the changes from instance `base` to instance `id` of the same application as a delta bundle at `dest`.
+    pub async fn export_delta(&self, id: &Uuid, base: &Uuid, dest: &Path) -> Result<DeltaManifest> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        let base_app = self.get(base).ok_or_else(|| anyhow!("Instance {} not found.", base))?;
+        if app.id != base_app.id {
+            bail!("Instance {} is not an instance of application {}.", base, app.id);
+        }
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let result = async {
+            let (base_dir, target_dir, delta_dir) = (temp.join("base"), temp.join("target"), temp.join("delta"));
+            self.clone(app, base, &base_dir).await?;
+            self.clone(app, id, &target_dir).await?;
+            let (base_meta, base_snapshot) = self.snapshot(&base_dir).await?;
+            let (target_meta, target_snapshot) = self.snapshot(&target_dir).await?;
+
+            let mut manifest = DeltaManifest {
+                version: DELTA_VERSION,
+                base: base_snapshot.fingerprint(),
+                target: target_snapshot.fingerprint(),
+                deleted: vec![],
+                changed: vec![],
+                cache: vec![],
+                release: None,
+            };
+            fs::create_dir_all(&delta_dir).await?;
+            for path in base_snapshot.files.keys() {
+                if !target_snapshot.files.contains_key(path) {
+                    manifest.deleted.push(path.clone());
+                }
+            }
+            for (path, digest) in &target_snapshot.files {
+                if base_snapshot.files.get(path) != Some(digest) {
+                    if !path.ends_with('/') {
+                        copy_entry(&target_dir.join(path), &delta_dir.join("files").join(path)).await?;
+                    }
+                    manifest.changed.push(path.clone());
+                }
+            }
+            for name in target_snapshot.cache.difference(&base_snapshot.cache) {
+                copy_entry(
+                    &self.cwd.join("home/.yarn/cache").join(name),
+                    &delta_dir.join("cache").join(name),
+                )
+                .await?;
+                manifest.cache.push(name.clone());
+            }
+            let release = PackageManager::yarn(&target_meta.package.package_manager)?.release_name();
+            if PackageManager::yarn(&base_meta.package.package_manager)?.release_name() != release {
+                copy_entry(
+                    &self.cwd.join("home/.yarn/releases").join(&release),
+                    &delta_dir.join("releases").join(&release),
+                )
+                .await?;
+                manifest.release = Some(release);
+            }
+
+            fs::write(delta_dir.join(DELTA_FILE), serde_json::to_string_pretty(&manifest)?).await?;
+            let progress = self.start_phase(Phase::Write, None, None)?;
+            let (dest, write_progress) = (dest.to_path_buf(), progress.clone());
+            tokio::task::spawn_blocking(move || BundleFormat::TarZst.write(&delta_dir, &dest, &write_progress))
+                .await??;
+            progress.finish();
+            Ok(manifest)
+        }
+        .await;
+        temp_dir.remove().await?;
+        result
+    }
+
+    /// Applies a delta bundle onto the head instance `id`, which must be identical to the base of the delta.
+    ///
+    /// The result is verified against the fingerprint of the delta before it replaces the head instance, which is
+    /// backed up first. Returns the ID of the backup.
+    pub async fn apply_delta(&mut self, src: &Path, id: &Uuid) -> Result<Uuid> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        if &app.id != id {
+            bail!("Cannot apply a delta onto a base instance.");
+        }
+        let head = self.cwd.join("apps").join(id.to_string());
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let mut rollback = Rollback::default();
+        let result = async {
+            let (delta_dir, target_dir) = (temp.join("delta"), temp.join("target"));
+            let progress = self.start_phase(Phase::Unpack, None, Some(fs::metadata(src).await?.len()))?;
+            let (archive, dest, extract_progress) = (src.to_path_buf(), delta_dir.clone(), progress.clone());
+            tokio::task::spawn_blocking(move || {
+                BundleFormat::detect(&archive)?.extract(&archive, &dest, &extract_progress)
+            })
+            .await??;
+            progress.finish();
+            let manifest: DeltaManifest = serde_json::from_str(&fs::read_to_string(delta_dir.join(DELTA_FILE)).await?)?;
+            if manifest.version != DELTA_VERSION {
+                bail!("Unsupported delta version: {}", manifest.version);
+            }
+
+            let (_, snapshot) = self.snapshot(&head).await?;
+            let fingerprint = snapshot.fingerprint();
+            if fingerprint != manifest.base {
+                bail!(
+                    "Instance {} does not match the base of the delta (expected {}, found {}).",
+                    id,
+                    manifest.base,
+                    fingerprint
+                );
+            }
+
+            let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+            self.clone(app, id, &target_dir).await?;
+            // directories are listed before their content, which they may already have removed
+            for path in &manifest.deleted {
+                let dest =
+                    join_relative(&target_dir, path).ok_or_else(|| anyhow!("Invalid path in delta: {}", path))?;
+                let Ok(metadata) = tokio::fs::symlink_metadata(&dest).await else {
+                    continue;
+                };
+                match metadata.is_dir() {
+                    true => fs::remove_dir_all(&dest).await?,
+                    false => fs::remove_file(&dest).await?,
+                }
+            }
+            for path in &manifest.changed {
+                let dest =
+                    join_relative(&target_dir, path).ok_or_else(|| anyhow!("Invalid path in delta: {}", path))?;
+                if path.ends_with('/') {
+                    fs::create_dir_all(&dest).await?;
+                    continue;
+                }
+                if tokio::fs::symlink_metadata(&dest).await.is_ok() {
+                    fs::remove_file(&dest).await?;
+                }
+                copy_entry(&delta_dir.join("files").join(path), &dest).await?;
+            }
+            for (name, dir) in manifest
+                .cache
+                .iter()
+                .map(|name| (name, "cache"))
+                .chain(manifest.release.iter().map(|name| (name, "releases")))
+            {
+                let dest = join_relative(&self.cwd.join("home/.yarn").join(dir), name)
+                    .ok_or_else(|| anyhow!("Invalid path in delta: {}", name))?;
+                if !tokio::fs::try_exists(&dest).await? {
+                    rollback.push(dest.clone());
+                    fs::rename(delta_dir.join(dir).join(name), dest).await?;
+                }
+            }
+
+            let (meta, snapshot) = self.snapshot(&target_dir).await?;
+            let fingerprint = snapshot.fingerprint();
+            if fingerprint != manifest.target {
+                bail!(
+                    "Delta produced an unexpected instance (expected {}, found {}).",
+                    manifest.target,
+                    fingerprint
+                );
+            }
+
+            // the release may have changed, whether it is shipped by the delta or already installed
+            let package_manager = PackageManager::yarn(&meta.package.package_manager)?;
+            let release_hash = package_manager.verify(&self.cwd.join("home/.yarn/releases")).await?;
+
+            // dependencies are installed before the swap, so that a failure leaves the head instance untouched
+            if self.node_linker(&meta.yarn_rc).await?.uses_node_modules() {
+                self.install(&target_dir).await?;
+            }
+
+            self.check_cancelled()?;
+            let options = BackupOptions {
+                r#type: Some("delta".to_string()),
+                message: Some(format!("Before applying delta {}", src.display())),
+                ..Default::default()
+            };
+            let backup = self.backup(id, &options).await?;
+            let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+            let snapshot = (app.clone(), self.state.get(&id.to_string()).cloned());
+            let old = temp.join("old");
+            fs::rename(&head, &old).await?;
+            let swapped = async {
+                fs::rename(&target_dir, &head).await?;
+                if let Some(app) = self.manifest.apps.iter_mut().find(|app| &app.id == id) {
+                    app.release_hash = release_hash.as_ref().map(ReleaseHash::to_string);
+                }
+                self.save().await
+            }
+            .await;
+            if let Err(error) = swapped {
+                if tokio::fs::try_exists(&head).await? {
+                    fs::rename(&head, &target_dir).await?;
+                }
+                fs::rename(&old, &head).await?;
+                self.rollback_app(snapshot).await;
+                return Err(error);
+            }
+            Ok(backup)
+        }
+        .await;
+        if result.is_err()
+            && let Err(error) = rollback.revert().await
+        {
+            self.warn(format!("Failed to revert the delta: {:#}", error));
+        }
+        temp_dir.remove().await?;
+        result
+    }
+}
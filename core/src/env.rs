@@ -0,0 +1,316 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::runtime::{self, RUNTIME_DIR, Runtime};
+use crate::yarn::PackageManager;
+use crate::{App, Cirno, ENTRY_FILE, Manifest, Package, fs};
+
+/// File holding the secret variables of every application, readable by its owner only.
+pub const SECRETS_FILE: &str = "cirno-secrets.yml";
+
+/// Host variables passed to applications when no policy is configured.
+const DEFAULT_ALLOW: [&str; 22] = [
+    "PATH",
+    "LANG",
+    "LANGUAGE",
+    "LC_*",
+    "TERM",
+    "COLORTERM",
+    "NO_COLOR",
+    "FORCE_COLOR",
+    "TZ",
+    "USER",
+    "USERNAME",
+    "LOGNAME",
+    "SHELL",
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "COMSPEC",
+    "PATHEXT",
+    "WINDIR",
+    "PROGRAMDATA",
+    "PROGRAMFILES",
+    "NUMBER_OF_PROCESSORS",
+    "PROCESSOR_ARCHITECTURE",
+];
+
+/// Environment policy of the environment, configured under `config.env` in `cirno.yml`.
+///
+/// Only the host variables matching the allow list are passed to applications. Patterns may end with `*` to match a
+/// prefix (eg. `LC_*`). Tokens, proxies and `NODE_OPTIONS` must be allowed explicitly or declared per application.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct EnvPolicy {
+    pub allow: Vec<String>,
+}
+
+impl Default for EnvPolicy {
+    fn default() -> Self {
+        Self {
+            allow: DEFAULT_ALLOW.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+}
+
+/// Compares variable names, which are case-insensitive on Windows (eg. `Path` is `PATH`).
+fn same_name(a: &str, b: &str) -> bool {
+    if cfg!(windows) {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// Iterates over the variables of the host. Variables which are not valid UTF-8 are skipped, since they cannot be
+/// passed on faithfully.
+fn host_vars() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+}
+
+impl EnvPolicy {
+    pub fn allows(&self, name: &str) -> bool {
+        self.allow.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.get(..prefix.len()).is_some_and(|start| same_name(start, prefix)),
+            None => same_name(name, pattern),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvSource {
+    /// Allowed by the environment policy.
+    Host,
+    /// Declared under `env` of the application in `cirno.yml`.
+    App,
+    /// Declared in the secrets file.
+    Secret,
+    /// Enforced by Cirno to isolate the application.
+    Cirno,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    pub source: EnvSource,
+}
+
+/// Effective environment of an application process.
+#[derive(Debug, Default)]
+pub struct Environment {
+    /// Managed runtime of the application, whose `bin` directory is prepended to `PATH`.
+    pub runtime: Option<Runtime>,
+    pub vars: Vec<EnvVar>,
+}
+
+impl Environment {
+    /// Environment passing every host variable through, used when the environment of an application cannot be built.
+    pub fn host() -> Self {
+        let mut env = Self::default();
+        for (name, value) in host_vars() {
+            env.set(name, value, EnvSource::Host);
+        }
+        env
+    }
+
+    fn set(&mut self, name: impl Into<String>, value: impl Into<String>, source: EnvSource) {
+        let name = name.into();
+        self.vars.retain(|var| !same_name(&var.name, &name));
+        self.vars.push(EnvVar {
+            name,
+            value: value.into(),
+            source,
+        });
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|var| same_name(&var.name, name))
+            .map(|var| var.value.as_str())
+    }
+
+    /// Iterates over the variables, to be passed to [`Command::envs`](std::process::Command::envs) after clearing
+    /// the inherited environment.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.iter().map(|var| (var.name.as_str(), var.value.as_str()))
+    }
+}
+
+/// Secret variables of each application, stored in [`SECRETS_FILE`].
+pub type Secrets = HashMap<Uuid, BTreeMap<String, String>>;
+
+pub async fn load_secrets(root: &Path) -> Result<Secrets> {
+    let path = root.join(SECRETS_FILE);
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Default::default()),
+        Err(error) => return Err(error).with_context(|| format!("Failed to read file: {}", path.display())),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).await?.permissions().mode();
+        if mode & 0o077 != 0 {
+            bail!(
+                "Secrets file {} is accessible by other users, run `chmod 600` on it.",
+                path.display()
+            );
+        }
+    }
+    Ok(serde_yaml_ng::from_str(&content)?)
+}
+
+pub async fn save_secrets(root: &Path, secrets: &Secrets) -> Result<()> {
+    let path = root.join(SECRETS_FILE);
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options
+        .open(&path)
+        .await
+        .with_context(|| format!("Failed to write file: {}", path.display()))?;
+    file.write_all(serde_yaml_ng::to_string(secrets)?.as_bytes()).await?;
+    Ok(())
+}
+
+/// Builds the environment of a process running in `cwd`, which is either an application directory or a temporary
+/// copy of one (in which case no application variables apply).
+///
+/// Layers are applied in order, each overriding the previous ones: allowed host variables, application variables,
+/// secrets, and the variables enforced by Cirno (home, temporary directories, yarn path and runtime).
+async fn build(root: &Path, manifest: &Manifest, cwd: &Path) -> Result<Environment> {
+    let policy = manifest.config.env.clone().unwrap_or_default();
+    let mut env = Environment::default();
+    for (name, value) in host_vars() {
+        if policy.allows(&name) {
+            env.set(name, value, EnvSource::Host);
+        }
+    }
+
+    let app = manifest
+        .apps
+        .iter()
+        .find(|app| cwd == root.join("apps").join(app.id.to_string()));
+    if let Some(app) = app {
+        for (name, value) in &app.env {
+            env.set(name, value, EnvSource::App);
+        }
+        if let Some(secrets) = load_secrets(root).await?.remove(&app.id) {
+            for (name, value) in secrets {
+                env.set(name, value, EnvSource::Secret);
+            }
+        }
+    }
+
+    let path_str = |path: PathBuf| path.to_string_lossy().to_string();
+    env.set("HOME", path_str(root.join("home")), EnvSource::Cirno);
+    for key in ["TEMP", "TMP", "TMPDIR"] {
+        env.set(key, path_str(root.join("tmp")), EnvSource::Cirno);
+    }
+    env.set(
+        "YARN_GLOBAL_FOLDER",
+        path_str(root.join("home/.yarn")),
+        EnvSource::Cirno,
+    );
+    let package: Package = serde_json::from_str(&fs::read_to_string(cwd.join("package.json")).await?)?;
+    if let Ok(package_manager) = PackageManager::yarn(&package.package_manager) {
+        let yarn_path = root.join("home/.yarn/releases").join(package_manager.release_name());
+        env.set("YARN_YARN_PATH", path_str(yarn_path), EnvSource::Cirno);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        env.set("APPDATA", path_str(root.join("home/AppData/Roaming")), EnvSource::Cirno);
+        env.set(
+            "LOCALAPPDATA",
+            path_str(root.join("home/AppData/Local")),
+            EnvSource::Cirno,
+        );
+        env.set("USERPROFILE", path_str(root.join("home")), EnvSource::Cirno);
+    }
+    let host_keys: &[&str] = if cfg!(target_os = "windows") {
+        &[
+            "HOME",
+            "TEMP",
+            "TMP",
+            "TMPDIR",
+            "APPDATA",
+            "LOCALAPPDATA",
+            "USERPROFILE",
+        ]
+    } else {
+        &["HOME", "TEMP", "TMP", "TMPDIR"]
+    };
+    for key in host_keys {
+        if let Ok(value) = std::env::var(key) {
+            env.set(format!("CIRNO_HOST_{}", key), value, EnvSource::Cirno);
+        }
+    }
+
+    // scripts spawning `node` should use the same runtime as yarn itself
+    env.runtime = runtime::resolve(&root.join(RUNTIME_DIR), cwd).await?;
+    if let Some(runtime) = &env.runtime {
+        let paths = env.get("PATH").unwrap_or_default();
+        let paths = std::iter::once(runtime.bin_dir()).chain(std::env::split_paths(paths));
+        let paths = std::env::join_paths(paths)?.to_string_lossy().to_string();
+        env.set("PATH", paths, EnvSource::Cirno);
+    }
+    Ok(env)
+}
+
+/// Builds the environment of a process running in `cwd` without opening the whole environment at `root`, which is
+/// used by the daemon.
+pub async fn load(root: &Path, cwd: &Path) -> Result<Environment> {
+    let manifest: Manifest = serde_yaml_ng::from_str(&fs::read_to_string(root.join(ENTRY_FILE)).await?)?;
+    build(root, &manifest, cwd).await
+}
+
+impl Cirno {
+    /// Builds the environment of a process running in `cwd`, see [`load`].
+    pub async fn environment(&self, cwd: &Path) -> Result<Environment> {
+        build(&self.cwd, &self.manifest, cwd).await
+    }
+
+    fn get_app_mut(&mut self, id: &Uuid) -> Result<&mut App> {
+        self.manifest
+            .apps
+            .iter_mut()
+            .find(|app| &app.id == id)
+            .ok_or_else(|| anyhow!("Application {} not found.", id))
+    }
+
+    /// Declares a variable of an application, or removes it if `value` is `None`.
+    pub async fn set_env(&mut self, id: &Uuid, name: &str, value: Option<String>) -> Result<()> {
+        let app = self.get_app_mut(id)?;
+        match value {
+            Some(value) => app.env.insert(name.to_string(), value),
+            None => app.env.remove(name),
+        };
+        self.save().await
+    }
+
+    /// Declares a secret variable of an application, or removes it if `value` is `None`.
+    pub async fn set_secret(&mut self, id: &Uuid, name: &str, value: Option<String>) -> Result<()> {
+        self.get_app_mut(id)?;
+        let mut secrets = load_secrets(&self.cwd).await?;
+        let vars = secrets.entry(*id).or_default();
+        match value {
+            Some(value) => vars.insert(name.to_string(), value),
+            None => vars.remove(name),
+        };
+        if vars.is_empty() {
+            secrets.remove(id);
+        }
+        save_secrets(&self.cwd, &secrets).await
+    }
+}
@@ -1,17 +1,28 @@
+use std::io::ErrorKind;
 use std::path::Path;
 use std::task::Poll;
 
 use anyhow::{Context, Result};
 use tokio::fs;
 
+/// Copies a file with its permissions and, where the platform allows it, its modification time.
 pub async fn copy(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<u64> {
-    fs::copy(&src, &dst).await.with_context(|| {
+    let size = fs::copy(&src, &dst).await.with_context(|| {
         format!(
             "Failed to copy file from {} to {}",
             src.as_ref().display(),
             dst.as_ref().display()
         )
+    })?;
+    // the copy may be read-only, so it is opened for reading, which is enough to set its times on unix
+    let (src, dst) = (src.as_ref().to_path_buf(), dst.as_ref().to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        let modified = std::fs::metadata(src)?.modified()?;
+        std::fs::File::open(dst)?.set_modified(modified)
     })
+    .await?
+    .ok();
+    Ok(size)
 }
 
 pub async fn create_dir_all(path: impl AsRef<Path>) -> Result<()> {
@@ -88,3 +99,101 @@ pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result
         .await
         .with_context(|| format!("Failed to write file: {}", path.as_ref().display()))
 }
+
+pub async fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+    fs::rename(&from, &to).await.with_context(|| {
+        format!(
+            "Failed to rename {} to {}",
+            from.as_ref().display(),
+            to.as_ref().display()
+        )
+    })
+}
+
+pub async fn metadata(path: impl AsRef<Path>) -> Result<std::fs::Metadata> {
+    fs::metadata(&path)
+        .await
+        .with_context(|| format!("Failed to read metadata: {}", path.as_ref().display()))
+}
+
+/// Copies a symbolic link as is. On Windows, where creating links requires privileges, the target is copied instead.
+pub async fn copy_symlink(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let target = fs::read_link(&src)
+            .await
+            .with_context(|| format!("Failed to read link: {}", src.as_ref().display()))?;
+        fs::symlink(&target, &dst)
+            .await
+            .with_context(|| format!("Failed to create link: {}", dst.as_ref().display()))
+    }
+    #[cfg(not(unix))]
+    {
+        copy(src, dst).await.map(|_| ())
+    }
+}
+
+/// Recursively copies a directory. The destination must not exist.
+pub async fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+    copy_dir_filtered(src, dst, &|_| true).await
+}
+
+/// Recursively copies a directory, skipping the entries (and their descendants) for which `filter` returns `false`.
+/// The destination must not exist.
+pub async fn copy_dir_filtered(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    filter: &(dyn Fn(&fs::DirEntry) -> bool + Sync),
+) -> Result<()> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    create_dir(dst).await?;
+    let mut dir = read_dir(src).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        if !filter(&entry) {
+            continue;
+        }
+        let file_type = entry
+            .file_type()
+            .await
+            .with_context(|| format!("Failed to read file type: {}", entry.path().display()))?;
+        if file_type.is_dir() {
+            Box::pin(copy_dir_filtered(entry.path(), dst.join(entry.file_name()), filter)).await?;
+        } else if file_type.is_symlink() {
+            copy_symlink(entry.path(), dst.join(entry.file_name())).await?;
+        } else {
+            copy(entry.path(), dst.join(entry.file_name())).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the total size of the files under a directory, not following symbolic links, or 0 if it does not exist.
+/// Entries removed while the directory is walked, such as the temporary files of a running operation, are skipped.
+pub async fn dir_size(path: impl AsRef<Path>) -> Result<u64> {
+    let path = path.as_ref();
+    let mut dir = match fs::read_dir(path).await {
+        Ok(dir) => dir,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error).with_context(|| format!("Failed to read directory: {}", path.display())),
+    };
+    let mut size = 0;
+    while let Some(entry) = dir
+        .next_entry()
+        .await
+        .with_context(|| format!("Failed to read directory: {}", path.display()))?
+    {
+        let metadata = match fs::symlink_metadata(entry.path()).await {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read metadata: {}", entry.path().display()));
+            }
+        };
+        if metadata.is_dir() {
+            size += Box::pin(dir_size(entry.path())).await?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
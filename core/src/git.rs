@@ -0,0 +1,127 @@
+//! Git sources.
+//!
+//! Applications can be imported from a local git repository or a git bundle file. The `git` executable is used to
+//! read them, and only the tree of the selected commit is imported: the history and the `.git` folder are not.
+
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::Cancelled;
+use crate::bundle::{ProgressReader, unpack_tar};
+use crate::report::PhaseProgress;
+
+const BUNDLE_SIGNATURES: [&[u8]; 2] = [b"# v2 git bundle\n", b"# v3 git bundle\n"];
+
+/// Git repository an application was imported from, recorded so that it can be updated later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitSource {
+    /// Absolute path of the repository or the bundle file.
+    pub repository: String,
+    /// Branch, tag or commit to import, `HEAD` of the repository if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#ref: Option<String>,
+    /// Commit which was imported.
+    pub commit: String,
+}
+
+/// Whether `path` is a git repository (including bare ones), or a git bundle file.
+pub fn is_git_source(path: &Path) -> bool {
+    if path.is_dir() {
+        return path.join(".git").exists() || (path.join("HEAD").is_file() && path.join("objects").is_dir());
+    }
+    let mut header = [0; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| BUNDLE_SIGNATURES.contains(&header.as_slice()))
+}
+
+/// Runs git, which is killed if `cancel` is cancelled.
+async fn git(args: &[&str], cancel: &CancellationToken) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::select! {
+        output = output => output,
+        () = cancel.cancelled() => return Err(Cancelled.into()),
+    }
+    .map_err(|error| match error.kind() {
+        ErrorKind::NotFound => anyhow!("git is not installed."),
+        _ => error.into(),
+    })?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Checks out the tree of `reference` (or `HEAD`) of a repository or a bundle file into `dest`, without the `.git`
+/// folder, reporting each file checked out. `work` is a temporary directory for the clone, removed by the caller.
+/// Returns the commit.
+pub async fn checkout(
+    src: &Path,
+    reference: Option<&str>,
+    work: &Path,
+    dest: &Path,
+    progress: &PhaseProgress,
+) -> Result<String> {
+    let cancel = progress.cancellation_token();
+    let work = work
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid path: {}", work.display()))?;
+    let src = src.to_str().ok_or_else(|| anyhow!("Invalid path: {}", src.display()))?;
+    git(&["clone", "--quiet", "--bare", "--", src, work], cancel).await?;
+    let commit = git(
+        &[
+            "--git-dir",
+            work,
+            "rev-parse",
+            "--verify",
+            "--end-of-options",
+            &format!("{}^{{commit}}", reference.unwrap_or("HEAD")),
+        ],
+        cancel,
+    )
+    .await
+    .with_context(|| format!("Revision {} not found in {}", reference.unwrap_or("HEAD"), src))?;
+
+    // `git archive` streams the tree of the commit, with the modes and symlinks of its files
+    let (work, dest, archive_commit, progress) =
+        (work.to_string(), dest.to_path_buf(), commit.clone(), progress.clone());
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut child = std::process::Command::new("git")
+            .args(["--git-dir", &work, "archive", "--format=tar", &archive_commit])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to read git archive"))?;
+        let result = unpack_tar(ProgressReader::new(stdout, &progress), &dest, &progress);
+        // the archive is not read to the end if extracting failed or was cancelled
+        if result.is_err() {
+            let _ = child.kill();
+        }
+        let output = child.wait_with_output()?;
+        if result.is_ok() && !output.status.success() {
+            bail!("git archive failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        result
+    })
+    .await??;
+    Ok(commit)
+}
@@ -0,0 +1,160 @@
+//! Detailed report of an instance.
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::daemon::ProcessRecord;
+use crate::yarn::mask_credentials;
+use crate::{App, Backup, Cirno, Package, fs, store_references};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ManifestEntry {
+    /// Head instance, whose entry is the application.
+    App(App),
+    Backup(Backup),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timeline {
+    /// ID of the application, ie. of its head instance.
+    pub app: Uuid,
+    /// Position of the instance in the timeline, counted from 1 for the oldest backup.
+    pub position: usize,
+    /// Number of instances in the timeline, ie. the position of the head instance.
+    pub len: usize,
+    /// Previous (older) instance, if any.
+    pub previous: Option<Uuid>,
+    /// Next (newer) instance, if any.
+    pub next: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaSummary {
+    pub package: Package,
+    pub lockfile_version: u32,
+    pub cache_key: String,
+    /// Number of entries of the lockfile, including workspaces.
+    pub packages: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceUsage {
+    /// Bytes of the instance in `apps/<id>`, only set for a head instance since backups are archived.
+    pub files: Option<u64>,
+    /// Bytes of the backup archive of the application.
+    pub backups: u64,
+    /// Number of files of the shared stores (cache files and yarn release) referenced by the instance.
+    pub store_files: usize,
+    /// Bytes of the files of the shared stores referenced by the instance.
+    pub store_bytes: u64,
+    /// Number of these files which are also referenced by other applications.
+    pub shared_files: usize,
+    /// Bytes of these files which are also referenced by other applications.
+    pub shared_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceInfo {
+    pub id: Uuid,
+    /// Name of the application of the instance.
+    pub name: String,
+    pub entry: ManifestEntry,
+    pub timeline: Timeline,
+    pub meta: MetaSummary,
+    /// Effective yarn configuration of a head instance, see [`Cirno::resolve_yarn_rc`], or the `.yarnrc.yml` recorded
+    /// with a backup. The values of secrets and credentials are masked.
+    pub yarn_rc: Value,
+    pub usage: InstanceUsage,
+    /// Last start and exit of the instance recorded by the daemon, if any.
+    pub process: Option<ProcessRecord>,
+    /// Whether the daemon is running the instance.
+    pub running: bool,
+}
+
+impl Cirno {
+    /// Collects a detailed report of an instance.
+    pub async fn info(&self, id: &Uuid) -> Result<InstanceInfo> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        let timeline = app
+            .backups
+            .iter()
+            .map(|backup| backup.id)
+            .chain([app.id])
+            .collect::<Vec<_>>();
+        let index = timeline.iter().position(|instance| instance == id).unwrap_or_default();
+        let entry = match app.backups.get(index) {
+            Some(backup) => ManifestEntry::Backup(backup.clone()),
+            None => ManifestEntry::App(app.clone()),
+        };
+
+        let meta = self.load_meta(id).await?;
+        let yarn_rc = match &entry {
+            ManifestEntry::App(_) => {
+                let mut resolved = self.resolve_yarn_rc(id).await?;
+                resolved.mask_secrets();
+                resolved.to_value()
+            }
+            ManifestEntry::Backup(_) => {
+                let mut yarn_rc = serde_json::to_value(&meta.yarn_rc)?;
+                mask_credentials(&mut yarn_rc);
+                yarn_rc
+            }
+        };
+
+        let archive = self.cwd.join("baka").join(format!("{}.tar.br", app.id));
+        let mut usage = InstanceUsage {
+            files: match &entry {
+                ManifestEntry::App(_) => Some(fs::dir_size(self.cwd.join("apps").join(id.to_string())).await?),
+                ManifestEntry::Backup(_) => None,
+            },
+            backups: match tokio::fs::try_exists(&archive).await? {
+                true => fs::metadata(&archive).await?.len(),
+                false => 0,
+            },
+            ..Default::default()
+        };
+        let store = self.store_usage().await?;
+        for file in store_references(&meta, &self.load_cache().await?)? {
+            let Some(store_file) = store.get(&file) else {
+                continue;
+            };
+            usage.store_files += 1;
+            usage.store_bytes += store_file.size;
+            if store_file.apps().iter().any(|other| other != &app.id) {
+                usage.shared_files += 1;
+                usage.shared_bytes += store_file.size;
+            }
+        }
+
+        let daemon = self.daemon_status().await?;
+        Ok(InstanceInfo {
+            id: *id,
+            name: app.name.clone(),
+            timeline: Timeline {
+                app: app.id,
+                position: index + 1,
+                len: timeline.len(),
+                previous: index.checked_sub(1).map(|index| timeline[index]),
+                next: timeline.get(index + 1).copied(),
+            },
+            meta: MetaSummary {
+                lockfile_version: meta.yarn_lock.metadata.version,
+                cache_key: meta.yarn_lock.metadata.cache_key.clone(),
+                packages: meta.yarn_lock.packages.len(),
+                package: meta.package,
+            },
+            entry,
+            yarn_rc,
+            usage,
+            running: daemon.is_running(&id.to_string()),
+            process: daemon.processes.get(&id.to_string()).cloned(),
+        })
+    }
+}
@@ -0,0 +1,233 @@
+//! Creation, cloning and removal of instances.
+
+use std::sync::LazyLock;
+
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::env::{load_secrets, save_secrets};
+use crate::yarn::{PackageManager, ReleaseHash};
+use crate::{App, Cirno, Meta, fs};
+
+/// Valid package names, as enforced by npm.
+static NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:(?:@(?:[a-z0-9-*~][a-z0-9-*._~]*)?/[a-z0-9-._~])|[a-z0-9-~])[a-z0-9-._~]*$").unwrap()
+});
+
+#[derive(Debug, Default)]
+pub struct CreateOptions {
+    /// ID of the new instance, generated if not set.
+    pub id: Option<Uuid>,
+    /// `packageManager` field of the new application, eg. `yarn@4.5.0`.
+    pub package_manager: String,
+    /// Arguments of the yarn command run in the new application, which defaults to `yarn install`.
+    pub yarn_args: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct CloneOptions {
+    /// ID of the new instance, generated if not set.
+    pub id: Option<Uuid>,
+    /// Name of the new application, which defaults to the name of the cloned one.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct RemoveOptions {
+    /// Also remove the earlier backups, ie. every backup of the application if the removed instance is its head.
+    pub recursive: bool,
+    /// Remove backups even if they are tagged, see [`Cirno::tag`].
+    pub force: bool,
+}
+
+#[derive(Debug)]
+pub struct RemoveReport {
+    /// Instances removed from the environment.
+    pub removed: Vec<Uuid>,
+    /// Backup which replaced the removed head instance, if any.
+    pub restored: Option<Uuid>,
+}
+
+impl Cirno {
+    /// Creates an empty application with the given name and package manager, and returns its ID.
+    ///
+    /// The yarn release is downloaded if it is not installed yet, see [`download_yarn`](Self::download_yarn).
+    pub async fn create(&mut self, name: &str, options: &CreateOptions) -> Result<Uuid> {
+        if !NAME_REGEX.is_match(name) {
+            bail!("Invalid application name: {}.", name);
+        }
+        let id = options.id.unwrap_or_else(Uuid::new_v4);
+        if self.get(&id).is_some() {
+            bail!("Instance {} already exists.", id);
+        }
+        let package_manager = PackageManager::yarn(&options.package_manager)?;
+        self.download_yarn(&package_manager, None).await?;
+        let release_hash = package_manager.verify(&self.cwd.join("home/.yarn/releases")).await?;
+
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let result = async {
+            fs::create_dir_all(temp).await?;
+            let package = serde_json::json!({
+                "name": name,
+                "version": "0.0.0",
+                "private": true,
+                "packageManager": options.package_manager,
+            });
+            fs::write(
+                temp.join("package.json"),
+                serde_json::to_string_pretty(&package)? + "\n",
+            )
+            .await?;
+            // an empty lockfile marks the folder as a project root for yarn
+            fs::write(temp.join("yarn.lock"), "").await?;
+            if options.yarn_args.is_empty() {
+                self.yarn_install(temp).await?;
+            } else {
+                let status = self.yarn(temp, &options.yarn_args).await?;
+                if !status.success() {
+                    bail!("Failed to install dependencies. Exit code: {}", status);
+                }
+            }
+            Meta::load(temp)
+                .await
+                .map_err(|error| anyhow!("Yarn did not write a valid lockfile: {}", error))?;
+            self.check_cancelled()?;
+            fs::rename(temp, self.cwd.join("apps").join(id.to_string())).await?;
+            self.manifest.apps.push(App {
+                id,
+                name: name.to_string(),
+                created: crate::get_timestamp(),
+                backups: vec![],
+                release_hash: release_hash.as_ref().map(ReleaseHash::to_string),
+                env: Default::default(),
+                source: None,
+                tags: vec![],
+                aliases: vec![],
+            });
+            self.state.insert(id.to_string(), Default::default());
+            self.save().await
+        }
+        .await;
+        temp_dir.remove().await?;
+        result?;
+        Ok(id)
+    }
+
+    /// Clones an instance, either a head instance or a backup, into a new application, and returns its ID.
+    ///
+    /// The dependencies of the clone are installed, see [`install`](Self::install).
+    pub async fn clone_instance(&mut self, id: &Uuid, options: &CloneOptions) -> Result<Uuid> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        let new_id = options.id.unwrap_or_else(Uuid::new_v4);
+        if self.get(&new_id).is_some() {
+            bail!("Instance {} already exists.", new_id);
+        }
+        let name = options.name.clone().unwrap_or_else(|| app.name.clone());
+        // the release hash only applies to the package manager of the head instance
+        let release_hash = if &app.id == id { app.release_hash.clone() } else { None };
+
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        let result = async {
+            let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+            self.clone(app, id, temp).await?;
+            self.install(temp).await?;
+            self.check_cancelled()?;
+            fs::rename(temp, self.cwd.join("apps").join(new_id.to_string())).await?;
+            self.manifest.apps.push(App {
+                id: new_id,
+                name,
+                created: crate::get_timestamp(),
+                backups: vec![],
+                release_hash,
+                env: Default::default(),
+                source: None,
+                tags: vec![],
+                aliases: vec![],
+            });
+            self.state.insert(new_id.to_string(), Default::default());
+            self.save().await
+        }
+        .await;
+        temp_dir.remove().await?;
+        result?;
+        Ok(new_id)
+    }
+
+    /// Removes an instance, then collects the files of the environment which are no longer used, see [`gc`].
+    ///
+    /// Removing a head instance restores the application to its last backup, or removes the application if it has no
+    /// backups. With `recursive`, the earlier backups are removed as well, ie. every backup of the application if `id`
+    /// is its head instance. Tagged backups are only removed with `force`.
+    ///
+    /// [`gc`]: Self::gc
+    pub async fn remove(&mut self, id: &Uuid, options: &RemoveOptions) -> Result<RemoveReport> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        let app_id = app.id;
+        let backups = app.backups.iter().map(|backup| backup.id).collect::<Vec<_>>();
+        let report = if &app_id != id {
+            let index = backups
+                .iter()
+                .position(|backup| backup == id)
+                .ok_or_else(|| anyhow!("Backup {} not found.", id))?;
+            let removed = match options.recursive {
+                true => backups[..=index].to_vec(),
+                false => vec![*id],
+            };
+            if !options.force {
+                self.check_untagged(&app_id, &removed)?;
+            }
+            self.remove_backups(&app_id, &removed).await?;
+            RemoveReport {
+                removed,
+                restored: None,
+            }
+        } else if let (false, Some(&last)) = (options.recursive, backups.last()) {
+            self.restore(&last, options.force).await?;
+            RemoveReport {
+                removed: vec![app_id],
+                restored: Some(last),
+            }
+        } else {
+            if !options.force {
+                self.check_untagged(&app_id, &backups)?;
+            }
+            self.remove_app(&app_id).await?;
+            RemoveReport {
+                removed: [app_id].into_iter().chain(backups).collect(),
+                restored: None,
+            }
+        };
+        self.gc().await?;
+        Ok(report)
+    }
+
+    /// Removes an application with all of its backups and secrets.
+    async fn remove_app(&mut self, id: &Uuid) -> Result<()> {
+        let temp_dir = self.temp_dir().await?;
+        let result = async {
+            // the application is moved out of `apps/` first, so that it is never left partially removed
+            let head = self.cwd.join("apps").join(id.to_string());
+            if tokio::fs::try_exists(&head).await? {
+                fs::rename(&head, temp_dir.path()).await?;
+            }
+            let archive = self.cwd.join("baka").join(format!("{}.tar.br", id));
+            if tokio::fs::try_exists(&archive).await? {
+                fs::remove_file(&archive).await?;
+            }
+            self.manifest.apps.retain(|app| &app.id != id);
+            self.state.remove(&id.to_string());
+            self.save().await?;
+            let mut secrets = load_secrets(&self.cwd).await?;
+            if secrets.remove(id).is_some() {
+                save_secrets(&self.cwd, &secrets).await?;
+            }
+            Ok(())
+        }
+        .await;
+        temp_dir.remove().await?;
+        result
+    }
+}
@@ -1,64 +1,129 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::ffi::OsStr;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
-use std::sync::LazyLock;
+use std::process::Stdio;
+use std::sync::{Arc, LazyLock, Mutex};
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use brotli::{BrotliCompress, BrotliDecompress};
-use futures::future::try_join_all;
+use flate2::read::GzDecoder;
+use futures::future::{join_all, try_join_all};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio::process::Command;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
 use uuid::Uuid;
 
-use crate::yarn::{NodeLinker, YarnLock, YarnRc};
+use crate::cleanup::TempDir;
+use crate::env::{EnvPolicy, EnvSource};
+use crate::git::GitSource;
+use crate::license::LicensePolicy;
+use crate::report::{Event, OutputStream, Phase, PhaseProgress, Reporter, SilentReporter};
+use crate::sign::TrustConfig;
+use crate::yarn::{NodeLinker, PackageManager, ReleaseHash, ResolvedYarnRc, YarnLock, YarnRc, YarnRcSource};
 
+pub mod baka;
+pub mod bundle;
+pub mod cache;
+mod cleanup;
+pub mod daemon;
+pub mod delta;
+pub mod env;
 pub mod fs;
+pub mod git;
+pub mod info;
+pub mod instance;
+pub mod license;
+pub mod report;
+pub mod resolve;
+pub mod runtime;
+pub mod sbom;
+pub mod sign;
+pub mod tag;
+pub mod usage;
 pub mod yarn;
 
+pub use tokio_util::sync::CancellationToken;
+
 const VERSION: &str = "1.0";
 const ENTRY_FILE: &str = "cirno.yml";
 const STATE_FILE: &str = "cirno-baka.br";
 
 static YARN_CACHE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+)-([0-9a-f]+)\.zip$").unwrap());
-static PACKAGE_MANAGER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([\w-]+)@(\d+\.\d+\.\d+)$").unwrap());
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Manifest {
     pub version: String,
-    // pub config: Config,
+    #[serde(default)]
+    pub config: Config,
     pub apps: Vec<App>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub licenses: Option<LicensePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<EnvPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trust: Option<TrustConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct App {
     pub id: Uuid,
     pub name: String,
     pub created: String, // TODO: time
     pub backups: Vec<Backup>,
+    /// Hash of the Yarn release declared by a Corepack-style `packageManager` field (eg. `sha512.<hex>`), recorded
+    /// once the release in `home/.yarn/releases` has been verified against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_hash: Option<String>,
+    /// Environment variables declared for the application. Secrets are stored separately, see [`env::SECRETS_FILE`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Git repository the application was imported from, see [`Cirno::update`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<GitSource>,
+    /// Labels of the head instance, which may be shared by several instances, see [`Cirno::tag`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Names of the head instance, unique in the environment, see [`Cirno::tag`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Backup {
     pub id: Uuid,
     pub r#type: Option<String>,
     pub message: Option<String>,
     pub created: String, // TODO: time
+    /// Labels of the backup, see [`App::tags`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Names of the backup, see [`App::aliases`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Package {
     pub name: String,
+    /// Empty if the field is missing, which is reported by [`PackageManager::yarn`].
+    #[serde(default)]
     pub package_manager: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Meta {
     pub package: Package,
@@ -69,8 +134,8 @@ pub struct Meta {
 impl Meta {
     pub async fn load(cwd: &Path) -> Result<Self> {
         let package = serde_json::from_str(&fs::read_to_string(&cwd.join("package.json")).await?)?;
-        let yarn_rc = serde_yaml_ng::from_str(&fs::read_to_string(&cwd.join(".yarnrc.yml")).await?)?;
-        let yarn_lock = serde_json::from_str(&fs::read_to_string(&cwd.join("yarn.lock")).await?)?;
+        let yarn_rc = read_yarn_rc(cwd).await?.unwrap_or_default();
+        let yarn_lock = serde_yaml_ng::from_str(&fs::read_to_string(&cwd.join("yarn.lock")).await?)?;
         Ok(Meta {
             package,
             yarn_rc,
@@ -102,6 +167,34 @@ impl<T: Into<anyhow::Error>> From<T> for OpenError {
     }
 }
 
+/// Error of an operation stopped by the cancellation token of the environment, see [`Cirno::set_cancellation_token`].
+#[derive(Debug, thiserror::Error)]
+#[error("Operation cancelled.")]
+pub struct Cancelled;
+
+/// Files of the shared cache, by cache key and locator.
+pub type Cache = HashMap<String, HashMap<String, String>>;
+
+/// Returns the files of the shared stores referenced by an instance, relative to `home/.yarn`: its yarn release and the
+/// cache files of its lockfile. Files which are referenced by no instance are collected by [`Cirno::gc`].
+pub(crate) fn store_references(meta: &Meta, cache: &Cache) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    if let Ok(package_manager) = PackageManager::yarn(&meta.package.package_manager) {
+        files.push(format!("releases/{}", package_manager.release_name()));
+    }
+    for (_, name) in meta.yarn_lock.find_cache_files(cache)? {
+        if let Some(name) = name {
+            files.push(format!("cache/{}", name));
+        }
+    }
+    Ok(files)
+}
+
+/// Current time in the format of `Date.prototype.toISOString`, used for the creation time of instances.
+fn get_timestamp() -> String {
+    jiff::Timestamp::now().strftime("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
 fn normalize_path(path: &Path) -> Result<PathBuf, std::io::Error> {
     if path.is_absolute() {
         Ok(path.to_path_buf())
@@ -110,6 +203,76 @@ fn normalize_path(path: &Path) -> Result<PathBuf, std::io::Error> {
     }
 }
 
+/// Number of lines of yarn output attached to the error of a failed process.
+const OUTPUT_TAIL_LINES: usize = 20;
+
+/// Reports the lines of a yarn process, and keeps the last [`OUTPUT_TAIL_LINES`] of them in `tail`, so that failures
+/// can be explained even when the output is not reported.
+async fn forward_lines(
+    reporter: &dyn Reporter,
+    stream: impl AsyncRead + Unpin,
+    kind: OutputStream,
+    tail: &Mutex<VecDeque<String>>,
+) -> Result<()> {
+    let mut lines = BufReader::new(stream).lines();
+    while let Some(line) = lines.next_line().await? {
+        {
+            let mut tail = tail.lock().unwrap();
+            if tail.len() == OUTPUT_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line.clone());
+        }
+        reporter.report(&Event::YarnOutput { stream: kind, line });
+    }
+    Ok(())
+}
+
+/// Reads the `.yarnrc.yml` of an application, which is optional: applications created by yarn itself have none.
+pub(crate) async fn read_yarn_rc(cwd: &Path) -> Result<Option<YarnRc>> {
+    let path = cwd.join(".yarnrc.yml");
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => Ok(Some(serde_yaml_ng::from_str(&content)?)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("Failed to read file: {}", path.display())),
+    }
+}
+
+/// Fetches the URL of the first argument into the file of the second one.
+const DOWNLOAD_SCRIPT: &str = r#"
+const [url, dest] = process.argv.slice(1)
+fetch(url)
+  .then(async (response) => {
+    if (!response.ok) throw new Error(`${response.status} ${response.statusText}`)
+    require('fs').writeFileSync(dest, Buffer.from(await response.arrayBuffer()))
+  })
+  .catch((error) => {
+    console.error(error.message)
+    process.exit(1)
+  })
+"#;
+
+/// Extracts `bin/yarn.js` of a `@yarnpkg/cli-dist` tarball to `dest`, and returns its size.
+fn extract_release(tarball: &Path, dest: &Path) -> Result<u64> {
+    let file = std::fs::File::open(tarball).with_context(|| format!("Failed to read file: {}", tarball.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(std::io::BufReader::new(file)));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // npm tarballs have a single top-level folder, usually `package/`
+        if entry
+            .path()?
+            .components()
+            .skip(1)
+            .eq(Path::new("bin/yarn.js").components())
+        {
+            let mut output =
+                std::fs::File::create(dest).with_context(|| format!("Failed to create file: {}", dest.display()))?;
+            return Ok(std::io::copy(&mut entry, &mut output)?);
+        }
+    }
+    bail!("Invalid yarn tarball: bin/yarn.js not found.")
+}
+
 async fn get_file_count(cwd: &Path) -> Result<usize, std::io::Error> {
     let mut len = 0;
     let mut dir = tokio::fs::read_dir(cwd).await?;
@@ -123,10 +286,12 @@ pub struct Cirno {
     pub cwd: PathBuf,
     pub manifest: Manifest,
     state: HashMap<String, HashMap<String, Meta>>,
+    reporter: Arc<dyn Reporter>,
+    cancel: CancellationToken,
 }
 
 impl Cirno {
-    pub async fn init(cwd: &Path, force: bool) -> Result<PathBuf, InitError> {
+    pub async fn init(cwd: &Path, force: bool, node_linker: NodeLinker) -> Result<PathBuf, InitError> {
         let cwd = normalize_path(cwd)?;
         match get_file_count(&cwd).await {
             Ok(0) => {}
@@ -158,7 +323,7 @@ impl Cirno {
         let yarn_rc = YarnRc {
             enable_tips: Some(false),
             enable_telemetry: Some(false),
-            node_linker: Some(NodeLinker::Pnp),
+            node_linker: Some(node_linker),
             pnp_enable_esm_loader: Some(true),
             ..YarnRc::default()
         };
@@ -167,9 +332,12 @@ impl Cirno {
             cwd,
             manifest: Manifest {
                 version: VERSION.to_string(),
+                config: Default::default(),
                 apps: vec![],
             },
             state: Default::default(),
+            reporter: Arc::new(SilentReporter),
+            cancel: CancellationToken::new(),
         };
         cirno.save().await?;
         Ok(cirno.cwd)
@@ -193,7 +361,60 @@ impl Cirno {
         let mut output = vec![];
         BrotliDecompress(&mut content.as_slice(), &mut output)?;
         let state: HashMap<String, HashMap<String, Meta>> = serde_json::from_str(std::str::from_utf8(&output)?)?;
-        Ok(Self { cwd, manifest, state })
+        cleanup::sweep(&cwd.join("tmp")).await?;
+        Ok(Self {
+            cwd,
+            manifest,
+            state,
+            reporter: Arc::new(SilentReporter),
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// Sets the reporter of the progress of long-running operations, see [`report`].
+    pub fn set_reporter(&mut self, reporter: Arc<dyn Reporter>) {
+        self.reporter = reporter;
+    }
+
+    /// Sets the token which cancels long-running operations. Cancelled operations fail with [`Cancelled`] once they
+    /// have removed their temporary files and reverted their changes to the environment.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel = token;
+    }
+
+    /// Starts a phase of an operation, or fails if the operation is cancelled.
+    pub(crate) fn start_phase(
+        &self,
+        phase: Phase,
+        total_files: Option<u64>,
+        total_bytes: Option<u64>,
+    ) -> Result<PhaseProgress, Cancelled> {
+        self.check_cancelled()?;
+        Ok(PhaseProgress::start(
+            self.reporter.clone(),
+            self.cancel.clone(),
+            phase,
+            total_files,
+            total_bytes,
+        ))
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), Cancelled> {
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Creates a temporary directory for an operation, see [`cleanup`].
+    pub(crate) async fn temp_dir(&self) -> Result<TempDir> {
+        TempDir::new(&self.cwd.join("tmp")).await
+    }
+
+    pub(crate) fn warn(&self, message: impl Into<String>) {
+        self.reporter.report(&Event::Warning {
+            message: message.into(),
+        });
     }
 
     pub async fn save(&self) -> Result<()> {
@@ -205,65 +426,296 @@ impl Cirno {
         Ok(())
     }
 
+    /// Returns the application an instance belongs to, whether it is the head instance or one of its backups.
+    pub fn get(&self, id: &Uuid) -> Option<&App> {
+        self.manifest
+            .apps
+            .iter()
+            .find(|app| &app.id == id || app.backups.iter().any(|backup| &backup.id == id))
+    }
+
+    pub async fn load_meta(&self, id: &Uuid) -> Result<Meta> {
+        let app = self.get(id).ok_or_else(|| anyhow!("Instance {} not found.", id))?;
+        if &app.id == id {
+            return Meta::load(&self.cwd.join("apps").join(id.to_string())).await;
+        }
+        self.state
+            .get(&app.id.to_string())
+            .and_then(|metas| metas.get(&id.to_string()))
+            .cloned()
+            .ok_or_else(|| anyhow!("Metadata of instance {} is missing.", id))
+    }
+
+    /// Returns the linker used by an application: its own `nodeLinker` setting, or the one of the global `.yarnrc.yml`.
+    pub async fn node_linker(&self, yarn_rc: &YarnRc) -> Result<NodeLinker> {
+        if let Some(node_linker) = yarn_rc.node_linker {
+            return Ok(node_linker);
+        }
+        let global: YarnRc = serde_yaml_ng::from_str(&fs::read_to_string(self.cwd.join("home/.yarnrc.yml")).await?)?;
+        Ok(global.node_linker.unwrap_or(NodeLinker::Pnp))
+    }
+
+    /// Computes the effective Yarn configuration of an application from, in order of priority: the global
+    /// `.yarnrc.yml`, the application's `.yarnrc.yml`, the `YARN_*` variables of its environment, and the settings
+    /// enforced by Cirno.
+    pub async fn resolve_yarn_rc(&self, id: &Uuid) -> Result<ResolvedYarnRc> {
+        if !self.manifest.apps.iter().any(|app| &app.id == id) {
+            return Err(anyhow!("Application {} not found.", id));
+        }
+        let cwd = self.cwd.join("apps").join(id.to_string());
+        let mut resolved = ResolvedYarnRc::default();
+        let global: YarnRc = serde_yaml_ng::from_str(&fs::read_to_string(self.cwd.join("home/.yarnrc.yml")).await?)?;
+        resolved.layer(&global, YarnRcSource::Global)?;
+        let local = read_yarn_rc(&cwd).await?.unwrap_or_default();
+        resolved.layer(&local, YarnRcSource::App)?;
+        for var in self.environment(&cwd).await?.vars {
+            let source = match var.source {
+                EnvSource::Cirno => YarnRcSource::Cirno,
+                _ => YarnRcSource::Env(var.name.clone()),
+            };
+            resolved.layer_env(&var.name, &var.value, source, var.source == EnvSource::Secret);
+        }
+        // the global cache, which is enabled by default since Yarn 4, ignores `cacheFolder` and stores packages in the
+        // global folder enforced by Cirno
+        let package: Package = serde_json::from_str(&fs::read_to_string(cwd.join("package.json")).await?)?;
+        let global_cache = match resolved.settings.get("enableGlobalCache") {
+            Some(setting) => setting.value == serde_json::Value::Bool(true),
+            None => PackageManager::yarn(&package.package_manager).is_ok_and(|package_manager| {
+                let major = package_manager.version.split('.').next().unwrap_or_default();
+                major.parse::<u32>().is_ok_and(|major| major >= 4)
+            }),
+        };
+        if global_cache {
+            let cache_folder = self.cwd.join("home/.yarn/cache").to_string_lossy().to_string();
+            resolved.set("cacheFolder", cache_folder.into(), YarnRcSource::Cirno, false);
+        }
+        Ok(resolved)
+    }
+
+    /// Copies an instance to `dest`.
+    ///
+    /// The `node_modules` folders of applications using the `node-modules` or `pnpm` linker are not copied. Run
+    /// [`install`](Self::install) on the copy to rebuild them.
     pub async fn clone(&self, app: &App, id: &Uuid, dest: &Path) -> Result<()> {
+        let progress = self.start_phase(Phase::Clone, None, None)?;
         if &app.id == id {
-            fs::copy(self.cwd.join("apps").join(id.to_string()), dest).await?;
+            let src = self.cwd.join("apps").join(id.to_string());
+            let yarn_rc = read_yarn_rc(&src).await?.unwrap_or_default();
+            if self.node_linker(&yarn_rc).await?.uses_node_modules() {
+                fs::copy_dir_filtered(src, dest, &|entry| entry.file_name() != "node_modules").await?;
+            } else {
+                fs::copy_dir_all(src, dest).await?;
+            }
         } else {
-            // const tar = new Tar(join(this.cwd, 'baka', id + '.tar.br'))
-            // tar.load()
-            // tar.extract(dest, 1)
-            // await tar.finalize()
+            let archive = self.cwd.join("baka").join(format!("{}.tar.br", app.id));
+            let (id, dest, progress) = (*id, dest.to_path_buf(), progress.clone());
+            tokio::task::spawn_blocking(move || baka::extract(&archive, &id, &dest, &progress)).await??;
         }
+        progress.finish();
         Ok(())
     }
 
-    pub async fn yarn<I, S>(&self, cwd: &Path, args: I) -> Result<ExitStatus>
+    /// Prepares a yarn command running in `cwd` with the environment of the application.
+    async fn yarn_command<I, S>(&self, cwd: &Path, args: I) -> Result<Command>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
         let pkg_meta: Package = serde_json::from_str(&fs::read_to_string(&cwd.join("package.json")).await?)?;
-        let Some(captures) = PACKAGE_MANAGER_REGEX.captures(&pkg_meta.package_manager) else {
-            return Err(anyhow!("Invalid package manager: {}", pkg_meta.package_manager));
+        let package_manager = PackageManager::yarn(&pkg_meta.package_manager)?;
+        let yarn_path = package_manager.locate(&self.cwd.join("home/.yarn/releases")).await?;
+        let env = self.environment(cwd).await?;
+        let mut command = match &env.runtime {
+            Some(runtime) => Command::new(runtime.node()),
+            None => Command::new("node"),
         };
-        if captures[1] != *"yarn" {
-            return Err(anyhow!("Unsupported package manager: {}", &captures[1]));
-        }
-        let yarn_path = self.cwd.join(format!("home/.yarn/releases/yarn-{}.cjs", &captures[2]));
-        let mut command = Command::new("node");
         command
             .arg(&yarn_path)
             .args(args)
             .current_dir(cwd)
-            .envs(std::env::vars_os())
-            .env("HOME", self.cwd.join("home"))
-            .env("TEMP", self.cwd.join("tmp"))
-            .env("TMP", self.cwd.join("tmp"))
-            .env("TMPDIR", self.cwd.join("tmp"))
-            .env("YARN_YARN_PATH", &yarn_path)
-            .env("YARN_GLOBAL_FOLDER", self.cwd.join("home/.yarn"));
-        for key in ["HOME", "TEMP", "TMP", "TMPDIR"] {
-            if let Some(value) = std::env::var_os(key) {
-                command.env(format!("CIRNO_HOST_{}", key), value);
+            .env_clear()
+            .envs(env.iter())
+            .kill_on_drop(true);
+        Ok(command)
+    }
+
+    /// Waits for a child process once `output` is read, and kills it if the operation is cancelled.
+    async fn wait_child(&self, child: &mut Child, output: impl Future<Output = Result<()>>) -> Result<ExitStatus> {
+        let result = tokio::select! {
+            result = async {
+                output.await?;
+                anyhow::Ok(child.wait().await?)
+            } => Some(result),
+            () = self.cancel.cancelled() => None,
+        };
+        match result {
+            Some(result) => result,
+            None => {
+                child.kill().await?;
+                Err(Cancelled.into())
             }
         }
-        #[cfg(target_os = "windows")]
-        {
-            command
-                .env("APPDATA", self.cwd.join("home/AppData/Roaming"))
-                .env("LOCALAPPDATA", self.cwd.join("home/AppData/Local"))
-                .env("USERPROFILE", self.cwd.join("home"));
-            for key in ["APPDATA", "LOCALAPPDATA", "USERPROFILE"] {
-                if let Some(value) = std::env::var_os(key) {
-                    command.env(format!("CIRNO_HOST_{}", key), value);
-                }
+    }
+
+    /// Runs yarn in `cwd`. The process is killed if the operation is cancelled.
+    pub async fn yarn<I, S>(&self, cwd: &Path, args: I) -> Result<ExitStatus>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut child = self.yarn_command(cwd, args).await?.spawn()?;
+        self.wait_child(&mut child, async { Ok(()) }).await
+    }
+
+    /// Downloads a yarn release from `@yarnpkg/cli-dist` on the npm registry into `home/.yarn/releases`, unless it is
+    /// already installed. The registry defaults to the `npmRegistryServer` of the global `.yarnrc.yml`.
+    ///
+    /// The tarball is fetched by `node`, which Cirno already requires to run yarn.
+    pub async fn download_yarn(&self, package_manager: &PackageManager, registry: Option<&str>) -> Result<()> {
+        let dest = self
+            .cwd
+            .join("home/.yarn/releases")
+            .join(package_manager.release_name());
+        if tokio::fs::try_exists(&dest).await? {
+            return Ok(());
+        }
+        let registry = match registry {
+            Some(registry) => registry.to_string(),
+            None => {
+                let global: YarnRc =
+                    serde_yaml_ng::from_str(&fs::read_to_string(self.cwd.join("home/.yarnrc.yml")).await?)?;
+                global
+                    .npm_registry_server
+                    .unwrap_or_else(|| "https://registry.yarnpkg.com".to_string())
+            }
+        };
+        let url = format!(
+            "{}/@yarnpkg/cli-dist/-/cli-dist-{}.tgz",
+            registry.trim_end_matches('/'),
+            package_manager.version
+        );
+        let progress = self.start_phase(Phase::Download, Some(1), None)?;
+        let temp_dir = self.temp_dir().await?;
+        let (tarball, release) = (
+            temp_dir.path().with_extension("tgz"),
+            temp_dir.path().with_extension("cjs"),
+        );
+        let result = async {
+            let mut child = Command::new("node")
+                .arg("-e")
+                .arg(DOWNLOAD_SCRIPT)
+                .arg(&url)
+                .arg(&tarball)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+            let mut stderr = child
+                .stderr
+                .take()
+                .ok_or_else(|| anyhow!("Failed to read the output of node"))?;
+            let mut message = String::new();
+            let output = async {
+                stderr.read_to_string(&mut message).await?;
+                Ok(())
+            };
+            let status = self.wait_child(&mut child, output).await?;
+            if !status.success() {
+                bail!(
+                    "Failed to download yarn {} from {}: {}",
+                    package_manager.version,
+                    url,
+                    message.trim()
+                );
             }
+            let (src, extracted) = (tarball.clone(), release.clone());
+            let size = tokio::task::spawn_blocking(move || extract_release(&src, &extracted)).await??;
+            progress.advance(1, size)?;
+            fs::rename(&release, &dest).await?;
+            Ok(())
         }
-        Ok(command.status().await?)
+        .await;
+        temp_dir.remove().await?;
+        result?;
+        progress.finish();
+        Ok(())
+    }
+
+    /// Verifies the Yarn release of an application against the hash of its `packageManager` field, and records the
+    /// verified hash in the manifest.
+    pub async fn verify_release(&mut self, id: &Uuid) -> Result<Option<ReleaseHash>> {
+        let meta = Meta::load(&self.cwd.join("apps").join(id.to_string())).await?;
+        let package_manager = PackageManager::yarn(&meta.package.package_manager)?;
+        let progress = self.start_phase(Phase::Verify, Some(1), None)?;
+        let hash = package_manager.verify(&self.cwd.join("home/.yarn/releases")).await?;
+        progress.advance(1, 0)?;
+        progress.finish();
+        let app = self
+            .manifest
+            .apps
+            .iter_mut()
+            .find(|app| &app.id == id)
+            .ok_or_else(|| anyhow!("Application {} not found.", id))?;
+        app.release_hash = hash.as_ref().map(ReleaseHash::to_string);
+        self.save().await?;
+        Ok(hash)
+    }
+
+    /// Rebuilds the `node_modules` folders of the application in `cwd` if it does not use PnP, which is required after
+    /// cloning or restoring it, since they are neither copied nor backed up.
+    pub async fn install(&self, cwd: &Path) -> Result<()> {
+        let meta = Meta::load(cwd).await?;
+        if !self.node_linker(&meta.yarn_rc).await?.uses_node_modules() {
+            return Ok(());
+        }
+        self.yarn_install(cwd).await
+    }
+
+    /// Runs `yarn install` in `cwd`, whatever the linker of the application.
+    ///
+    /// The output of yarn is reported line by line, see [`Event::YarnOutput`], and its last lines are attached to the
+    /// error if the install fails.
+    pub(crate) async fn yarn_install(&self, cwd: &Path) -> Result<()> {
+        let progress = self.start_phase(Phase::Install, None, None)?;
+        let mut child = self
+            .yarn_command(cwd, ["install"])
+            .await?
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to read the output of yarn"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("Failed to read the output of yarn"))?;
+        let tail = Mutex::new(VecDeque::new());
+        let output = async {
+            tokio::try_join!(
+                forward_lines(&*self.reporter, stdout, OutputStream::Stdout, &tail),
+                forward_lines(&*self.reporter, stderr, OutputStream::Stderr, &tail),
+            )
+            .map(|_| ())
+        };
+        let status = self.wait_child(&mut child, output).await?;
+        progress.finish();
+        if !status.success() {
+            let tail = Vec::from(tail.into_inner().unwrap()).join("\n");
+            return Err(anyhow!(
+                "Failed to install dependencies. Exit code: {}\n{}",
+                status,
+                tail
+            ));
+        }
+        Ok(())
     }
 
-    pub async fn load_cache(&self) -> Result<HashMap<String, HashMap<String, String>>> {
-        let mut cache = HashMap::<String, HashMap<String, String>>::new();
+    pub async fn load_cache(&self) -> Result<Cache> {
+        let mut cache = Cache::new();
         let mut dir = fs::read_dir(self.cwd.join("home/.yarn/cache")).await?;
         while let Some(entry) = dir.next_entry().await? {
             let name = entry.file_name();
@@ -279,43 +731,81 @@ impl Cirno {
         Ok(cache)
     }
 
-    pub async fn gc(&self) -> Result<()> {
-        let mut cache = self.load_cache().await?;
-        let mut releases = HashSet::new();
+    /// Returns the files of the shared stores, relative to `home/.yarn`: the yarn releases, and the cache files whose
+    /// name carries a cache key.
+    pub(crate) async fn load_store_files(&self, cache: &Cache) -> Result<HashSet<String>> {
+        let mut files = HashSet::new();
         let mut dir = fs::read_dir(self.cwd.join("home/.yarn/releases")).await?;
         while let Some(entry) = dir.next_entry().await? {
-            let name = entry.file_name().to_string_lossy().to_string();
-            releases.insert(name);
+            files.insert(format!("releases/{}", entry.file_name().to_string_lossy()));
         }
-        for meta_map in self.state.values() {
-            for meta in meta_map.values() {
-                if let Some(captures) = PACKAGE_MANAGER_REGEX.captures(&meta.package.package_manager)
-                    && captures[1] == *"yarn"
-                {
-                    releases.remove(&format!("yarn-{}.cjs", &captures[2]));
-                }
-                for locator in meta.yarn_lock.get_cache_files()? {
-                    if let Some(locators) = cache.get_mut(&meta.yarn_lock.metadata.cache_key) {
-                        locators.remove(&locator);
-                    }
-                }
+        files.extend(
+            cache
+                .values()
+                .flat_map(|locators| locators.values())
+                .map(|name| format!("cache/{}", name)),
+        );
+        Ok(files)
+    }
+
+    /// Loads the metadata of every instance along with the IDs of its application and of the instance itself.
+    ///
+    /// Fails if any instance cannot be loaded, since callers such as [`gc`](Self::gc) must never overlook the files
+    /// referenced by an instance.
+    pub(crate) async fn load_instance_metas(&self) -> Result<Vec<(Uuid, Uuid, Meta)>> {
+        self.instance_metas().await.into_iter().collect()
+    }
+
+    /// Loads the metadata of every instance like [`load_instance_metas`](Self::load_instance_metas), but skips the
+    /// instances which cannot be loaded with a warning, so that one broken application does not prevent reporting on
+    /// the others.
+    pub(crate) async fn load_instance_metas_lenient(&self) -> Vec<(Uuid, Uuid, Meta)> {
+        self.instance_metas()
+            .await
+            .into_iter()
+            .filter_map(|result| result.map_err(|error| self.warn(format!("{:#}", error))).ok())
+            .collect()
+    }
+
+    async fn instance_metas(&self) -> Vec<Result<(Uuid, Uuid, Meta)>> {
+        let mut metas = join_all(self.manifest.apps.iter().map(async |app| {
+            let meta = Meta::load(&self.cwd.join("apps").join(app.id.to_string()))
+                .await
+                .with_context(|| format!("Failed to load instance {}", app.id))?;
+            anyhow::Ok((app.id, app.id, meta))
+        }))
+        .await;
+        for (app_id, backups) in &self.state {
+            for (id, meta) in backups {
+                let ids = Uuid::parse_str(app_id).and_then(|app_id| Ok((app_id, Uuid::parse_str(id)?)));
+                metas.push(
+                    ids.map(|(app_id, id)| (app_id, id, meta.clone()))
+                        .with_context(|| format!("Invalid backup {} of application {}", id, app_id)),
+                );
             }
         }
-        let release_dir = self.cwd.join("home/.yarn/releases");
-        try_join_all(
-            releases
-                .into_iter()
-                .map(async |name| fs::remove_file(&release_dir.join(&name)).await),
-        )
-        .await?;
-        let cache_dir = self.cwd.join("home/.yarn/cache");
-        try_join_all(
-            cache
-                .into_values()
-                .flat_map(|locators| locators.into_values())
-                .map(async |name| fs::remove_file(&cache_dir.join(&name)).await),
-        )
+        metas
+    }
+
+    pub async fn gc(&self) -> Result<()> {
+        let cache = self.load_cache().await?;
+        let mut unused = self.load_store_files(&cache).await?;
+        for (_, _, meta) in self.load_instance_metas().await? {
+            for file in store_references(&meta, &cache)? {
+                unused.remove(&file);
+            }
+        }
+        let store_dir = self.cwd.join("home/.yarn");
+        let paths = unused.into_iter().map(|file| store_dir.join(file)).collect::<Vec<_>>();
+        let progress = self.start_phase(Phase::Collect, Some(paths.len() as u64), None)?;
+        try_join_all(paths.iter().map(async |path| {
+            let size = fs::metadata(path).await?.len();
+            fs::remove_file(path).await?;
+            progress.advance(1, size)?;
+            anyhow::Ok(())
+        }))
         .await?;
+        progress.finish();
         Ok(())
     }
 }
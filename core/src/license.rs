@@ -0,0 +1,329 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use uuid::Uuid;
+use zip::ZipArchive;
+
+use crate::Cirno;
+
+/// Number of cache archives read at the same time.
+const READ_CONCURRENCY: usize = 16;
+
+/// File name prefixes (lowercased) that are considered license texts.
+const LICENSE_FILE_PREFIXES: [&str; 3] = ["license", "licence", "copying"];
+
+/// License allow/deny list of the environment, configured under `config.licenses` in `cirno.yml`.
+///
+/// Both lists contain SPDX license identifiers (eg. `MIT`, `Apache-2.0`), compared case-insensitively. A license is
+/// approved if it is not denied, and the allow list is either empty or contains it. `GPL-2.0+` may be listed as such,
+/// as `GPL-2.0-or-later` or as `GPL-2.0`, and `Apache-2.0 WITH LLVM-exception` either as a whole or as `Apache-2.0`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct LicensePolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl LicensePolicy {
+    /// Checks a license, which is denied if any of the names it may be listed under is denied.
+    fn check_license(&self, id: &str, exception: Option<&str>) -> LicenseStatus {
+        let mut ids = vec![id.to_string()];
+        if let Some(base) = id.strip_suffix('+') {
+            ids.push(format!("{}-or-later", base));
+            ids.push(base.to_string());
+        }
+        let names = match exception {
+            Some(exception) => ids
+                .iter()
+                .map(|id| format!("{} WITH {}", id, exception))
+                .chain(ids.iter().cloned())
+                .collect(),
+            None => ids,
+        };
+        let listed = |list: &[String]| {
+            names
+                .iter()
+                .any(|name| list.iter().any(|x| x.eq_ignore_ascii_case(name)))
+        };
+        if listed(&self.deny) {
+            LicenseStatus::Denied
+        } else if self.allow.is_empty() || listed(&self.allow) {
+            LicenseStatus::Approved
+        } else {
+            LicenseStatus::NotApproved
+        }
+    }
+
+    /// Checks an SPDX license expression against the policy.
+    ///
+    /// `OR` expressions are approved if any of their operands is approved, `AND` expressions only if all of them are.
+    /// Operators are case-insensitive, and an expression which cannot be parsed is checked as a single license.
+    pub fn check(&self, expression: &str) -> LicenseStatus {
+        match SpdxExpression::parse(expression) {
+            Some(expression) => expression.check(self),
+            None => self.check_license(expression.trim(), None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LicenseStatus {
+    /// The license is allowed by the policy.
+    Approved,
+    /// The license is explicitly denied by the policy.
+    Denied,
+    /// The license is not in the allow list.
+    NotApproved,
+    /// The package does not declare any license.
+    Missing,
+}
+
+impl LicenseStatus {
+    pub fn is_flagged(&self) -> bool {
+        *self != LicenseStatus::Approved
+    }
+}
+
+enum SpdxExpression {
+    License { id: String, exception: Option<String> },
+    And(Vec<SpdxExpression>),
+    Or(Vec<SpdxExpression>),
+}
+
+/// Whether a token is the given operator, which SPDX matches case-insensitively.
+fn is_operator(token: Option<&&str>, operator: &str) -> bool {
+    token.is_some_and(|token| token.eq_ignore_ascii_case(operator))
+}
+
+impl SpdxExpression {
+    fn parse(input: &str) -> Option<Self> {
+        let spaced = input.replace('(', " ( ").replace(')', " ) ");
+        let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+        let mut pos = 0;
+        let expression = Self::parse_or(&tokens, &mut pos)?;
+        (pos == tokens.len()).then_some(expression)
+    }
+
+    fn parse_or(tokens: &[&str], pos: &mut usize) -> Option<Self> {
+        let mut operands = vec![Self::parse_and(tokens, pos)?];
+        while is_operator(tokens.get(*pos), "OR") {
+            *pos += 1;
+            operands.push(Self::parse_and(tokens, pos)?);
+        }
+        Some(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Self::Or(operands)
+        })
+    }
+
+    fn parse_and(tokens: &[&str], pos: &mut usize) -> Option<Self> {
+        let mut operands = vec![Self::parse_atom(tokens, pos)?];
+        while is_operator(tokens.get(*pos), "AND") {
+            *pos += 1;
+            operands.push(Self::parse_atom(tokens, pos)?);
+        }
+        Some(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Self::And(operands)
+        })
+    }
+
+    fn parse_atom(tokens: &[&str], pos: &mut usize) -> Option<Self> {
+        let token = tokens.get(*pos)?;
+        if *token == "(" {
+            *pos += 1;
+            let expression = Self::parse_or(tokens, pos)?;
+            if tokens.get(*pos) != Some(&")") {
+                return None;
+            }
+            *pos += 1;
+            return Some(expression);
+        }
+        if *token == ")"
+            || ["AND", "OR", "WITH"]
+                .iter()
+                .any(|operator| is_operator(Some(token), operator))
+        {
+            return None;
+        }
+        *pos += 1;
+        let mut exception = None;
+        if is_operator(tokens.get(*pos), "WITH") {
+            let name = tokens.get(*pos + 1).filter(|name| !["(", ")"].contains(name))?;
+            exception = Some(name.to_string());
+            *pos += 2;
+        }
+        Some(Self::License {
+            id: token.to_string(),
+            exception,
+        })
+    }
+
+    fn check(&self, policy: &LicensePolicy) -> LicenseStatus {
+        match self {
+            Self::License { id, exception } => policy.check_license(id, exception.as_deref()),
+            Self::And(operands) => operands
+                .iter()
+                .map(|operand| operand.check(policy))
+                .find(LicenseStatus::is_flagged)
+                .unwrap_or(LicenseStatus::Approved),
+            Self::Or(operands) => {
+                let statuses = operands.iter().map(|operand| operand.check(policy)).collect::<Vec<_>>();
+                if statuses.contains(&LicenseStatus::Approved) {
+                    LicenseStatus::Approved
+                } else if statuses.contains(&LicenseStatus::NotApproved) {
+                    LicenseStatus::NotApproved
+                } else {
+                    LicenseStatus::Denied
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseFile {
+    /// Path of the file relative to the package root.
+    pub path: String,
+    /// Hex-encoded SHA-512 digest of the file content.
+    pub sha512: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageLicense {
+    pub name: String,
+    pub version: String,
+    pub resolution: String,
+    /// SPDX license expression declared in the package's `package.json`.
+    pub license: Option<String>,
+    pub files: Vec<LicenseFile>,
+    pub status: LicenseStatus,
+}
+
+/// Extracts the license expression from a `package.json`, supporting both the `license` field and the deprecated
+/// `licenses` array.
+fn get_license_expression(manifest: &Value) -> Option<String> {
+    match manifest.get("license") {
+        Some(Value::String(license)) => return Some(license.clone()),
+        Some(Value::Object(license)) => {
+            if let Some(Value::String(license)) = license.get("type") {
+                return Some(license.clone());
+            }
+        }
+        _ => {}
+    }
+    let licenses = manifest
+        .get("licenses")?
+        .as_array()?
+        .iter()
+        .filter_map(|license| match license {
+            Value::String(license) => Some(license.as_str()),
+            Value::Object(license) => license.get("type")?.as_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    match licenses.len() {
+        0 => None,
+        1 => Some(licenses[0].to_string()),
+        _ => Some(format!("({})", licenses.join(" OR "))),
+    }
+}
+
+/// Reads the `package.json` and the license files of a package from its cache archive, without loading the whole
+/// archive in memory.
+async fn read_cache_archive(path: PathBuf, ident: String) -> Result<(Option<String>, Vec<LicenseFile>)> {
+    tokio::task::spawn_blocking(move || {
+        read_cache_file(&path, &ident).with_context(|| format!("Failed to read cache archive: {}", path.display()))
+    })
+    .await?
+}
+
+fn read_cache_file(path: &Path, ident: &str) -> Result<(Option<String>, Vec<LicenseFile>)> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let root = format!("node_modules/{}/", ident);
+    let mut license = None;
+    let mut files = vec![];
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(path) = file.name().strip_prefix(&root).map(str::to_string) else {
+            continue;
+        };
+        if path.contains('/') {
+            continue;
+        }
+        if path == "package.json" {
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            let manifest: Value = serde_json::from_str(&content)?;
+            license = get_license_expression(&manifest);
+        } else if LICENSE_FILE_PREFIXES
+            .iter()
+            .any(|prefix| path.to_lowercase().starts_with(prefix))
+        {
+            let mut content = vec![];
+            file.read_to_end(&mut content)?;
+            files.push(LicenseFile {
+                path,
+                sha512: hex::encode(Sha512::digest(&content)),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((license, files))
+}
+
+impl Cirno {
+    /// Collects the license information of every package referenced by an instance, reading each package's cache
+    /// archive, and checks them against the license policy of the environment.
+    pub async fn licenses(&self, id: &Uuid) -> Result<Vec<PackageLicense>> {
+        let meta = self.load_meta(id).await?;
+        let cache = self.load_cache().await?;
+        let cache_dir = self.cwd.join("home/.yarn/cache");
+        let policy = self.manifest.config.licenses.clone().unwrap_or_default();
+        let mut licenses = stream::iter(meta.yarn_lock.get_cache_entries()?)
+            .map(async |(locator, entry)| -> Result<PackageLicense> {
+                let slug = locator.slugify();
+                let name = meta
+                    .yarn_lock
+                    .cache_keys(entry)
+                    .iter()
+                    .find_map(|key| cache.get(key)?.get(&slug))
+                    .ok_or_else(|| anyhow!("Cache not found: {}", slug))?;
+                let ident = locator.stringify_ident();
+                let path = cache_dir.join(name);
+                let (license, files) = read_cache_archive(path, ident.clone()).await?;
+                let status = match &license {
+                    Some(license) => policy.check(license),
+                    None => LicenseStatus::Missing,
+                };
+                Ok(PackageLicense {
+                    name: ident,
+                    version: entry.version.clone(),
+                    resolution: entry.resolution.clone(),
+                    license,
+                    files,
+                    status,
+                })
+            })
+            .buffered(READ_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+        licenses.sort_by(|a, b| a.resolution.cmp(&b.resolution));
+        Ok(licenses)
+    }
+}
@@ -0,0 +1,190 @@
+//! Progress reporting.
+//!
+//! Long-running operations emit [`Event`]s to the [`Reporter`] of the environment, set with
+//! [`Cirno::set_reporter`](crate::Cirno::set_reporter). Events are informative only: reporters cannot alter the
+//! operation, and an environment without a reporter is silent.
+//!
+//! Phases also carry the cancellation token of the environment: advancing a phase fails once the token is cancelled,
+//! so that the loops which report progress are also the points where operations stop.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::Cancelled;
+
+/// Step of a long-running operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    /// Downloading a yarn release.
+    Download,
+    /// Copying an instance, or extracting it from a backup archive.
+    Clone,
+    /// Copying the yarn release, runtime and cache files of an instance into a bundle.
+    Pack,
+    /// Writing a bundle file or an image.
+    Write,
+    /// Extracting or copying a bundle, or checking out a git source.
+    Unpack,
+    /// Verifying a bundle or a yarn release.
+    Verify,
+    /// Moving the yarn release, runtimes and cache files of a bundle to the environment.
+    Unbundle,
+    /// Running `yarn install`.
+    Install,
+    /// Adding an instance to a backup archive.
+    Backup,
+    /// Replacing a head instance with a backup.
+    Restore,
+    /// Removing the yarn releases and cache files which are no longer referenced.
+    Collect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A phase started. Totals are set when they are known in advance.
+    #[serde(rename_all = "camelCase")]
+    PhaseStarted {
+        phase: Phase,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total_files: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total_bytes: Option<u64>,
+    },
+    /// Files and bytes processed since the start of the phase.
+    Progress {
+        phase: Phase,
+        files: u64,
+        bytes: u64,
+    },
+    PhaseFinished {
+        phase: Phase,
+        files: u64,
+        bytes: u64,
+    },
+    Warning {
+        message: String,
+    },
+    /// Line printed by a yarn process run by an operation, such as `yarn install` after an import.
+    YarnOutput {
+        stream: OutputStream,
+        line: String,
+    },
+}
+
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: &Event);
+}
+
+/// Reporter which ignores every event.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SilentReporter;
+
+impl Reporter for SilentReporter {
+    fn report(&self, _event: &Event) {}
+}
+
+/// Progress of a running phase. Clones share the same counters, so that a phase can be advanced from blocking tasks.
+///
+/// A phase which is not finished explicitly, such as one interrupted by an error, is finished when its last clone is
+/// dropped, so that reporters never see a phase which never ends.
+#[derive(Clone)]
+pub struct PhaseProgress {
+    reporter: Arc<dyn Reporter>,
+    cancel: CancellationToken,
+    phase: Phase,
+    files: Arc<AtomicU64>,
+    bytes: Arc<AtomicU64>,
+    finished: Arc<AtomicBool>,
+}
+
+impl PhaseProgress {
+    /// Starts a phase, see [`Event::PhaseStarted`].
+    pub fn start(
+        reporter: Arc<dyn Reporter>,
+        cancel: CancellationToken,
+        phase: Phase,
+        total_files: Option<u64>,
+        total_bytes: Option<u64>,
+    ) -> Self {
+        reporter.report(&Event::PhaseStarted {
+            phase,
+            total_files,
+            total_bytes,
+        });
+        Self {
+            reporter,
+            cancel,
+            phase,
+            files: Default::default(),
+            bytes: Default::default(),
+            finished: Default::default(),
+        }
+    }
+
+    /// Progress which is not reported and cannot be cancelled, for callers which are not interested in it.
+    pub fn silent(phase: Phase) -> Self {
+        Self {
+            reporter: Arc::new(SilentReporter),
+            cancel: CancellationToken::new(),
+            phase,
+            files: Default::default(),
+            bytes: Default::default(),
+            finished: Default::default(),
+        }
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Fails if the operation is cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Reports processed files and bytes, and fails if the operation is cancelled.
+    pub fn advance(&self, files: u64, bytes: u64) -> Result<(), Cancelled> {
+        let files = self.files.fetch_add(files, Ordering::Relaxed) + files;
+        let bytes = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.reporter.report(&Event::Progress {
+            phase: self.phase,
+            files,
+            bytes,
+        });
+        self.check()
+    }
+
+    pub fn finish(&self) {
+        if self.finished.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        self.reporter.report(&Event::PhaseFinished {
+            phase: self.phase,
+            files: self.files.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        });
+    }
+}
+
+impl Drop for PhaseProgress {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.finished) == 1 {
+            self.finish();
+        }
+    }
+}
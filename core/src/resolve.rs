@@ -0,0 +1,185 @@
+//! Resolution of the instance references accepted by commands.
+//!
+//! Besides a full ID, an instance can be referred to by:
+//! - a unique prefix of its ID, of at least [`MIN_PREFIX_LEN`] characters, like git abbreviates commits;
+//! - the name of its application, for the head instance;
+//! - one of its tags or aliases, see [`tag`](crate::tag);
+//! - `<app>@<n>`, the n-th backup of an application, counted from 1 for the oldest one;
+//! - `<app>~<n>`, the n-th instance before the head instance, so that `<app>~0` is the head instance and `<app>~1`
+//!   its last backup.
+//!
+//! In the last two forms, `<app>` is itself the name, a tag or an alias of an application, or a prefix of the ID of its
+//! head instance.
+
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{App, Cirno, Manifest};
+
+/// Minimum length of an ID prefix, shorter prefixes are likely to match names or typos.
+pub const MIN_PREFIX_LEN: usize = 4;
+
+/// `<app>@<n>` or `<app>~<n>`.
+static BACKUP_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+)([@~])(\d+)$").unwrap());
+
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("Instance {0} not found.")]
+    NotFound(String),
+    #[error("Application {app} has no instance {query}.")]
+    BackupNotFound { app: String, query: String },
+    #[error("Instance {query} is ambiguous, it may refer to: {}.", format_candidates(.candidates))]
+    Ambiguous { query: String, candidates: Vec<Candidate> },
+}
+
+/// Instance matching an ambiguous reference.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id: Uuid,
+    /// Name of the application of the instance.
+    pub name: String,
+    /// Position of the backup in its application, counted from 1, or `None` for a head instance.
+    pub backup: Option<usize>,
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.backup {
+            Some(index) => write!(f, "{} ({}@{})", self.id, self.name, index),
+            None => write!(f, "{} ({})", self.id, self.name),
+        }
+    }
+}
+
+fn format_candidates(candidates: &[Candidate]) -> String {
+    candidates
+        .iter()
+        .map(Candidate::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Whether `query` may abbreviate an ID, ie. it is long enough and only has hexadecimal digits and dashes.
+fn is_prefix(query: &str) -> bool {
+    query.len() >= MIN_PREFIX_LEN && query.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+fn matches(id: &Uuid, prefix: &str) -> bool {
+    id.to_string().starts_with(&prefix.to_ascii_lowercase())
+}
+
+fn is_labelled(tags: &[String], aliases: &[String], query: &str) -> bool {
+    tags.iter().chain(aliases).any(|label| label == query)
+}
+
+/// Whether `query` refers to the head instance of `app`, ignoring backup positions.
+fn matches_app(app: &App, query: &str) -> bool {
+    app.name == query || is_labelled(&app.tags, &app.aliases, query) || (is_prefix(query) && matches(&app.id, query))
+}
+
+impl Manifest {
+    /// Resolves a reference to an instance to its ID, see the [module documentation](self).
+    pub fn resolve(&self, query: &str) -> Result<Uuid, ResolveError> {
+        let query = query.trim();
+        if let Ok(id) = Uuid::parse_str(query) {
+            let exists = self
+                .apps
+                .iter()
+                .any(|app| app.id == id || app.backups.iter().any(|backup| backup.id == id));
+            return match exists {
+                true => Ok(id),
+                false => Err(ResolveError::NotFound(query.to_string())),
+            };
+        }
+
+        // npm names may contain `~`, an application named after the whole reference takes precedence
+        if let Some(captures) = BACKUP_REGEX.captures(query)
+            && !self.apps.iter().any(|app| app.name == query)
+            && let Some(app) = self.resolve_app(&captures[1])?
+        {
+            let backups = &app.backups;
+            let index = captures[3].parse::<usize>().ok();
+            let backup = match &captures[2] {
+                "@" => index
+                    .filter(|&index| index >= 1)
+                    .and_then(|index| backups.get(index - 1)),
+                _ => match index {
+                    Some(0) => return Ok(app.id),
+                    Some(index) => backups.len().checked_sub(index).and_then(|index| backups.get(index)),
+                    None => None,
+                },
+            };
+            return backup
+                .map(|backup| backup.id)
+                .ok_or_else(|| ResolveError::BackupNotFound {
+                    app: app.name.clone(),
+                    query: query.to_string(),
+                });
+        }
+
+        let mut candidates = Vec::<Candidate>::new();
+        for app in &self.apps {
+            if matches_app(app, query) {
+                candidates.push(Candidate {
+                    id: app.id,
+                    name: app.name.clone(),
+                    backup: None,
+                });
+            }
+            for (index, backup) in app.backups.iter().enumerate() {
+                if is_labelled(&backup.tags, &backup.aliases, query) || (is_prefix(query) && matches(&backup.id, query))
+                {
+                    candidates.push(Candidate {
+                        id: backup.id,
+                        name: app.name.clone(),
+                        backup: Some(index + 1),
+                    });
+                }
+            }
+        }
+        match candidates.len() {
+            0 => Err(ResolveError::NotFound(query.to_string())),
+            1 => Ok(candidates[0].id),
+            _ => Err(ResolveError::Ambiguous {
+                query: query.to_string(),
+                candidates,
+            }),
+        }
+    }
+
+    /// Resolves the application part of `<app>@<n>` and `<app>~<n>`, or returns `None` if nothing matches, since the
+    /// whole reference may then be the name of an application.
+    fn resolve_app(&self, query: &str) -> Result<Option<&App>, ResolveError> {
+        let apps = self
+            .apps
+            .iter()
+            .filter(|app| matches_app(app, query))
+            .collect::<Vec<_>>();
+        match apps[..] {
+            [] => Ok(None),
+            [app] => Ok(Some(app)),
+            _ => Err(ResolveError::Ambiguous {
+                query: query.to_string(),
+                candidates: apps
+                    .into_iter()
+                    .map(|app| Candidate {
+                        id: app.id,
+                        name: app.name.clone(),
+                        backup: None,
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl Cirno {
+    /// Resolves a reference to an instance of the environment to its ID, see [`Manifest::resolve`].
+    pub fn resolve(&self, query: &str) -> Result<Uuid, ResolveError> {
+        self.manifest.resolve(query)
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::{BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, anyhow, bail};
+use flate2::read::GzDecoder;
+use regex::Regex;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::{Cirno, fs};
+
+/// Top-level directory of the official Node.js archives, eg. `node-v20.11.0-linux-x64`.
+static ARCHIVE_ROOT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^node-v(\d+\.\d+\.\d+)-").unwrap());
+
+/// Directory of the runtime store, relative to the environment root. Each runtime is stored under its version.
+pub const RUNTIME_DIR: &str = "home/.node";
+
+/// Directory of the runtime carried by a bundle, relative to the bundle root.
+pub const BUNDLE_RUNTIME_DIR: &str = ".node";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Runtime {
+    pub version: Version,
+    pub path: PathBuf,
+}
+
+impl Runtime {
+    /// Directory containing the `node` executable.
+    pub fn bin_dir(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.path.clone()
+        } else {
+            self.path.join("bin")
+        }
+    }
+
+    pub fn node(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.path.join("node.exe")
+        } else {
+            self.path.join("bin/node")
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Engines {
+    node: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PackageEngines {
+    #[serde(default)]
+    engines: Engines,
+}
+
+/// Converts a comparator of an npm range (eg. `>=18.x`) into a Cargo one.
+///
+/// Wildcard components (`x`, `X` or `*`) are dropped, since partial versions have the same meaning in both syntaxes.
+/// A bare version is an exact match in npm but a caret requirement in Cargo, so it is prefixed with `=`.
+fn convert_comparator(comparator: &str) -> String {
+    let (operator, version) = comparator.split_at(
+        comparator
+            .find(|c: char| !matches!(c, '<' | '>' | '=' | '~' | '^'))
+            .unwrap_or(comparator.len()),
+    );
+    let mut parts = version.trim_start_matches('v').split('.').collect::<Vec<_>>();
+    while parts.last().is_some_and(|part| matches!(*part, "x" | "X" | "*")) {
+        parts.pop();
+    }
+    match (operator, parts.is_empty()) {
+        (_, true) => "*".to_string(),
+        ("", false) => format!("={}", parts.join(".")),
+        (_, false) => format!("{}{}", operator, parts.join(".")),
+    }
+}
+
+/// Converts an npm version range (eg. `^18 || >=20.1.0 <21`) into the equivalent set of Cargo requirements.
+fn parse_npm_range(range: &str) -> Option<Vec<VersionReq>> {
+    range
+        .split("||")
+        .map(|range| {
+            let range = range.trim();
+            if range.is_empty() {
+                return VersionReq::parse("*").ok();
+            }
+            if let Some((lower, upper)) = range.split_once(" - ") {
+                // the bounds are inclusive, and partial bounds cover every version they match
+                let comparators = [(">=", lower), ("<=", upper)]
+                    .into_iter()
+                    .map(|(operator, bound)| convert_comparator(&format!("{}{}", operator, bound.trim())))
+                    .filter(|comparator| comparator != "*");
+                return VersionReq::parse(&comparators.collect::<Vec<_>>().join(", ")).ok();
+            }
+            // comparators are separated by spaces, which may also follow an operator (`>= 18`)
+            let mut comparators: Vec<String> = vec![];
+            for token in range.split_whitespace() {
+                match comparators.last_mut() {
+                    Some(last) if last.ends_with(['<', '>', '=', '~', '^']) => last.push_str(token),
+                    _ => comparators.push(token.to_string()),
+                }
+            }
+            let comparators = comparators.iter().map(|comparator| convert_comparator(comparator));
+            VersionReq::parse(&comparators.collect::<Vec<_>>().join(", ")).ok()
+        })
+        .collect()
+}
+
+/// Version requirement of an application, as declared in its files.
+enum Declaration {
+    /// Content of a version file, which is a (possibly partial) version.
+    Version(String),
+    /// `engines.node` of the `package.json`, which is an npm version range.
+    Range(String),
+}
+
+/// Reads the version requirement of an application: `.node-version`, then `.nvmrc`, then `engines.node`.
+///
+/// Version files may contain a partial version (eg. `20`), which matches the latest installed `20.x.x`. Aliases such
+/// as `lts/*` or `node` are not supported and ignored.
+async fn read_declaration(cwd: &Path) -> Result<Option<Declaration>> {
+    for name in [".node-version", ".nvmrc"] {
+        let content = match tokio::fs::read_to_string(cwd.join(name)).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => return Err(error).with_context(|| format!("Failed to read file: {}", name)),
+        };
+        let version = content.trim().trim_start_matches('v');
+        if VersionReq::parse(&format!("={}", version)).is_ok() {
+            return Ok(Some(Declaration::Version(version.to_string())));
+        }
+    }
+    let package: PackageEngines = serde_json::from_str(&fs::read_to_string(cwd.join("package.json")).await?)?;
+    Ok(package.engines.node.map(Declaration::Range))
+}
+
+async fn read_requirement(cwd: &Path) -> Result<Option<Vec<VersionReq>>> {
+    Ok(match read_declaration(cwd).await? {
+        Some(Declaration::Version(version)) => VersionReq::parse(&format!("={}", version)).ok().map(|req| vec![req]),
+        Some(Declaration::Range(range)) => parse_npm_range(&range),
+        None => None,
+    })
+}
+
+/// Returns the version requirement declared by an application, see [`read_requirement`].
+pub async fn declared_requirement(cwd: &Path) -> Result<Option<String>> {
+    Ok(read_declaration(cwd).await?.map(|declaration| match declaration {
+        Declaration::Version(version) => version,
+        Declaration::Range(range) => range,
+    }))
+}
+
+/// Lists the runtimes of a store, sorted by version.
+pub async fn list(store: &Path) -> Result<Vec<Runtime>> {
+    let mut runtimes = vec![];
+    let mut dir = match tokio::fs::read_dir(store).await {
+        Ok(dir) => dir,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(runtimes),
+        Err(error) => return Err(error).with_context(|| format!("Failed to read directory: {}", store.display())),
+    };
+    while let Some(entry) = dir.next_entry().await? {
+        let Ok(version) = Version::parse(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        runtimes.push(Runtime {
+            version,
+            path: entry.path(),
+        });
+    }
+    runtimes.sort_by(|a, b| a.version.cmp(&b.version));
+    Ok(runtimes)
+}
+
+/// Picks the latest runtime of a store that satisfies the requirement of the application at `cwd`.
+///
+/// Returns `None` if the application declares no requirement or no runtime satisfies it, in which case the caller
+/// falls back to its default `node` executable.
+pub async fn resolve(store: &Path, cwd: &Path) -> Result<Option<Runtime>> {
+    let Some(reqs) = read_requirement(cwd).await? else {
+        return Ok(None);
+    };
+    Ok(list(store)
+        .await?
+        .into_iter()
+        .rev()
+        .find(|runtime| reqs.iter().any(|req| req.matches(&runtime.version))))
+}
+
+/// Extracts an official Node.js archive (`.tar.gz` or `.zip`) into `dest`, and returns the name of its top-level
+/// directory.
+fn extract_archive(archive: &Path, dest: &Path) -> Result<String> {
+    let file = File::open(archive).with_context(|| format!("Failed to read file: {}", archive.display()))?;
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        tar::Archive::new(GzDecoder::new(BufReader::new(file))).unpack(dest)?;
+    } else if name.ends_with(".zip") {
+        ZipArchive::new(BufReader::new(file))?.extract(dest)?;
+    } else {
+        bail!("Unsupported runtime archive: {} (expected .tar.gz or .zip).", name);
+    }
+    let mut roots = std::fs::read_dir(dest)?.collect::<Result<Vec<_>, _>>()?;
+    match (roots.pop(), roots.is_empty()) {
+        (Some(root), true) if root.file_type()?.is_dir() => Ok(root.file_name().to_string_lossy().to_string()),
+        _ => bail!("Runtime archive must contain a single top-level directory."),
+    }
+}
+
+impl Cirno {
+    pub async fn runtimes(&self) -> Result<Vec<Runtime>> {
+        list(&self.cwd.join(RUNTIME_DIR)).await
+    }
+
+    /// Picks the managed runtime of the application at `cwd`, see [`resolve`].
+    pub async fn resolve_runtime(&self, cwd: &Path) -> Result<Option<Runtime>> {
+        resolve(&self.cwd.join(RUNTIME_DIR), cwd).await
+    }
+
+    /// Installs a runtime from an official Node.js archive on the local file system.
+    pub async fn install_runtime(&self, archive: &Path) -> Result<Runtime> {
+        let temp_dir = self.temp_dir().await?;
+        let temp = temp_dir.path();
+        fs::create_dir_all(temp).await?;
+        let result = async {
+            let (archive, dest) = (archive.to_path_buf(), temp.to_path_buf());
+            let root = tokio::task::spawn_blocking(move || extract_archive(&archive, &dest)).await??;
+            let version = ARCHIVE_ROOT_REGEX
+                .captures(&root)
+                .and_then(|captures| Version::parse(&captures[1]).ok())
+                .ok_or_else(|| anyhow!("Failed to detect runtime version from {}.", root))?;
+            let path = self.cwd.join(RUNTIME_DIR).join(version.to_string());
+            if tokio::fs::try_exists(&path).await? {
+                bail!("Runtime {} is already installed.", version);
+            }
+            fs::create_dir_all(self.cwd.join(RUNTIME_DIR)).await?;
+            fs::rename(temp.join(&root), &path).await?;
+            Ok(Runtime { version, path })
+        }
+        .await;
+        temp_dir.remove().await?;
+        result
+    }
+
+    /// Removes a runtime from the store. Runtimes resolved by an application are kept unless `force` is set.
+    pub async fn remove_runtime(&self, version: &Version, force: bool) -> Result<()> {
+        let path = self.cwd.join(RUNTIME_DIR).join(version.to_string());
+        if !tokio::fs::try_exists(&path).await? {
+            bail!("Runtime {} is not installed.", version);
+        }
+        if !force {
+            for app in &self.manifest.apps {
+                let cwd = self.cwd.join("apps").join(app.id.to_string());
+                if self
+                    .resolve_runtime(&cwd)
+                    .await?
+                    .is_some_and(|runtime| &runtime.version == version)
+                {
+                    bail!("Runtime {} is used by application {} ({}).", version, app.name, app.id);
+                }
+            }
+        }
+        fs::remove_dir_all(&path).await
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{Error, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::yarn::{Locator, PackageManager, YarnLock};
+use crate::{Cirno, Meta};
+
+/// Software bill of materials formats supported by `cirno sbom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SbomFormat {
+    /// [CycloneDX 1.5](https://cyclonedx.org/docs/1.5/json/) in JSON.
+    CyclonedxJson,
+    /// [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/) in JSON.
+    SpdxJson,
+}
+
+impl SbomFormat {
+    /// Name of the SBOM file when it is embedded in a bundle.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SbomFormat::CyclonedxJson => "sbom.cdx.json",
+            SbomFormat::SpdxJson => "sbom.spdx.json",
+        }
+    }
+}
+
+impl FromStr for SbomFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cyclonedx-json" => Ok(SbomFormat::CyclonedxJson),
+            "spdx-json" => Ok(SbomFormat::SpdxJson),
+            _ => Err(anyhow!("Unsupported SBOM format: {}", s)),
+        }
+    }
+}
+
+struct Component {
+    name: String,
+    version: String,
+    resolution: String,
+    purl: String,
+    /// Hex-encoded SHA-512 digest of the cache archive, as recorded in the lockfile.
+    sha512: Option<String>,
+    depends_on: Vec<String>,
+    workspace: bool,
+}
+
+/// Package URL of an npm package. The `@` of scoped packages must be percent-encoded.
+fn get_purl(locator: &Locator, version: &str) -> String {
+    match &locator.ident.scope {
+        Some(scope) => format!("pkg:npm/%40{}/{}@{}", scope, locator.ident.name, version),
+        None => format!("pkg:npm/{}@{}", locator.ident.name, version),
+    }
+}
+
+fn get_components(yarn_lock: &YarnLock) -> Result<Vec<Component>> {
+    // Each lockfile key is a comma-separated list of the descriptors resolved to the entry.
+    let descriptors = yarn_lock
+        .packages
+        .iter()
+        .flat_map(|(key, entry)| key.split(", ").map(move |descriptor| (descriptor, &entry.resolution)))
+        .collect::<HashMap<_, _>>();
+    let mut components = yarn_lock
+        .packages
+        .values()
+        .map(|entry| {
+            let locator = Locator::try_parse(&entry.resolution, true)
+                .ok_or_else(|| anyhow!("Failed to parse resolution: {}", entry.resolution))?;
+            let mut depends_on = entry
+                .dependencies
+                .iter()
+                .flatten()
+                .filter_map(|(name, range)| descriptors.get(format!("{}@{}", name, range).as_str()))
+                .map(|resolution| resolution.to_string())
+                .collect::<Vec<_>>();
+            depends_on.sort();
+            Ok(Component {
+                name: locator.stringify_ident(),
+                version: entry.version.clone(),
+                resolution: entry.resolution.clone(),
+                purl: get_purl(&locator, &entry.version),
+                sha512: entry
+                    .checksum
+                    .as_ref()
+                    .map(|checksum| checksum.rsplit('/').next().unwrap_or(checksum).to_string()),
+                depends_on,
+                workspace: locator.reference.starts_with("workspace:"),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    components.sort_by(|a, b| (!a.workspace, &a.resolution).cmp(&(!b.workspace, &b.resolution)));
+    Ok(components)
+}
+
+/// Version of the package manager, without the Corepack hash.
+fn get_package_manager_version(meta: &Meta) -> String {
+    match meta.package.package_manager.parse::<PackageManager>() {
+        Ok(package_manager) => package_manager.version,
+        Err(_) => meta.package.package_manager.clone(),
+    }
+}
+
+fn get_timestamp() -> String {
+    jiff::Timestamp::now().strftime("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn generate_cyclonedx(meta: &Meta, components: &[Component]) -> Value {
+    let root = components
+        .iter()
+        .find(|component| component.resolution.ends_with("@workspace:."));
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", Uuid::new_v4()),
+        "version": 1,
+        "metadata": {
+            "timestamp": get_timestamp(),
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "cirno",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }, {
+                    "type": "application",
+                    "name": "yarn",
+                    "version": get_package_manager_version(meta),
+                }],
+            },
+            "component": root.map(|root| json!({
+                "type": "application",
+                "bom-ref": root.resolution,
+                "name": root.name,
+                "version": root.version,
+            })),
+            "properties": [{
+                "name": "cirno:yarn:packageManager",
+                "value": meta.package.package_manager,
+            }, {
+                "name": "cirno:yarn:lockfileVersion",
+                "value": meta.yarn_lock.metadata.version.to_string(),
+            }, {
+                "name": "cirno:yarn:cacheKey",
+                "value": meta.yarn_lock.metadata.cache_key,
+            }],
+        },
+        "components": components.iter().filter(|component| !root.is_some_and(|root| std::ptr::eq(root, *component))).map(|component| json!({
+            "type": if component.workspace { "application" } else { "library" },
+            "bom-ref": component.resolution,
+            "name": component.name,
+            "version": component.version,
+            "purl": component.purl,
+            "hashes": component.sha512.iter().map(|sha512| json!({
+                "alg": "SHA-512",
+                "content": sha512,
+            })).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+        "dependencies": components.iter().map(|component| json!({
+            "ref": component.resolution,
+            "dependsOn": component.depends_on,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn generate_spdx(meta: &Meta, components: &[Component]) -> Value {
+    let ids = components
+        .iter()
+        .enumerate()
+        .map(|(index, component)| (component.resolution.as_str(), format!("SPDXRef-Package-{}", index + 1)))
+        .collect::<HashMap<_, _>>();
+    let mut relationships = components
+        .iter()
+        .filter(|component| component.workspace)
+        .map(|component| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": ids[component.resolution.as_str()],
+            })
+        })
+        .collect::<Vec<_>>();
+    for component in components {
+        for dependency in &component.depends_on {
+            relationships.push(json!({
+                "spdxElementId": ids[component.resolution.as_str()],
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": ids[dependency.as_str()],
+            }));
+        }
+    }
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": meta.package.name,
+        "documentNamespace": format!("https://spdx.org/spdxdocs/{}-{}", meta.package.name.replace('/', "-"), Uuid::new_v4()),
+        "creationInfo": {
+            "created": get_timestamp(),
+            "creators": [
+                format!("Tool: cirno-{}", env!("CARGO_PKG_VERSION")),
+                format!("Tool: yarn-{}", get_package_manager_version(meta)),
+            ],
+            "comment": format!(
+                "yarn.lock version {}, cache key {}",
+                meta.yarn_lock.metadata.version, meta.yarn_lock.metadata.cache_key,
+            ),
+        },
+        "packages": components.iter().map(|component| json!({
+            "name": component.name,
+            "SPDXID": ids[component.resolution.as_str()],
+            "versionInfo": component.version,
+            "downloadLocation": "NOASSERTION",
+            "filesAnalyzed": false,
+            "checksums": component.sha512.iter().map(|sha512| json!({
+                "algorithm": "SHA512",
+                "checksumValue": sha512,
+            })).collect::<Vec<_>>(),
+            "externalRefs": [{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": component.purl,
+            }],
+        })).collect::<Vec<_>>(),
+        "relationships": relationships,
+    })
+}
+
+/// Generates a software bill of materials from the metadata of an instance.
+///
+/// Components are taken from the lockfile. Their checksums are the SHA-512 digests of the cache archives recorded by
+/// Yarn, and the dependency relationships are resolved through the lockfile descriptors.
+pub fn generate(meta: &Meta, format: SbomFormat) -> Result<Value> {
+    let components = get_components(&meta.yarn_lock)?;
+    Ok(match format {
+        SbomFormat::CyclonedxJson => generate_cyclonedx(meta, &components),
+        SbomFormat::SpdxJson => generate_spdx(meta, &components),
+    })
+}
+
+impl Cirno {
+    pub async fn sbom(&self, id: &Uuid, format: SbomFormat) -> Result<Value> {
+        let meta = self.load_meta(id).await?;
+        generate(&meta, format)
+    }
+}
@@ -0,0 +1,296 @@
+//! Bundle signatures.
+//!
+//! A signed bundle carries [`MANIFEST_FILE`], the SHA-512 digest of every other file of the bundle, and
+//! [`SIGNATURE_FILE`], an ed25519 signature of the raw manifest. Bundles are verified on import against the public
+//! keys trusted by the environment, configured under `config.trust` in `cirno.yml`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, anyhow, bail};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand_core::OsRng;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use tokio::io::AsyncWriteExt;
+
+use crate::{Cirno, fs};
+
+/// Digests of the files of a signed bundle.
+pub const MANIFEST_FILE: &str = "cirno-manifest.json";
+/// Signature of [`MANIFEST_FILE`].
+pub const SIGNATURE_FILE: &str = "cirno-manifest.sig";
+/// Directory of the private keys of the environment, each readable by its owner only.
+pub const KEYS_DIR: &str = "keys";
+
+static KEY_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[\w.-]+$").unwrap());
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrustPolicy {
+    /// Signatures are not verified, as before bundles could be signed.
+    #[default]
+    Off,
+    /// Unsigned bundles and invalid signatures are reported, but the bundle is imported.
+    Warn,
+    /// Only bundles signed by a trusted key are imported.
+    Require,
+}
+
+/// Trust store of the environment, configured under `config.trust` in `cirno.yml`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct TrustConfig {
+    pub policy: TrustPolicy,
+    /// Trusted public keys by name, in hex.
+    pub keys: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignedManifest {
+    /// SHA-512 digest of each file by path, in hex. Symlinks are recorded as `link:<target>`.
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureEnvelope {
+    /// Public key of the signer, in hex.
+    public_key: String,
+    /// Signature of the raw content of [`MANIFEST_FILE`], in hex.
+    signature: String,
+}
+
+/// Result of the verification of a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum SignatureStatus {
+    /// Verification is disabled by the trust policy.
+    Skipped,
+    Unsigned,
+    /// Signed by the trusted key of the given name.
+    Verified {
+        key: String,
+    },
+    /// Signed by a key that is not in the trust store.
+    Untrusted {
+        public_key: String,
+    },
+    Invalid {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyInfo {
+    pub name: String,
+    pub public_key: String,
+    /// Whether the private key is available to sign bundles.
+    pub private: bool,
+}
+
+fn digest_dir(root: &Path, base: &str, files: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in std::fs::read_dir(root).with_context(|| format!("Failed to read directory: {}", root.display()))? {
+        let entry = entry?;
+        let name = format!("{}{}", base, entry.file_name().to_string_lossy());
+        if name == MANIFEST_FILE || name == SIGNATURE_FILE {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            digest_dir(&entry.path(), &format!("{}/", name), files)?;
+        } else if file_type.is_symlink() {
+            let target = std::fs::read_link(entry.path())?;
+            files.insert(name, format!("link:{}", target.to_string_lossy()));
+        } else {
+            let mut hasher = Sha512::new();
+            let mut file =
+                File::open(entry.path()).with_context(|| format!("Failed to read file: {}", entry.path().display()))?;
+            std::io::copy(&mut file, &mut hasher)?;
+            files.insert(name, hex::encode(hasher.finalize()));
+        }
+    }
+    Ok(())
+}
+
+fn parse_public_key(value: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(value.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("Invalid public key: expected 32 bytes in hex."))?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+/// Signs the bundle at `root` by writing its manifest and signature.
+pub fn sign_dir(root: &Path, key: &SigningKey) -> Result<()> {
+    let mut files = BTreeMap::new();
+    digest_dir(root, "", &mut files)?;
+    let manifest = serde_json::to_vec_pretty(&SignedManifest { files })?;
+    let envelope = SignatureEnvelope {
+        public_key: hex::encode(key.verifying_key().as_bytes()),
+        signature: hex::encode(key.sign(&manifest).to_bytes()),
+    };
+    std::fs::write(root.join(MANIFEST_FILE), &manifest)?;
+    std::fs::write(root.join(SIGNATURE_FILE), serde_json::to_vec_pretty(&envelope)?)?;
+    Ok(())
+}
+
+/// Verifies the bundle at `root` against the keys of a trust store. The signature is checked before the digests, so
+/// that the manifest is only trusted once it is known to come from its signer.
+pub fn verify_dir(root: &Path, trust: &TrustConfig) -> Result<SignatureStatus> {
+    let invalid = |reason: String| Ok(SignatureStatus::Invalid { reason });
+    let manifest = match std::fs::read(root.join(MANIFEST_FILE)) {
+        Ok(manifest) => manifest,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(SignatureStatus::Unsigned),
+        Err(error) => return Err(error.into()),
+    };
+    let envelope = match std::fs::read(root.join(SIGNATURE_FILE)) {
+        Ok(envelope) => envelope,
+        Err(error) if error.kind() == ErrorKind::NotFound => return invalid(format!("{} is missing", SIGNATURE_FILE)),
+        Err(error) => return Err(error.into()),
+    };
+    let Ok(envelope) = serde_json::from_slice::<SignatureEnvelope>(&envelope) else {
+        return invalid(format!("{} is malformed", SIGNATURE_FILE));
+    };
+    let Ok(public_key) = parse_public_key(&envelope.public_key) else {
+        return invalid("malformed public key".into());
+    };
+    let signature = hex::decode(&envelope.signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok());
+    let Some(signature) = signature else {
+        return invalid("malformed signature".into());
+    };
+    if public_key.verify_strict(&manifest, &signature).is_err() {
+        return invalid("signature does not match the manifest".into());
+    }
+
+    let Ok(manifest) = serde_json::from_slice::<SignedManifest>(&manifest) else {
+        return invalid(format!("{} is malformed", MANIFEST_FILE));
+    };
+    let mut files = BTreeMap::new();
+    digest_dir(root, "", &mut files)?;
+    for (path, digest) in &manifest.files {
+        match files.remove(path) {
+            Some(actual) if &actual == digest => {}
+            Some(_) => return invalid(format!("{} was modified", path)),
+            None => return invalid(format!("{} is missing", path)),
+        }
+    }
+    if let Some(path) = files.into_keys().next() {
+        return invalid(format!("{} is not signed", path));
+    }
+
+    let key = trust
+        .keys
+        .iter()
+        .find(|(_, key)| parse_public_key(key).is_ok_and(|key| key == public_key));
+    Ok(match key {
+        Some((name, _)) => SignatureStatus::Verified { key: name.clone() },
+        None => SignatureStatus::Untrusted {
+            public_key: envelope.public_key,
+        },
+    })
+}
+
+fn check_key_name(name: &str) -> Result<()> {
+    if !KEY_NAME_REGEX.is_match(name) {
+        bail!(
+            "Invalid key name: {} (expected letters, digits, `.`, `_` or `-`).",
+            name
+        );
+    }
+    Ok(())
+}
+
+impl Cirno {
+    pub fn trust(&self) -> TrustConfig {
+        self.manifest.config.trust.clone().unwrap_or_default()
+    }
+
+    /// Generates a key pair. The private key is stored in [`KEYS_DIR`], and the public key is trusted.
+    pub async fn generate_key(&mut self, name: &str) -> Result<KeyInfo> {
+        check_key_name(name)?;
+        if self.trust().keys.contains_key(name) {
+            bail!("Key {} already exists.", name);
+        }
+        let key = SigningKey::generate(&mut OsRng);
+        let path = self.cwd.join(KEYS_DIR).join(format!("{}.key", name));
+        fs::create_dir_all(self.cwd.join(KEYS_DIR)).await?;
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        file.write_all(hex::encode(key.to_bytes()).as_bytes()).await?;
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        self.import_key(name, &public_key).await?;
+        Ok(KeyInfo {
+            name: name.to_string(),
+            public_key,
+            private: true,
+        })
+    }
+
+    /// Adds a public key (in hex) to the trust store.
+    pub async fn import_key(&mut self, name: &str, public_key: &str) -> Result<()> {
+        check_key_name(name)?;
+        let public_key = hex::encode(parse_public_key(public_key)?.as_bytes());
+        let trust = self.manifest.config.trust.get_or_insert_default();
+        if trust.keys.contains_key(name) {
+            bail!("Key {} already exists.", name);
+        }
+        trust.keys.insert(name.to_string(), public_key);
+        self.save().await
+    }
+
+    /// Lists the trusted keys.
+    pub async fn keys(&self) -> Result<Vec<KeyInfo>> {
+        let mut keys = vec![];
+        for (name, public_key) in self.trust().keys {
+            let private = tokio::fs::try_exists(self.cwd.join(KEYS_DIR).join(format!("{}.key", name))).await?;
+            keys.push(KeyInfo {
+                name,
+                public_key,
+                private,
+            });
+        }
+        Ok(keys)
+    }
+
+    pub async fn load_signing_key(&self, name: &str) -> Result<SigningKey> {
+        check_key_name(name)?;
+        let path = self.cwd.join(KEYS_DIR).join(format!("{}.key", name));
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => bail!("Private key {} not found.", name),
+            Err(error) => return Err(error).with_context(|| format!("Failed to read file: {}", path.display())),
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).await?.permissions().mode();
+            if mode & 0o077 != 0 {
+                bail!(
+                    "Private key {} is accessible by other users, run `chmod 600` on it.",
+                    path.display()
+                );
+            }
+        }
+        let bytes: [u8; 32] = hex::decode(content.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!("Invalid private key: {}", path.display()))?;
+        Ok(SigningKey::from_bytes(&bytes))
+    }
+}
@@ -0,0 +1,128 @@
+//! Tags and aliases of instances.
+//!
+//! Tags are labels which may be shared by several instances (eg. `customer-x`), while an alias names a single instance
+//! of the environment (eg. `stable`). Both can be used to refer to an instance, see [`resolve`](crate::resolve), and
+//! tagged backups are kept when a backup timeline is pruned, unless forced.
+
+use std::sync::LazyLock;
+
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::Cirno;
+
+/// Valid tags and aliases, which cannot be mistaken for the `<app>@<n>` and `<app>~<n>` references.
+static LABEL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[A-Za-z0-9][A-Za-z0-9._-]*$").unwrap());
+
+/// Tags and aliases of an instance.
+struct Labels<'a> {
+    tags: &'a mut Vec<String>,
+    aliases: &'a mut Vec<String>,
+}
+
+impl Cirno {
+    fn labels_mut(&mut self, id: &Uuid) -> Result<Labels<'_>> {
+        for app in &mut self.manifest.apps {
+            if &app.id == id {
+                return Ok(Labels {
+                    tags: &mut app.tags,
+                    aliases: &mut app.aliases,
+                });
+            }
+            if let Some(backup) = app.backups.iter_mut().find(|backup| &backup.id == id) {
+                return Ok(Labels {
+                    tags: &mut backup.tags,
+                    aliases: &mut backup.aliases,
+                });
+            }
+        }
+        Err(anyhow!("Instance {} not found.", id))
+    }
+
+    /// Returns the instance which has an alias, if any.
+    fn find_alias(&self, alias: &str) -> Option<Uuid> {
+        self.manifest.apps.iter().find_map(|app| {
+            let has_alias = |aliases: &[String]| aliases.iter().any(|a| a == alias);
+            match has_alias(&app.aliases) {
+                true => Some(app.id),
+                false => app
+                    .backups
+                    .iter()
+                    .find(|backup| has_alias(&backup.aliases))
+                    .map(|backup| backup.id),
+            }
+        })
+    }
+
+    /// Adds tags to an instance, or aliases with `alias`. An alias may not be used by another instance, nor be the name
+    /// of an application.
+    pub async fn tag(&mut self, id: &Uuid, labels: &[String], alias: bool) -> Result<()> {
+        for label in labels {
+            if !LABEL_REGEX.is_match(label) {
+                bail!("Invalid tag: {}.", label);
+            }
+            if alias {
+                if let Some(other) = self.find_alias(label).filter(|other| other != id) {
+                    bail!("Alias {} is already used by instance {}.", label, other);
+                }
+                if self.manifest.apps.iter().any(|app| &app.name == label) {
+                    bail!("Alias {} is already the name of an application.", label);
+                }
+            }
+        }
+        let instance = self.labels_mut(id)?;
+        let list = match alias {
+            true => instance.aliases,
+            false => instance.tags,
+        };
+        for label in labels {
+            if !list.contains(label) {
+                list.push(label.clone());
+            }
+        }
+        self.save().await
+    }
+
+    /// Removes tags or aliases from an instance.
+    pub async fn untag(&mut self, id: &Uuid, labels: &[String]) -> Result<()> {
+        let instance = self.labels_mut(id)?;
+        for label in labels {
+            if !instance.tags.contains(label) && !instance.aliases.contains(label) {
+                bail!("Instance {} has no tag {}.", id, label);
+            }
+        }
+        instance.tags.retain(|tag| !labels.contains(tag));
+        instance.aliases.retain(|alias| !labels.contains(alias));
+        self.save().await
+    }
+
+    /// Fails if one of the instances of `ids` has tags or aliases, before they are pruned from the timeline of an
+    /// application. `ids` may include the head instance, which a restore discards.
+    pub(crate) fn check_untagged(&self, app_id: &Uuid, ids: &[Uuid]) -> Result<()> {
+        let app = self
+            .manifest
+            .apps
+            .iter()
+            .find(|app| &app.id == app_id)
+            .ok_or_else(|| anyhow!("Application {} not found.", app_id))?;
+        let head = (app.id, &app.tags, &app.aliases);
+        let backups = app
+            .backups
+            .iter()
+            .map(|backup| (backup.id, &backup.tags, &backup.aliases));
+        for (id, tags, aliases) in std::iter::once(head).chain(backups) {
+            let labels = tags.iter().chain(aliases).cloned().collect::<Vec<_>>();
+            if ids.contains(&id) && !labels.is_empty() {
+                let kind = if id == app.id { "Instance" } else { "Backup" };
+                bail!(
+                    "{} {} is tagged ({}). Use --force to remove it anyway.",
+                    kind,
+                    id,
+                    labels.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
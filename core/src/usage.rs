@@ -0,0 +1,263 @@
+//! Disk usage of applications and of the environment.
+//!
+//! The files of the shared stores (the yarn cache and releases) are attributed to the instances which reference them,
+//! with the same reachability as [`Cirno::gc`]: a file referenced only by the instances of one application is freed
+//! when the application is removed, while a file also referenced by other applications is shared.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::baka::backup_sizes;
+use crate::{Cirno, fs, store_references};
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppUsage {
+    /// Bytes of the head instance, in `apps/<id>`.
+    pub files: u64,
+    /// Bytes of the backup archive, in `baka/<id>.tar.br`.
+    pub backups: u64,
+    /// Bytes of the shared stores only referenced by the application and its backups.
+    pub exclusive_store: u64,
+    /// Bytes of the shared stores also referenced by other applications.
+    pub shared_store: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceDiskUsage {
+    pub id: Uuid,
+    /// Position of a backup in the timeline, counted from 1, or `None` for the head instance.
+    pub backup: Option<usize>,
+    /// Bytes of the head instance, in `apps/<id>`. Always 0 for a backup.
+    pub files: u64,
+    /// Bytes of a backup in the archive of the application. Always 0 for the head instance.
+    ///
+    /// Since the archive is compressed as a whole, this is its size split in proportion of the files of each backup.
+    pub backups: u64,
+    /// Bytes of the shared stores only referenced by the instance, which removing the instance frees.
+    pub exclusive_store: u64,
+    /// Bytes of the shared stores referenced by the instance and by other instances.
+    pub shared_store: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDiskUsage {
+    pub id: Uuid,
+    pub name: String,
+    /// Usage of the application as a whole, see [`AppUsage::exclusive`] for what removing it frees.
+    #[serde(flatten)]
+    pub total: AppUsage,
+    /// Usage of each instance, from the oldest backup to the head instance.
+    pub instances: Vec<InstanceDiskUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentUsage {
+    /// Bytes of the shared cache, in `home/.yarn/cache`.
+    pub cache: u64,
+    /// Bytes of the yarn releases, in `home/.yarn/releases`.
+    pub releases: u64,
+    /// Bytes of the cache files and yarn releases referenced by no instance, which [`Cirno::gc`] frees.
+    pub unreferenced: u64,
+    /// Bytes of the head instances, in `apps/`.
+    pub apps: u64,
+    /// Bytes of the backup archives, in `baka/`.
+    pub backups: u64,
+    /// Bytes of the temporary files, in `tmp/`.
+    pub tmp: u64,
+    /// Bytes of the logs of the daemon, in `logs/`.
+    pub logs: u64,
+    /// Bytes of the whole environment.
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsage {
+    pub apps: Vec<AppDiskUsage>,
+    pub environment: EnvironmentUsage,
+}
+
+/// File of the shared stores, see [`Cirno::store_usage`].
+pub(crate) struct StoreFile {
+    pub(crate) size: u64,
+    /// IDs of the instances which reference the file, along with the IDs of their application.
+    pub(crate) instances: HashSet<(Uuid, Uuid)>,
+}
+
+impl StoreFile {
+    /// Returns the applications which reference the file through any of their instances.
+    pub(crate) fn apps(&self) -> HashSet<Uuid> {
+        self.instances.iter().map(|(app_id, _)| *app_id).collect()
+    }
+}
+
+impl AppUsage {
+    /// Bytes freed by removing the application with its backups.
+    pub fn exclusive(&self) -> u64 {
+        self.files + self.backups + self.exclusive_store
+    }
+}
+
+impl Cirno {
+    /// Returns the files of the shared stores which are referenced by instances, with their size and the instances
+    /// which reference them.
+    pub(crate) async fn store_usage(&self) -> Result<HashMap<String, StoreFile>> {
+        let cache = self.load_cache().await?;
+        let files = self.load_store_files(&cache).await?;
+        let mut usage = HashMap::<String, StoreFile>::new();
+        for (app_id, id, meta) in self.load_instance_metas_lenient().await {
+            let references = match store_references(&meta, &cache) {
+                Ok(references) => references,
+                Err(error) => {
+                    self.warn(format!("Failed to read the lockfile of instance {}: {:#}", id, error));
+                    continue;
+                }
+            };
+            for file in references {
+                if !files.contains(&file) {
+                    continue;
+                }
+                if !usage.contains_key(&file) {
+                    let size = file_size(&self.cwd.join("home/.yarn").join(&file)).await?;
+                    let store_file = StoreFile {
+                        size,
+                        instances: HashSet::new(),
+                    };
+                    usage.insert(file.clone(), store_file);
+                }
+                if let Some(store_file) = usage.get_mut(&file) {
+                    store_file.instances.insert((app_id, id));
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Computes the disk usage of every application, by ID.
+    pub async fn app_usage(&self) -> Result<HashMap<Uuid, AppUsage>> {
+        self.app_usage_with(&self.store_usage().await?).await
+    }
+
+    async fn app_usage_with(&self, store: &HashMap<String, StoreFile>) -> Result<HashMap<Uuid, AppUsage>> {
+        let mut usage = HashMap::new();
+        for app in &self.manifest.apps {
+            let app_usage = AppUsage {
+                files: fs::dir_size(self.cwd.join("apps").join(app.id.to_string())).await?,
+                backups: file_size(&self.archive_path(&app.id)).await?,
+                ..Default::default()
+            };
+            usage.insert(app.id, app_usage);
+        }
+        for store_file in store.values() {
+            let apps = store_file.apps();
+            for app in &apps {
+                if let Some(app_usage) = usage.get_mut(app) {
+                    match apps.len() {
+                        1 => app_usage.exclusive_store += store_file.size,
+                        _ => app_usage.shared_store += store_file.size,
+                    }
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    fn archive_path(&self, app_id: &Uuid) -> PathBuf {
+        self.cwd.join("baka").join(format!("{}.tar.br", app_id))
+    }
+
+    /// Computes the disk usage of every application and instance, and of the environment as a whole.
+    pub async fn disk_usage(&self) -> Result<DiskUsage> {
+        let store = self.store_usage().await?;
+        let mut app_usage = self.app_usage_with(&store).await?;
+        let mut instance_store = HashMap::<Uuid, (u64, u64)>::new();
+        for store_file in store.values() {
+            for (_, id) in &store_file.instances {
+                let (exclusive, shared) = instance_store.entry(*id).or_default();
+                match store_file.instances.len() {
+                    1 => *exclusive += store_file.size,
+                    _ => *shared += store_file.size,
+                }
+            }
+        }
+
+        let mut apps = Vec::new();
+        for app in &self.manifest.apps {
+            let total = app_usage.remove(&app.id).unwrap_or_default();
+            let archive = self.archive_path(&app.id);
+            let sizes = match total.backups {
+                0 => HashMap::new(),
+                _ => tokio::task::spawn_blocking(move || backup_sizes(&archive)).await??,
+            };
+            let uncompressed = sizes.values().sum::<u64>().max(1);
+            let mut instances = app
+                .backups
+                .iter()
+                .enumerate()
+                .map(|(index, backup)| {
+                    let size = sizes.get(&backup.id.to_string()).copied().unwrap_or_default();
+                    InstanceDiskUsage {
+                        id: backup.id,
+                        backup: Some(index + 1),
+                        files: 0,
+                        // the archive is compressed as a whole, so its size is split in proportion of the files
+                        backups: (total.backups as u128 * size as u128 / uncompressed as u128) as u64,
+                        ..Default::default()
+                    }
+                })
+                .collect::<Vec<_>>();
+            instances.push(InstanceDiskUsage {
+                id: app.id,
+                backup: None,
+                files: total.files,
+                ..Default::default()
+            });
+            for instance in &mut instances {
+                (instance.exclusive_store, instance.shared_store) =
+                    instance_store.get(&instance.id).copied().unwrap_or_default();
+            }
+            apps.push(AppDiskUsage {
+                id: app.id,
+                name: app.name.clone(),
+                total,
+                instances,
+            });
+        }
+
+        let cache = self.load_cache().await?;
+        let mut unreferenced = 0;
+        for file in self.load_store_files(&cache).await? {
+            if !store.contains_key(&file) {
+                unreferenced += file_size(&self.cwd.join("home/.yarn").join(file)).await?;
+            }
+        }
+        let environment = EnvironmentUsage {
+            cache: fs::dir_size(self.cwd.join("home/.yarn/cache")).await?,
+            releases: fs::dir_size(self.cwd.join("home/.yarn/releases")).await?,
+            unreferenced,
+            apps: fs::dir_size(self.cwd.join("apps")).await?,
+            backups: fs::dir_size(self.cwd.join("baka")).await?,
+            tmp: fs::dir_size(self.cwd.join("tmp")).await?,
+            logs: fs::dir_size(self.cwd.join("logs")).await?,
+            total: fs::dir_size(&self.cwd).await?,
+        };
+        Ok(DiskUsage { apps, environment })
+    }
+}
+
+/// Returns the size of a file, or 0 if it does not exist.
+async fn file_size(path: &Path) -> Result<u64> {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(error) => Err(error).with_context(|| format!("Failed to read metadata: {}", path.display())),
+    }
+}
@@ -1,16 +1,23 @@
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::sync::LazyLock;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sha2::{Digest, Sha512};
 
+mod merge;
+mod package_manager;
 mod rc;
 
+pub use merge::*;
+pub use package_manager::*;
 pub use rc::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::Cache;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct YarnLock {
     #[serde(rename = "__metadata")]
     pub metadata: YarnLockMetadata,
@@ -18,21 +25,57 @@ pub struct YarnLock {
     pub packages: HashMap<String, YarnLockEntry>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Lockfile versions supported by Cirno: 6 is written by Yarn 3, 7 by the release candidates of Yarn 4, and 8 by Yarn 4.
+pub const SUPPORTED_LOCKFILE_VERSIONS: RangeInclusive<u32> = 6..=8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct YarnLockMetadata {
     pub version: u32,
+    /// Key of the cache format used by the lockfile. Yarn 3 writes it as a number (eg. `8`), while Yarn 4 also encodes
+    /// the compression level (eg. `10c0`).
+    #[serde(deserialize_with = "deserialize_cache_key")]
     pub cache_key: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn deserialize_cache_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CacheKey {
+        String(String),
+        Number(u64),
+    }
+
+    Ok(match CacheKey::deserialize(deserializer)? {
+        CacheKey::String(value) => value,
+        CacheKey::Number(value) => value.to_string(),
+    })
+}
+
+impl YarnLockMetadata {
+    pub fn check_version(&self) -> Result<()> {
+        if !SUPPORTED_LOCKFILE_VERSIONS.contains(&self.version) {
+            bail!(
+                "Unsupported yarn.lock version: {} (supported: {} to {}).",
+                self.version,
+                SUPPORTED_LOCKFILE_VERSIONS.start(),
+                SUPPORTED_LOCKFILE_VERSIONS.end()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct YarnLockEntry {
     pub version: String,
     pub resolution: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dependencies: Option<HashMap<String, String>>,
-    pub checksum: String,
+    /// Workspaces and other soft links have no checksum.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
     pub language_name: String,
     pub link_type: LinkType,
 }
@@ -145,8 +188,41 @@ impl Locator {
         }
     }
 
+    /// Returns a string from a locator, formatted as a slug (eg. `@types-lodash-npm-1.0.0-abcdef1234`).
     pub fn slugify(&self) -> String {
-        todo!()
+        let range = Range::parse(&self.reference);
+        let human_protocol = range
+            .protocol
+            .map_or("exotic", |protocol| protocol.trim_end_matches(':'));
+        let human_reference = match valid_semver(range.selector) {
+            Some(version) => format!("{}-{}", human_protocol, version),
+            None => human_protocol.to_string(),
+        };
+
+        // 10 hex characters means that 47 different entries have 10^-9 chances of
+        // causing a hash collision. Since this hash is joined with the package name
+        // (making it highly unlikely you'll have more than a handful of instances
+        // of any single package), this should provide a good enough guard in most
+        // cases.
+        //
+        // Also note that eCryptfs eats some bytes, so the theoretical maximum for a
+        // file size is around 140 bytes (but we don't need as much, as explained).
+        const HASH_TRUNCATE: usize = 10;
+
+        format!(
+            "{}-{}-{}",
+            self.ident.slugify(),
+            human_reference,
+            &self.locator_hash[..HASH_TRUNCATE]
+        )
+    }
+
+    /// Returns the ident as you'd see it in the `dependencies` field (eg. `@types/node`).
+    pub fn stringify_ident(&self) -> String {
+        match &self.ident.scope {
+            Some(scope) => format!("@{}/{}", scope, self.ident.name),
+            None => self.ident.name.clone(),
+        }
     }
 
     pub fn try_parse(string: &str, strict: bool) -> Option<Self> {
@@ -167,15 +243,107 @@ impl Locator {
     }
 }
 
+/// Constituents of a range. Ranges typically follow these forms, with both
+/// `protocol` and `bindings` being optionals:
+///
+/// ```text
+/// <protocol>:<selector>::<bindings>
+/// <protocol>:<source>#<selector>::<bindings>
+/// ```
+///
+/// The selector is intended to "refine" the source, and is required. The source
+/// itself is optional (for instance we don't need it for npm packages, but we
+/// do for git dependencies).
+///
+/// Unlike Yarn, the components are not URI-decoded.
+pub struct Range<'a> {
+    pub protocol: Option<&'a str>,
+    pub source: Option<&'a str>,
+    pub selector: &'a str,
+    pub bindings: Option<&'a str>,
+}
+
+impl<'a> Range<'a> {
+    pub fn parse(range: &'a str) -> Self {
+        let (protocol, rest) = match range.find([':', '#']) {
+            Some(index) if range.as_bytes()[index] == b':' => (Some(&range[..=index]), &range[index + 1..]),
+            _ => (None, range),
+        };
+        let (body, bindings) = match rest.find("::") {
+            Some(index) => (&rest[..index], Some(&rest[index + 2..])),
+            None => (rest, None),
+        };
+        let (source, selector) = match body.split_once('#') {
+            Some((source, selector)) => (Some(source), selector),
+            None => (None, body),
+        };
+        Self {
+            protocol,
+            source,
+            selector,
+            bindings,
+        }
+    }
+}
+
+/// Equivalent of `semver.valid` from node-semver: returns the normalized
+/// version (without build metadata) if `version` is a valid semver string.
+fn valid_semver(version: &str) -> Option<String> {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let mut version = semver::Version::parse(version).ok()?;
+    version.build = semver::BuildMetadata::EMPTY;
+    Some(version.to_string())
+}
+
 impl YarnLock {
-    pub fn get_cache_files(&self) -> Result<Vec<String>> {
+    /// Returns the locators of all packages that are stored in the cache, i.e.
+    /// every package except the workspaces.
+    pub fn get_cache_entries(&self) -> Result<Vec<(Locator, &YarnLockEntry)>> {
         self.packages.values().try_fold(Vec::new(), |mut acc, value| {
             let locator = Locator::try_parse(&value.resolution, true)
                 .with_context(|| format!("Failed to parse resolution: {}", value.resolution))?;
             if !locator.reference.starts_with("workspace:") {
-                acc.push(locator.slugify());
+                acc.push((locator, value));
             }
             Ok(acc)
         })
     }
+
+    pub fn get_cache_files(&self) -> Result<Vec<String>> {
+        Ok(self
+            .get_cache_entries()?
+            .into_iter()
+            .map(|(locator, _)| locator.slugify())
+            .collect())
+    }
+
+    /// Returns the keys under which the cache may store the file of an entry, in order of preference: the cache key
+    /// of the lockfile, as named by the global cache, then the first 10 characters of the checksum, as named by a
+    /// local cache. The checksums of Yarn 4 are prefixed with their cache key (eg. `10c0/<hex>`), unlike those of
+    /// Yarn 3.
+    pub fn cache_keys(&self, entry: &YarnLockEntry) -> Vec<String> {
+        let mut keys = vec![self.metadata.cache_key.clone()];
+        if let Some(checksum) = &entry.checksum {
+            let hash = checksum.rsplit('/').next().unwrap_or(checksum);
+            if let Some(prefix) = hash.get(..10) {
+                keys.push(prefix.to_string());
+            }
+        }
+        keys
+    }
+
+    /// Returns the slug of every package stored in the cache, with the name of its file in `cache` if any, see
+    /// [`cache_keys`](Self::cache_keys).
+    pub fn find_cache_files<'a>(&self, cache: &'a Cache) -> Result<Vec<(String, Option<&'a String>)>> {
+        Ok(self
+            .get_cache_entries()?
+            .into_iter()
+            .map(|(locator, entry)| {
+                let slug = locator.slugify();
+                let name = self.cache_keys(entry).iter().find_map(|key| cache.get(key)?.get(&slug));
+                (slug, name)
+            })
+            .collect())
+    }
 }
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{Misspelling, YarnRc};
+
+/// Layer which supplied a setting of a [`ResolvedYarnRc`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "layer", content = "name", rename_all = "kebab-case")]
+pub enum YarnRcSource {
+    /// `home/.yarnrc.yml` of the environment.
+    Global,
+    /// `.yarnrc.yml` of the application.
+    App,
+    /// A `YARN_*` variable of the application environment.
+    Env(String),
+    /// Enforced by Cirno, either through a `YARN_*` variable or derived from other settings.
+    Cirno,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedSetting {
+    pub value: Value,
+    pub source: YarnRcSource,
+    /// Layers whose value was overridden, from the lowest to the highest.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<YarnRcSource>,
+    /// Whether the value comes from a secret variable.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+/// Effective Yarn configuration of an application, with the layer which supplied each setting.
+///
+/// Layers are applied from the lowest to the highest priority, and a layer replaces the whole value of a setting, as
+/// Yarn does for the settings of its rc files.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ResolvedYarnRc {
+    pub settings: BTreeMap<String, ResolvedSetting>,
+    /// Misspelled settings found in the rc files, see [`YarnRc::validate`].
+    #[serde(skip)]
+    pub misspellings: Vec<(YarnRcSource, Misspelling)>,
+}
+
+/// Converts a `YARN_*` variable name to the name of the setting, eg. `YARN_CACHE_FOLDER` to `cacheFolder`.
+pub fn env_setting_name(name: &str) -> Option<String> {
+    let name = name.strip_prefix("YARN_")?;
+    let mut words = name.split('_').filter(|word| !word.is_empty());
+    let mut result = words.next()?.to_lowercase();
+    for word in words {
+        let word = word.to_lowercase();
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            result.push(first.to_ascii_uppercase());
+            result.extend(chars);
+        }
+    }
+    Some(result)
+}
+
+/// Settings holding credentials, at the top level of a configuration or in its `npmScopes` and `npmRegistries`.
+const CREDENTIAL_SETTINGS: [&str; 2] = ["npmAuthIdent", "npmAuthToken"];
+
+/// Placeholder of masked values.
+pub const MASKED: &str = "********";
+
+/// Masks the credentials of a configuration, such as the serialized `.yarnrc.yml` of a backup, at any depth.
+pub fn mask_credentials(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                match CREDENTIAL_SETTINGS.contains(&name.as_str()) && !value.is_null() {
+                    true => *value = MASKED.into(),
+                    false => mask_credentials(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(mask_credentials),
+        _ => {}
+    }
+}
+
+/// Yarn parses variables into booleans and numbers when the setting expects them.
+fn parse_env_value(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match value.parse::<u64>() {
+            Ok(number) => Value::from(number),
+            Err(_) => Value::String(value.to_string()),
+        },
+    }
+}
+
+impl ResolvedYarnRc {
+    pub fn set(&mut self, name: impl Into<String>, value: Value, source: YarnRcSource, secret: bool) {
+        let name = name.into();
+        let overrides = match self.settings.remove(&name) {
+            Some(previous) => {
+                let mut overrides = previous.overrides;
+                overrides.push(previous.source);
+                overrides
+            }
+            None => vec![],
+        };
+        self.settings.insert(
+            name,
+            ResolvedSetting {
+                value,
+                source,
+                overrides,
+                secret,
+            },
+        );
+    }
+
+    /// Applies the settings of an rc file.
+    pub fn layer(&mut self, yarn_rc: &YarnRc, source: YarnRcSource) -> Result<()> {
+        for misspelling in yarn_rc.validate() {
+            self.misspellings.push((source.clone(), misspelling));
+        }
+        if let Value::Object(map) = serde_json::to_value(yarn_rc)? {
+            for (name, value) in map {
+                self.set(name, value, source.clone(), false);
+            }
+        }
+        Ok(())
+    }
+
+    /// Masks the settings which come from secret variables, and the credentials of the other settings, see
+    /// [`mask_credentials`].
+    pub fn mask_secrets(&mut self) {
+        for (name, setting) in &mut self.settings {
+            match setting.secret || CREDENTIAL_SETTINGS.contains(&name.as_str()) {
+                true => setting.value = MASKED.into(),
+                false => mask_credentials(&mut setting.value),
+            }
+        }
+    }
+
+    /// Applies a `YARN_*` variable. Other variables are ignored.
+    pub fn layer_env(&mut self, name: &str, value: &str, source: YarnRcSource, secret: bool) {
+        if let Some(setting) = env_setting_name(name) {
+            self.set(setting, parse_env_value(value), source, secret);
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        Value::Object(
+            self.settings
+                .iter()
+                .map(|(name, setting)| (name.clone(), setting.value.clone()))
+                .collect::<Map<_, _>>(),
+        )
+    }
+
+    pub fn to_yarn_rc(&self) -> Result<YarnRc> {
+        serde_json::from_value(self.to_value()).context("Failed to parse the effective yarn config")
+    }
+}
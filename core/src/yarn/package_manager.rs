@@ -0,0 +1,159 @@
+use std::fmt::{self, Display};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use thiserror::Error;
+
+/// `<name>@<version>[+<algorithm>.<hex>]`, where the version may carry a prerelease tag (eg. `4.0.0-rc.53`).
+static PACKAGE_MANAGER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^([\w-]+)@(\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?)(?:\+(\w+)\.([0-9a-fA-F]+))?$").unwrap()
+});
+
+#[derive(Debug, Error)]
+pub enum PackageManagerError {
+    #[error("Missing `packageManager` in package.json.")]
+    Missing,
+    #[error("Invalid package manager: {0}")]
+    Invalid(String),
+    #[error("Unsupported package manager: {0}")]
+    Unsupported(String),
+    #[error("Unsupported hash algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("Yarn release {0} is not installed.")]
+    ReleaseNotFound(String),
+    #[error("Hash mismatch for yarn {version}: expected {expected}, found {actual}.")]
+    HashMismatch {
+        version: String,
+        expected: ReleaseHash,
+        actual: ReleaseHash,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Digest of a package manager release, written by Corepack as `<algorithm>.<hex>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseHash {
+    pub algorithm: String,
+    pub digest: String,
+}
+
+impl ReleaseHash {
+    /// Hashes `content` with the given algorithm. Corepack accepts any algorithm supported by Node.js, of which the
+    /// SHA family is supported here.
+    pub fn compute(algorithm: &str, content: &[u8]) -> Result<Self, PackageManagerError> {
+        let digest = match algorithm {
+            "sha1" => hex::encode(Sha1::digest(content)),
+            "sha224" => hex::encode(Sha224::digest(content)),
+            "sha256" => hex::encode(Sha256::digest(content)),
+            "sha384" => hex::encode(Sha384::digest(content)),
+            "sha512" => hex::encode(Sha512::digest(content)),
+            _ => return Err(PackageManagerError::UnsupportedAlgorithm(algorithm.to_string())),
+        };
+        Ok(Self {
+            algorithm: algorithm.to_string(),
+            digest,
+        })
+    }
+}
+
+impl Display for ReleaseHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.algorithm, self.digest)
+    }
+}
+
+/// Parsed `packageManager` field of a `package.json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageManager {
+    pub name: String,
+    pub version: String,
+    pub hash: Option<ReleaseHash>,
+}
+
+impl FromStr for PackageManager {
+    type Err = PackageManagerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let captures = PACKAGE_MANAGER_REGEX
+            .captures(s)
+            .ok_or_else(|| PackageManagerError::Invalid(s.to_string()))?;
+        Ok(Self {
+            name: captures[1].to_string(),
+            version: captures[2].to_string(),
+            hash: captures
+                .get(3)
+                .zip(captures.get(4))
+                .map(|(algorithm, digest)| ReleaseHash {
+                    algorithm: algorithm.as_str().to_string(),
+                    digest: digest.as_str().to_lowercase(),
+                }),
+        })
+    }
+}
+
+impl PackageManager {
+    /// Parses a `packageManager` field, which must refer to Yarn.
+    pub fn yarn(value: &str) -> Result<Self, PackageManagerError> {
+        if value.is_empty() {
+            return Err(PackageManagerError::Missing);
+        }
+        let package_manager = value.parse::<Self>()?;
+        if package_manager.name != "yarn" {
+            return Err(PackageManagerError::Unsupported(package_manager.name));
+        }
+        Ok(package_manager)
+    }
+
+    /// File name of the release under `home/.yarn/releases`.
+    pub fn release_name(&self) -> String {
+        format!("{}-{}.cjs", self.name, self.version)
+    }
+
+    /// Returns the path of the release in `releases`, or fails if it is not installed. Unlike [`verify`](Self::verify),
+    /// the release is not hashed, so this is cheap enough to run before each yarn command.
+    pub async fn locate(&self, releases: &Path) -> Result<PathBuf, PackageManagerError> {
+        let path = releases.join(self.release_name());
+        match tokio::fs::try_exists(&path).await? {
+            true => Ok(path),
+            false => Err(PackageManagerError::ReleaseNotFound(self.version.clone())),
+        }
+    }
+
+    /// Checks the release in `releases` against the hash of the `packageManager` field.
+    ///
+    /// Returns the verified hash, or `None` if the field does not declare one. Releases are verified when they enter
+    /// the environment or leave it in a bundle, and by `cirno verify`, rather than on each yarn command.
+    pub async fn verify(&self, releases: &Path) -> Result<Option<ReleaseHash>, PackageManagerError> {
+        self.verify_file(&releases.join(self.release_name())).await
+    }
+
+    /// Checks a release file at any location, such as the one carried by a bundle, against the hash of the
+    /// `packageManager` field.
+    pub async fn verify_file(&self, path: &Path) -> Result<Option<ReleaseHash>, PackageManagerError> {
+        let Some(expected) = &self.hash else {
+            return Ok(None);
+        };
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Err(PackageManagerError::ReleaseNotFound(self.version.clone()));
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let actual = ReleaseHash::compute(&expected.algorithm, &content)?;
+        if &actual != expected {
+            return Err(PackageManagerError::HashMismatch {
+                version: self.version.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(Some(actual))
+    }
+}
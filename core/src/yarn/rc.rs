@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use either::Either;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 /// Yarnrc files (named this way because they must be called `.yarnrc.yml`) are the one place where you'll be able to
 /// configure Yarn's internal settings. While Yarn will automatically find them in the parent directories, they should
@@ -692,6 +693,12 @@ pub struct YarnRc {
     /// we now recommend to use [Corepack](https://nodejs.org/api/corepack.html) in most cases.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub yarn_path: Option<String>,
+
+    /// Settings which are not modeled above, such as the ones of plugins.
+    ///
+    /// They are kept as is, so that rewriting a `.yarnrc.yml` does not strip them.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl std::fmt::Debug for YarnRc {
@@ -973,10 +980,185 @@ impl std::fmt::Debug for YarnRc {
         if let Some(value) = &self.yarn_path {
             ds.field("yarn_path", value);
         }
+        if !self.extra.is_empty() {
+            ds.field("extra", &self.extra);
+        }
         ds.finish()
     }
 }
 
+/// Names of the settings modeled by [`YarnRc`].
+pub const YARN_RC_SETTINGS: [&str; 92] = [
+    "cacheFolder",
+    "cacheMigrationMode",
+    "changesetBaseRefs",
+    "changesetIgnorePatterns",
+    "checksumBehavior",
+    "cloneConcurrency",
+    "compressionLevel",
+    "constraintsPath",
+    "defaultLanguageName",
+    "defaultProtocol",
+    "defaultSemverRangePrefix",
+    "deferredVersionFolder",
+    "enableColors",
+    "enableConstraintsChecks",
+    "enableGlobalCache",
+    "enableHardenedMode",
+    "enableHyperlinks",
+    "enableImmutableCache",
+    "enableImmutableInstalls",
+    "enableInlineBuilds",
+    "enableInlineHunks",
+    "enableMessageNames",
+    "enableMirror",
+    "enableNetwork",
+    "enableOfflineMode",
+    "enableProgressBars",
+    "enableScripts",
+    "enableStrictSsl",
+    "enableTelemetry",
+    "enableTimers",
+    "enableTips",
+    "enableTransparentWorkspaces",
+    "globalFolder",
+    "httpProxy",
+    "httpRetry",
+    "httpTimeout",
+    "httpsCaFilePath",
+    "httpsCertFilePath",
+    "httpsKeyFilePath",
+    "httpsProxy",
+    "ignorePath",
+    "immutablePatterns",
+    "initScope",
+    "initFields",
+    "injectEnvironmentFiles",
+    "installStatePath",
+    "logFilters",
+    "networkConcurrency",
+    "networkSettings",
+    "nmHoistingLimits",
+    "nmSelfReferences",
+    "nmMode",
+    "nodeLinker",
+    "npmMinimalAgeGate",
+    "npmPreapprovedPackages",
+    "pnpmStoreFolder",
+    "winLinkType",
+    "npmAlwaysAuth",
+    "npmAuditRegistry",
+    "npmAuthIdent",
+    "npmAuthToken",
+    "npmPublishAccess",
+    "npmPublishProvenance",
+    "npmAuditExcludePackages",
+    "npmAuditIgnoreAdvisories",
+    "npmPublishRegistry",
+    "npmRegistries",
+    "npmRegistryServer",
+    "npmScopes",
+    "packageExtensions",
+    "patchFolder",
+    "pnpEnableEsmLoader",
+    "pnpEnableInlining",
+    "pnpFallbackMode",
+    "pnpIgnorePatterns",
+    "pnpMode",
+    "pnpShebang",
+    "pnpUnpluggedFolder",
+    "preferDeferredVersions",
+    "preferInteractive",
+    "preferReuse",
+    "preferTruncatedLines",
+    "progressBarStyle",
+    "supportedArchitectures",
+    "taskPoolConcurrency",
+    "taskPoolMode",
+    "telemetryInterval",
+    "telemetryUserId",
+    "tsEnableAutoTypes",
+    "unsafeHttpWhitelist",
+    "virtualFolder",
+    "yarnPath",
+];
+
+/// Setting which is not modeled by [`YarnRc`], but close to one which is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub name: String,
+    pub suggestion: &'static str,
+}
+
+impl std::fmt::Display for Misspelling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown setting `{}`, did you mean `{}`?",
+            self.name, self.suggestion
+        )
+    }
+}
+
+/// Levenshtein distance between two ASCII-insensitive strings.
+fn get_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.to_ascii_lowercase().into_bytes(), b.to_ascii_lowercase().into_bytes());
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, x) in a.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let next = (prev + usize::from(x != y)).min(row[j] + 1).min(row[j + 1] + 1);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+impl YarnRc {
+    /// Checks the unknown settings for misspellings of known ones.
+    ///
+    /// Plugins may define their own settings, so unknown settings are only reported when they are close enough to a
+    /// known one (eg. `enableGlobalCahce` or `NodeLinker`).
+    pub fn validate(&self) -> Vec<Misspelling> {
+        self.extra
+            .keys()
+            .filter_map(|name| {
+                let (distance, suggestion) = YARN_RC_SETTINGS
+                    .iter()
+                    .map(|setting| (get_distance(name, setting), *setting))
+                    .min()?;
+                (distance <= 2.min(name.len() / 4)).then(|| Misspelling {
+                    name: name.clone(),
+                    suggestion,
+                })
+            })
+            .collect()
+    }
+
+    /// Serializes the config, keeping the settings of `original` (the previous content of the file) in their order.
+    /// New settings are appended.
+    ///
+    /// Comments are not preserved.
+    pub fn to_yaml(&self, original: Option<&str>) -> Result<String, serde_yaml_ng::Error> {
+        let serde_yaml_ng::Value::Mapping(mut mapping) = serde_yaml_ng::to_value(self)? else {
+            unreachable!("YarnRc is serialized as a mapping");
+        };
+        let order = original
+            .and_then(|original| serde_yaml_ng::from_str::<serde_yaml_ng::Mapping>(original).ok())
+            .unwrap_or_default();
+        let mut ordered = serde_yaml_ng::Mapping::new();
+        for key in order.keys() {
+            if let Some(value) = mapping.shift_remove(key) {
+                ordered.insert(key.clone(), value);
+            }
+        }
+        ordered.extend(mapping);
+        serde_yaml_ng::to_string(&ordered)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum CacheMigrationMode {
@@ -1096,21 +1278,31 @@ pub enum LogLevel {
     Discard,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct NetworkSetting {
     /// See [`enable_network`](YarnRc::enable_network).
-    pub enable_network: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_network: Option<bool>,
     /// See [`http_proxy`](YarnRc::http_proxy).
-    pub http_proxy: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<String>,
     /// See [`https_ca_file_path`](YarnRc::https_ca_file_path).
-    pub https_ca_file_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_ca_file_path: Option<String>,
     /// See [`https_cert_file_path`](YarnRc::https_cert_file_path).
-    pub https_cert_file_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_cert_file_path: Option<String>,
     /// See [`https_key_file_path`](YarnRc::https_key_file_path).
-    pub https_key_file_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_key_file_path: Option<String>,
     /// See [`https_proxy`](YarnRc::https_proxy).
-    pub https_proxy: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_proxy: Option<String>,
+    /// Fields which are not modeled above, kept as is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -1137,6 +1329,37 @@ pub enum NodeLinker {
     Pnpm,
 }
 
+impl NodeLinker {
+    /// Whether the linker installs packages into `node_modules` folders. These folders are derived from the cache and
+    /// the lockfile, and can always be rebuilt by `yarn install`.
+    pub fn uses_node_modules(&self) -> bool {
+        !matches!(self, Self::Pnp)
+    }
+}
+
+impl std::str::FromStr for NodeLinker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "node-modules" => Ok(Self::NodeModules),
+            "pnp" => Ok(Self::Pnp),
+            "pnpm" => Ok(Self::Pnpm),
+            _ => Err(format!("invalid node linker: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for NodeLinker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NodeModules => "node-modules",
+            Self::Pnp => "pnp",
+            Self::Pnpm => "pnpm",
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum NpmPublishAccess {
@@ -1144,44 +1367,72 @@ pub enum NpmPublishAccess {
     Restricted,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct NpmRegistry {
     /// See [`npm_always_auth`](YarnRc::npm_always_auth).
-    pub npm_always_auth: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_always_auth: Option<bool>,
     /// See [`npm_auth_ident`](YarnRc::npm_auth_ident).
-    pub npm_auth_ident: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_auth_ident: Option<String>,
     /// See [`npm_auth_token`](YarnRc::npm_auth_token).
-    pub npm_auth_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_auth_token: Option<String>,
+    /// Fields which are not modeled above, kept as is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct NpmScope {
     /// See [`npm_publish_registry`](YarnRc::npm_publish_registry).
-    pub npm_publish_registry: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_publish_registry: Option<String>,
     /// See [`npm_registry_server`](YarnRc::npm_registry_server).
-    pub npm_registry_server: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_registry_server: Option<String>,
     /// See [`npm_always_auth`](YarnRc::npm_always_auth).
-    pub npm_always_auth: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_always_auth: Option<bool>,
     /// See [`npm_auth_ident`](YarnRc::npm_auth_ident).
-    pub npm_auth_ident: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_auth_ident: Option<String>,
     /// See [`npm_auth_token`](YarnRc::npm_auth_token).
-    pub npm_auth_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm_auth_token: Option<String>,
+    /// Fields which are not modeled above, kept as is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct PackageExtension {
-    dependencies: HashMap<String, String>,
-    peer_dependencies: HashMap<String, String>,
-    peer_dependencies_meta: HashMap<String, PeerDependencyMeta>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub dependencies: HashMap<String, String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub peer_dependencies: HashMap<String, String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub peer_dependencies_meta: HashMap<String, PeerDependencyMeta>,
+    /// Fields which are not modeled above, kept as is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct PeerDependencyMeta {
-    optional: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optional: Option<bool>,
+    /// Fields which are not modeled above, kept as is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -1244,17 +1495,24 @@ impl<'de> Deserialize<'de> for SemverRangePrefix {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct SupportedArchitectures {
     /// List of operating systems to cover.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub os: Vec<String>,
     /// List of CPU architectures to cover.
     ///
     /// See <https://nodejs.org/docs/latest/api/process.html#processarch> for the architectures supported by Node.js.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub cpu: Vec<String>,
     /// The list of standard C libraries to cover.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub libc: Vec<String>,
+    /// Fields which are not modeled above, kept as is.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -0,0 +1,268 @@
+//! Extraction of crafted bundle files, which must never write outside of the destination.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use cirno_core::bundle::BundleFormat;
+use cirno_core::report::{Phase, PhaseProgress};
+use tar::{EntryType, Header};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+use crate::common::Scratch;
+
+mod common;
+
+enum Entry<'a> {
+    Dir(&'a str),
+    File(&'a str, &'a [u8]),
+    Symlink(&'a str, &'a str),
+}
+
+/// Writes a tarball with raw entry names, bypassing the checks of [`tar::Builder`].
+fn write_tar(path: &Path, entries: &[Entry]) {
+    let mut tar = tar::Builder::new(File::create(path).unwrap());
+    for entry in entries {
+        let mut header = Header::new_gnu();
+        let (name, data, link) = match entry {
+            Entry::Dir(name) => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(0o755);
+                (*name, &[][..], None)
+            }
+            Entry::File(name, data) => {
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(0o644);
+                (*name, *data, None)
+            }
+            Entry::Symlink(name, target) => {
+                header.set_entry_type(EntryType::Symlink);
+                header.set_mode(0o777);
+                (*name, &[][..], Some(*target))
+            }
+        };
+        let gnu = header.as_gnu_mut().unwrap();
+        gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+        if let Some(link) = link {
+            gnu.linkname[..link.len()].copy_from_slice(link.as_bytes());
+        }
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        tar.append(&header, data).unwrap();
+    }
+    tar.into_inner().unwrap().flush().unwrap();
+}
+
+fn write_zip(path: &Path, entries: &[Entry]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    let options = SimpleFileOptions::default();
+    for entry in entries {
+        match entry {
+            Entry::Dir(name) => zip.add_directory(*name, options).unwrap(),
+            Entry::File(name, data) => {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(data).unwrap();
+            }
+            Entry::Symlink(name, target) => zip.add_symlink(*name, *target, options).unwrap(),
+        }
+    }
+    zip.finish().unwrap();
+}
+
+/// Extracts the entries as both a tarball and a zip archive into `root/bundle`, and returns the error messages.
+fn extract(root: &Path, entries: &[Entry]) -> Vec<Result<(), String>> {
+    let progress = PhaseProgress::silent(Phase::Unpack);
+    let tar = root.join("bundle.tar.zst");
+    let plain = root.join("bundle.tar");
+    write_tar(&plain, entries);
+    zstd::stream::copy_encode(File::open(&plain).unwrap(), File::create(&tar).unwrap(), 0).unwrap();
+    let zip = root.join("bundle.zip");
+    write_zip(&zip, entries);
+    [(BundleFormat::TarZst, tar), (BundleFormat::Zip, zip)]
+        .into_iter()
+        .map(|(format, path)| {
+            let dest = root.join(format!("bundle-{}", format));
+            let result = format
+                .extract(&path, &dest, &progress)
+                .map_err(|error| format!("{:#}", error));
+            assert!(!root.join("evil").exists(), "{} wrote outside of the bundle", format);
+            result
+        })
+        .collect()
+}
+
+fn assert_rejected(entries: &[Entry], message: &str) {
+    let scratch = Scratch::new();
+    for result in extract(&scratch.0, entries) {
+        let error = result.expect_err("malicious bundle was extracted");
+        assert!(error.contains(message), "unexpected error: {}", error);
+    }
+}
+
+#[test]
+fn rejects_parent_paths() {
+    assert_rejected(&[Entry::File("../evil", b"evil")], "Unsafe path in bundle: ../evil");
+    assert_rejected(&[Entry::File("app/../../evil", b"evil")], "Unsafe path in bundle");
+}
+
+#[test]
+fn rejects_backslash_paths() {
+    assert_rejected(&[Entry::File("..\\evil", b"evil")], "Unsafe path in bundle: ../evil");
+    assert_rejected(&[Entry::File("app\\..\\..\\evil", b"evil")], "Unsafe path in bundle");
+}
+
+#[test]
+fn rejects_absolute_paths() {
+    assert_rejected(&[Entry::File("/tmp/evil", b"evil")], "Unsafe path in bundle: /tmp/evil");
+    assert_rejected(&[Entry::File("C:\\evil", b"evil")], "Unsafe path in bundle: C:/evil");
+}
+
+#[test]
+fn rejects_escaping_symlinks() {
+    assert_rejected(
+        &[Entry::Symlink("link", "..")],
+        "Symlink link points outside the bundle: ..",
+    );
+    assert_rejected(&[Entry::Symlink("a/link", "../../evil")], "points outside the bundle");
+    assert_rejected(&[Entry::Symlink("link", "/etc/passwd")], "points outside the bundle");
+    assert_rejected(&[Entry::Symlink("link", "..\\..\\evil")], "points outside the bundle");
+}
+
+#[test]
+fn rejects_chained_symlinks() {
+    // each target stays inside the bundle lexically, but `link` resolves through `a/b/up` to the parent of the bundle
+    assert_rejected(
+        &[
+            Entry::Dir("a/b"),
+            Entry::Symlink("a/b/up", "../.."),
+            Entry::Symlink("link", "a/b/up/../evil"),
+        ],
+        "Symlink link points outside the bundle",
+    );
+}
+
+#[test]
+fn rejects_writes_through_symlinks() {
+    // the symlink is valid, but a later entry must not be written through it
+    assert_rejected(
+        &[
+            Entry::Dir("dir"),
+            Entry::Symlink("link", "dir"),
+            Entry::File("link/file", b"data"),
+        ],
+        "Entry link/file is not inside a directory.",
+    );
+}
+
+#[test]
+fn rejects_hard_links() {
+    let scratch = Scratch::new();
+    let path = scratch.0.join("bundle.tar");
+    let mut tar = tar::Builder::new(File::create(&path).unwrap());
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Link);
+    header.set_size(0);
+    tar.append_link(&mut header, "link", "/etc/passwd").unwrap();
+    tar.finish().unwrap();
+    drop(tar);
+    let compressed = scratch.0.join("bundle.tar.zst");
+    zstd::stream::copy_encode(File::open(&path).unwrap(), File::create(&compressed).unwrap(), 0).unwrap();
+    let error = BundleFormat::TarZst
+        .extract(
+            &compressed,
+            &scratch.0.join("bundle"),
+            &PhaseProgress::silent(Phase::Unpack),
+        )
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("Unsupported entry in bundle: link"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn extracts_safe_entries() {
+    let scratch = Scratch::new();
+    let entries = [
+        Entry::Dir("./empty"),
+        Entry::File("app\\package.json", b"{}"),
+        Entry::Symlink("app/node_modules/self", "../.."),
+        Entry::Symlink("app/link", "./package.json"),
+    ];
+    for result in extract(&scratch.0, &entries) {
+        result.unwrap();
+    }
+    for format in [BundleFormat::TarZst, BundleFormat::Zip] {
+        let dest = scratch.0.join(format!("bundle-{}", format));
+        assert!(dest.join("empty").is_dir());
+        assert_eq!(std::fs::read(dest.join("app/link")).unwrap(), b"{}");
+        assert!(dest.join("app/node_modules/self/app/package.json").is_file());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn round_trips_metadata() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let scratch = Scratch::new();
+    let src = scratch.0.join("src");
+    // zip archives store times with a precision of two seconds
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    std::fs::create_dir_all(src.join("bin")).unwrap();
+    std::fs::create_dir_all(src.join("empty")).unwrap();
+    std::fs::write(src.join("bin/run"), "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(src.join("bin/run"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    File::options()
+        .write(true)
+        .open(src.join("bin/run"))
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    std::fs::write(src.join("data"), "data").unwrap();
+    std::fs::set_permissions(src.join("data"), std::fs::Permissions::from_mode(0o600)).unwrap();
+    symlink("bin/run", src.join("run")).unwrap();
+    let progress = PhaseProgress::silent(Phase::Unpack);
+
+    for format in BundleFormat::ALL {
+        let path = scratch.0.join(format!("bundle.{}", format));
+        format.write(&src, &path, &progress).unwrap();
+        let dest = scratch.0.join(format!("dest-{}", format));
+        format.extract(&path, &dest, &progress).unwrap();
+
+        let mode = |path: &str| std::fs::metadata(dest.join(path)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("bin/run"), 0o755, "{}", format);
+        assert_eq!(mode("data"), 0o600, "{}", format);
+        assert!(dest.join("empty").is_dir(), "{}", format);
+        assert_eq!(
+            std::fs::read_link(dest.join("run")).unwrap(),
+            Path::new("bin/run"),
+            "{}",
+            format
+        );
+        let modified = std::fs::metadata(dest.join("bin/run")).unwrap().modified().unwrap();
+        assert_eq!(modified, mtime, "{}", format);
+    }
+}
+
+#[test]
+fn rejects_escaping_symlinks_on_write() {
+    let scratch = Scratch::new();
+    let src = scratch.0.join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("../outside", src.join("link")).unwrap();
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file("..\\outside", src.join("link")).unwrap();
+    for format in BundleFormat::ALL {
+        let path = scratch.0.join(format!("bundle.{}", format));
+        let error = format
+            .write(&src, &path, &PhaseProgress::silent(Phase::Pack))
+            .unwrap_err();
+        assert!(error.to_string().contains("points outside the bundle"), "{}", error);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,241 @@
+//! Lookup of the cache files of a lockfile and of a bundle, and migration of cache entries between cache keys.
+
+use cirno_core::Cache;
+use cirno_core::baka::BackupOptions;
+use cirno_core::bundle::BUNDLE_CACHE_REGEX;
+use cirno_core::cache::MigrateCacheOptions;
+use cirno_core::yarn::{YarnLock, YarnLockMetadata};
+use serde::Deserialize;
+use sha2::{Digest, Sha512};
+
+use crate::common::{Scratch, add_app, env, fill_cache, fixture, fixture_meta};
+
+mod common;
+
+#[test]
+fn lockfile_versions() {
+    // the cache key of Yarn 3 is a number in the lockfile
+    let metadata: YarnLockMetadata = serde_yaml_ng::from_str("version: 6\ncacheKey: 8\n").unwrap();
+    assert_eq!(metadata.cache_key, "8");
+    assert!(metadata.check_version().is_ok());
+    for (version, supported) in [(4, false), (6, true), (7, true), (8, true), (9, false)] {
+        let metadata: YarnLockMetadata =
+            serde_yaml_ng::from_str(&format!("version: {}\ncacheKey: 10c0\n", version)).unwrap();
+        assert_eq!(metadata.check_version().is_ok(), supported, "version {}", version);
+    }
+}
+
+#[test]
+fn workspaces_are_not_cached() {
+    let meta = fixture_meta("dep-1");
+    let files = meta.yarn_lock.get_cache_files().unwrap();
+    assert!(!files.is_empty());
+    assert!(files.iter().all(|slug| !slug.starts_with("@fixture-dep-1")));
+    let workspace = meta
+        .yarn_lock
+        .packages
+        .values()
+        .find(|entry| entry.resolution.contains("@workspace:"));
+    assert!(workspace.unwrap().checksum.is_none());
+}
+
+fn lockfile(version: u32, cache_key: &str, checksum: &str) -> YarnLock {
+    let content = format!(
+        "__metadata:\n  version: {}\n  cacheKey: {}\n\n\"a@npm:1.0.0\":\n  version: 1.0.0\n  resolution: \"a@npm:1.0.0\"\n  checksum: {}\n  languageName: node\n  linkType: hard\n",
+        version, cache_key, checksum
+    );
+    serde_yaml_ng::from_str(&content).unwrap()
+}
+
+#[test]
+fn cache_keys() {
+    // the checksums of Yarn 4 are prefixed with the cache key, unlike those of Yarn 3
+    let yarn4 = lockfile(8, "10c0", "10c0/0123456789abcdef");
+    let entry = yarn4.packages.values().next().unwrap();
+    assert_eq!(yarn4.cache_keys(entry), ["10c0", "0123456789"]);
+
+    let yarn3 = lockfile(6, "8", "fedcba9876543210");
+    let entry = yarn3.packages.values().next().unwrap();
+    assert_eq!(yarn3.cache_keys(entry), ["8", "fedcba9876"]);
+}
+
+#[test]
+fn find_cache_files_by_checksum() {
+    let meta = fixture_meta("dep-1");
+    let (locator, entry) = meta
+        .yarn_lock
+        .get_cache_entries()
+        .unwrap()
+        .into_iter()
+        .find(|(locator, _)| locator.stringify_ident() == "tslib")
+        .unwrap();
+    let slug = locator.slugify();
+    let prefix = meta.yarn_lock.cache_keys(entry)[1].clone();
+    let mut cache = Cache::new();
+    cache
+        .entry(prefix.clone())
+        .or_default()
+        .insert(slug.clone(), format!("{}-{}.zip", slug, prefix));
+
+    let files = meta.yarn_lock.find_cache_files(&cache).unwrap();
+    assert_eq!(files.len(), meta.yarn_lock.get_cache_files().unwrap().len());
+    for (file, name) in files {
+        match file == slug {
+            true => assert_eq!(name.unwrap(), &format!("{}-{}.zip", slug, prefix)),
+            false => assert!(name.is_none()),
+        }
+    }
+
+    // the cache key of the lockfile is preferred
+    cache
+        .entry("10c0".to_string())
+        .or_default()
+        .insert(slug.clone(), format!("{}-10c0.zip", slug));
+    let files = meta.yarn_lock.find_cache_files(&cache).unwrap();
+    let (_, name) = files.into_iter().find(|(file, _)| file == &slug).unwrap();
+    assert_eq!(name.unwrap(), &format!("{}-10c0.zip", slug));
+}
+
+#[derive(Deserialize)]
+struct BundleCacheName {
+    name: String,
+    slug: Option<String>,
+    hash: Option<String>,
+    key: Option<String>,
+}
+
+#[test]
+fn bundle_cache_names() {
+    // the test vector is shared with the TypeScript import
+    let content = std::fs::read_to_string(fixture("bundle-cache-names.json")).unwrap();
+    let names: Vec<BundleCacheName> = serde_json::from_str(&content).unwrap();
+    for case in names {
+        let captures = BUNDLE_CACHE_REGEX.captures(&case.name);
+        let parts = captures.map(|captures| {
+            (
+                captures[1].to_string(),
+                captures[2].to_string(),
+                captures[3].to_string(),
+            )
+        });
+        let expected = case
+            .slug
+            .zip(case.hash)
+            .zip(case.key)
+            .map(|((slug, hash), key)| (slug, hash, key));
+        assert_eq!(parts, expected, "{}", case.name);
+    }
+}
+
+#[tokio::test]
+async fn migrate_renames_entries() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let meta = fixture_meta("dep-1");
+    let sources = fill_cache(&cirno, &meta, "8");
+
+    let report = cirno.migrate_cache(&id, &MigrateCacheOptions::default()).await.unwrap();
+    assert_eq!(report.cache_key, "10c0");
+    assert_eq!(report.renamed, sources.len());
+    assert_eq!(report.copied, 0);
+    assert!(report.missing.is_empty());
+    assert!(report.lockfile_updated);
+    assert!(sources.iter().all(|path| !path.exists()));
+
+    // the checksums of the lockfile match the migrated files
+    let lockfile = std::fs::read_to_string(cirno.cwd.join("apps").join(id.to_string()).join("yarn.lock")).unwrap();
+    let yarn_lock: YarnLock = serde_yaml_ng::from_str(&lockfile).unwrap();
+    assert_eq!(yarn_lock.metadata.cache_key, "10c0");
+    for (locator, entry) in yarn_lock.get_cache_entries().unwrap() {
+        let slug = locator.slugify();
+        let content = std::fs::read(cirno.cwd.join(format!("home/.yarn/cache/{}-10c0.zip", slug))).unwrap();
+        let expected = format!("10c0/{}", hex::encode(Sha512::digest(content)));
+        assert_eq!(entry.checksum.as_ref().unwrap(), &expected, "{}", slug);
+    }
+
+    // a second migration has nothing to do
+    let report = cirno.migrate_cache(&id, &MigrateCacheOptions::default()).await.unwrap();
+    assert_eq!(report.renamed + report.copied, 0);
+    assert!(!report.lockfile_updated);
+}
+
+#[tokio::test]
+async fn migrate_reverts_on_failure() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let lockfile_path = cirno.cwd.join("apps").join(id.to_string()).join("yarn.lock");
+    let lockfile = std::fs::read_to_string(&lockfile_path).unwrap();
+    let meta = fixture_meta("dep-1");
+    let sources = fill_cache(&cirno, &meta, "8");
+    // an entry which cannot be checksummed once it is moved
+    let broken = sources
+        .iter()
+        .find(|path| path.to_string_lossy().contains("tslib"))
+        .unwrap();
+    std::fs::remove_file(broken).unwrap();
+    std::fs::create_dir(broken).unwrap();
+
+    assert!(cirno.migrate_cache(&id, &MigrateCacheOptions::default()).await.is_err());
+    assert!(sources.iter().all(|path| path.exists()));
+    assert!(broken.is_dir());
+    let cache = cirno.load_cache().await.unwrap();
+    assert_eq!(cache.keys().collect::<Vec<_>>(), ["8"]);
+    assert_eq!(std::fs::read_to_string(&lockfile_path).unwrap(), lockfile);
+}
+
+#[tokio::test]
+async fn migrate_copies_shared_entries() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let other = add_app(&mut cirno, "dep-1", "other").await;
+    // the other application still uses the cache key of the files
+    let lockfile_path = cirno.cwd.join("apps").join(other.to_string()).join("yarn.lock");
+    let lockfile = std::fs::read_to_string(&lockfile_path).unwrap();
+    std::fs::write(&lockfile_path, lockfile.replace("cacheKey: 10c0", "cacheKey: 8")).unwrap();
+    let meta = fixture_meta("dep-1");
+    let sources = fill_cache(&cirno, &meta, "8");
+
+    let report = cirno.migrate_cache(&id, &MigrateCacheOptions::default()).await.unwrap();
+    assert_eq!(report.renamed, 0);
+    assert_eq!(report.copied, sources.len());
+    assert!(sources.iter().all(|path| path.exists()));
+}
+
+#[tokio::test]
+async fn migrate_to_cache_key() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let meta = fixture_meta("dep-1");
+    let sources = fill_cache(&cirno, &meta, "10c0");
+
+    let options = MigrateCacheOptions {
+        from: None,
+        to: Some("10c1".to_string()),
+    };
+    let report = cirno.migrate_cache(&id, &options).await.unwrap();
+    assert_eq!(report.cache_key, "10c1");
+    assert_eq!(report.renamed, sources.len());
+    let lockfile = std::fs::read_to_string(cirno.cwd.join("apps").join(id.to_string()).join("yarn.lock")).unwrap();
+    let yarn_lock: YarnLock = serde_yaml_ng::from_str(&lockfile).unwrap();
+    assert_eq!(yarn_lock.metadata.cache_key, "10c1");
+    for (_, entry) in yarn_lock.get_cache_entries().unwrap() {
+        assert!(entry.checksum.as_ref().unwrap().starts_with("10c1/"));
+    }
+}
+
+#[tokio::test]
+async fn migrate_rejects_backups() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let backup = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+    let error = cirno
+        .migrate_cache(&backup, &MigrateCacheOptions::default())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Restore it first"));
+}
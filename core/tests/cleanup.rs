@@ -0,0 +1,52 @@
+//! Cleanup of the temporary directories left by interrupted operations.
+
+use std::fs::File;
+
+use cirno_core::Cirno;
+use uuid::Uuid;
+
+use crate::common::{Scratch, env};
+
+mod common;
+
+#[tokio::test]
+async fn sweep_own_entries() {
+    let scratch = Scratch::new();
+    let cirno = env(&scratch.0).await;
+    let tmp = cirno.cwd.join("tmp");
+    let operations = tmp.join(".cirno");
+    std::fs::create_dir_all(&operations).unwrap();
+
+    // entries of other tools and of running applications, named like ours
+    let foreign = Uuid::new_v4().to_string();
+    std::fs::create_dir(tmp.join(&foreign)).unwrap();
+    std::fs::write(tmp.join(format!("{}.baka", foreign)), "").unwrap();
+    std::fs::write(tmp.join(format!("{}.lock", foreign)), "").unwrap();
+
+    // an interrupted operation, with and without its lock
+    let crashed = Uuid::new_v4().to_string();
+    std::fs::create_dir(operations.join(&crashed)).unwrap();
+    std::fs::create_dir(operations.join(format!("{}.git", crashed))).unwrap();
+    std::fs::write(operations.join(format!("{}.lock", crashed)), "").unwrap();
+    let killed = Uuid::new_v4().to_string();
+    std::fs::create_dir(operations.join(&killed)).unwrap();
+
+    // a running operation
+    let running = Uuid::new_v4().to_string();
+    std::fs::create_dir(operations.join(&running)).unwrap();
+    let lock = File::create(operations.join(format!("{}.lock", running))).unwrap();
+    lock.lock().unwrap();
+
+    Cirno::open(&cirno.cwd)
+        .await
+        .unwrap_or_else(|_| panic!("Failed to open the environment"));
+    let mut names = std::fs::read_dir(&operations)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, [running.clone(), format!("{}.lock", running)]);
+    assert!(tmp.join(&foreign).is_dir());
+    assert!(tmp.join(format!("{}.baka", foreign)).exists());
+    assert!(tmp.join(format!("{}.lock", foreign)).exists());
+}
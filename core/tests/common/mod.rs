@@ -0,0 +1,89 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use cirno_core::yarn::NodeLinker;
+use cirno_core::{App, Cirno, Meta};
+use uuid::Uuid;
+
+/// Temporary directory removed when dropped.
+pub struct Scratch(pub PathBuf);
+
+impl Scratch {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("cirno-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Path of an application of the fixtures shared with the TypeScript tests.
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests/fixtures")
+        .join(name)
+}
+
+/// Reads the metadata of a fixture application, which has no `.yarnrc.yml`.
+pub fn fixture_meta(name: &str) -> Meta {
+    let path = fixture(name);
+    Meta {
+        package: serde_json::from_str(&std::fs::read_to_string(path.join("package.json")).unwrap()).unwrap(),
+        yarn_rc: Default::default(),
+        yarn_lock: serde_yaml_ng::from_str(&std::fs::read_to_string(path.join("yarn.lock")).unwrap()).unwrap(),
+    }
+}
+
+/// Initializes and opens an environment in `root`, whose applications use PnP.
+pub async fn env(root: &Path) -> Cirno {
+    let cwd = Cirno::init(&root.join("env"), false, NodeLinker::Pnp)
+        .await
+        .unwrap_or_else(|_| panic!("Failed to initialize the environment"));
+    Cirno::open(&cwd)
+        .await
+        .unwrap_or_else(|_| panic!("Failed to open the environment"))
+}
+
+/// Adds an application copied from a fixture, without installing it, and returns its ID.
+pub async fn add_app(cirno: &mut Cirno, fixture_name: &str, name: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    let dest = cirno.cwd.join("apps").join(id.to_string());
+    std::fs::create_dir_all(&dest).unwrap();
+    for file in ["package.json", "yarn.lock"] {
+        std::fs::copy(fixture(fixture_name).join(file), dest.join(file)).unwrap();
+    }
+    std::fs::write(dest.join(".yarnrc.yml"), "enableTelemetry: false\n").unwrap();
+    cirno.manifest.apps.push(App {
+        id,
+        name: name.to_string(),
+        created: "2025-01-01T00:00:00.000Z".to_string(),
+        backups: vec![],
+        release_hash: None,
+        env: Default::default(),
+        source: None,
+        tags: vec![],
+        aliases: vec![],
+    });
+    cirno.save().await.unwrap();
+    id
+}
+
+/// Writes a file to the shared cache for every package of a lockfile, named after `key`, and returns their paths.
+pub fn fill_cache(cirno: &Cirno, meta: &Meta, key: &str) -> Vec<PathBuf> {
+    let cache_dir = cirno.cwd.join("home/.yarn/cache");
+    let mut paths = vec![];
+    for slug in meta.yarn_lock.get_cache_files().unwrap() {
+        let path = cache_dir.join(format!("{}-{}.zip", slug, key));
+        std::fs::write(&path, format!("{}-{}", slug, key)).unwrap();
+        paths.push(path);
+    }
+    paths
+}
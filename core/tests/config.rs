@@ -0,0 +1,87 @@
+//! Effective Yarn configuration of an application, layered from its rc files and environment.
+
+use cirno_core::yarn::{ResolvedYarnRc, YarnRc, YarnRcSource, env_setting_name};
+use serde_json::Value;
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+#[test]
+fn env_setting_names() {
+    assert_eq!(env_setting_name("YARN_CACHE_FOLDER").as_deref(), Some("cacheFolder"));
+    assert_eq!(env_setting_name("YARN_NPM_AUTH_TOKEN").as_deref(), Some("npmAuthToken"));
+    assert_eq!(
+        env_setting_name("YARN_ENABLE_TELEMETRY").as_deref(),
+        Some("enableTelemetry")
+    );
+    assert_eq!(env_setting_name("YARN_"), None);
+    assert_eq!(env_setting_name("NODE_OPTIONS"), None);
+}
+
+#[test]
+fn layers_override() {
+    let mut resolved = ResolvedYarnRc::default();
+    let global = YarnRc {
+        enable_telemetry: Some(true),
+        enable_tips: Some(false),
+        ..Default::default()
+    };
+    resolved.layer(&global, YarnRcSource::Global).unwrap();
+    let local = YarnRc {
+        enable_telemetry: Some(false),
+        ..Default::default()
+    };
+    resolved.layer(&local, YarnRcSource::App).unwrap();
+    resolved.layer_env(
+        "YARN_ENABLE_TELEMETRY",
+        "true",
+        YarnRcSource::Env("YARN_ENABLE_TELEMETRY".into()),
+        false,
+    );
+    resolved.layer_env("PATH", "/bin", YarnRcSource::Env("PATH".into()), false);
+
+    let setting = &resolved.settings["enableTelemetry"];
+    assert_eq!(setting.value, Value::Bool(true));
+    assert_eq!(setting.source, YarnRcSource::Env("YARN_ENABLE_TELEMETRY".into()));
+    assert_eq!(setting.overrides, [YarnRcSource::Global, YarnRcSource::App]);
+    assert_eq!(resolved.settings["enableTips"].source, YarnRcSource::Global);
+    assert!(!resolved.settings.contains_key("path"));
+    assert_eq!(resolved.to_yarn_rc().unwrap().enable_telemetry, Some(true));
+}
+
+#[tokio::test]
+async fn resolves_app_config() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    cirno
+        .set_secret(&id, "YARN_NPM_AUTH_TOKEN", Some("token".to_string()))
+        .await
+        .unwrap();
+
+    let resolved = cirno.resolve_yarn_rc(&id).await.unwrap();
+    assert_eq!(resolved.settings["nodeLinker"].source, YarnRcSource::Global);
+    let telemetry = &resolved.settings["enableTelemetry"];
+    assert_eq!(telemetry.source, YarnRcSource::App);
+    assert_eq!(telemetry.overrides, [YarnRcSource::Global]);
+    assert_eq!(resolved.settings["globalFolder"].source, YarnRcSource::Cirno);
+    assert_eq!(resolved.settings["yarnPath"].source, YarnRcSource::Cirno);
+    assert!(resolved.settings["npmAuthToken"].secret);
+    // the global cache is the default of Yarn 4, and is not enforced
+    assert!(!resolved.settings.contains_key("enableGlobalCache"));
+    let cache_folder = cirno.cwd.join("home/.yarn/cache").to_string_lossy().to_string();
+    assert_eq!(resolved.settings["cacheFolder"].value, Value::String(cache_folder));
+    assert_eq!(resolved.settings["cacheFolder"].source, YarnRcSource::Cirno);
+
+    // a local cache is reported as configured
+    let cwd = cirno.cwd.join("apps").join(id.to_string());
+    std::fs::write(
+        cwd.join(".yarnrc.yml"),
+        "enableGlobalCache: false\ncacheFolder: ./cache\n",
+    )
+    .unwrap();
+    let resolved = cirno.resolve_yarn_rc(&id).await.unwrap();
+    assert_eq!(resolved.settings["cacheFolder"].value, Value::String("./cache".into()));
+    assert_eq!(resolved.settings["cacheFolder"].source, YarnRcSource::App);
+}
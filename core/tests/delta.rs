@@ -0,0 +1,136 @@
+//! Delta bundles between two instances of an application.
+
+use std::path::Path;
+
+use cirno_core::baka::BackupOptions;
+use sha2::{Digest, Sha512};
+
+use crate::common::{Scratch, add_app, env, fill_cache, fixture_meta};
+
+mod common;
+
+fn write(path: &Path, content: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+#[tokio::test]
+async fn export_and_apply() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    write(&head.join("src/index.js"), "v1");
+    write(&head.join("src/old.js"), "old");
+    write(&head.join("legacy/lib/a.js"), "a");
+    std::fs::create_dir_all(head.join("empty-old")).unwrap();
+    let base = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+
+    // the target ships another yarn release, declared with its hash
+    let release = b"// yarn 4.3.0";
+    write(
+        &cirno.cwd.join("home/.yarn/releases/yarn-4.3.0.cjs"),
+        std::str::from_utf8(release).unwrap(),
+    );
+    let package = std::fs::read_to_string(head.join("package.json")).unwrap();
+    let hash = format!("sha512.{}", hex::encode(Sha512::digest(release)));
+    write(
+        &head.join("package.json"),
+        &package.replace("yarn@4.2.2", &format!("yarn@4.3.0+{}", hash)),
+    );
+    write(&head.join("src/index.js"), "v2");
+    std::fs::remove_file(head.join("src/old.js")).unwrap();
+    std::fs::remove_dir_all(head.join("legacy")).unwrap();
+    std::fs::remove_dir(head.join("empty-old")).unwrap();
+    std::fs::create_dir_all(head.join("data/empty")).unwrap();
+
+    let dest = scratch.0.join("patch.cirnodelta");
+    let manifest = cirno.export_delta(&id, &base, &dest).await.unwrap();
+    assert_eq!(
+        manifest.changed,
+        ["data/", "data/empty/", "package.json", "src/index.js"]
+    );
+    assert_eq!(
+        manifest.deleted,
+        ["empty-old/", "legacy/", "legacy/lib/", "legacy/lib/a.js", "src/old.js"]
+    );
+    assert!(manifest.cache.is_empty());
+    assert_eq!(manifest.release.as_deref(), Some("yarn-4.3.0.cjs"));
+
+    // a delta only applies onto its base
+    let error = cirno.apply_delta(&dest, &id).await.unwrap_err();
+    assert!(error.to_string().contains("does not match the base"), "{}", error);
+
+    cirno.restore(&base, false).await.unwrap();
+    assert!(head.join("legacy/lib/a.js").exists());
+    assert_eq!(cirno.get(&id).unwrap().release_hash, None);
+    let backup = cirno.apply_delta(&dest, &id).await.unwrap();
+    assert_eq!(std::fs::read_to_string(head.join("src/index.js")).unwrap(), "v2");
+    assert!(!head.join("src/old.js").exists());
+    assert!(!head.join("legacy").exists());
+    assert!(!head.join("empty-old").exists());
+    assert!(head.join("data/empty").is_dir());
+    assert_eq!(cirno.get(&id).unwrap().release_hash, Some(hash));
+    let app = cirno.get(&id).unwrap();
+    assert_eq!(app.backups.last().unwrap().id, backup);
+    assert_eq!(app.backups.last().unwrap().r#type.as_deref(), Some("delta"));
+}
+
+#[tokio::test]
+async fn rejects_other_applications() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let other = add_app(&mut cirno, "dep-1", "other").await;
+    let error = cirno
+        .export_delta(&id, &other, &scratch.0.join("patch.cirnodelta"))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("is not an instance of application"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn failed_install_keeps_head() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    let base = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+
+    // the target uses the node-modules linker, with a release which fails to install
+    let release = b"process.exit(1);\n";
+    let release_path = cirno.cwd.join("home/.yarn/releases/yarn-4.3.0.cjs");
+    write(&release_path, std::str::from_utf8(release).unwrap());
+    let package = std::fs::read_to_string(head.join("package.json")).unwrap();
+    let hash = format!("sha512.{}", hex::encode(Sha512::digest(release)));
+    write(
+        &head.join("package.json"),
+        &package.replace("yarn@4.2.2", &format!("yarn@4.3.0+{}", hash)),
+    );
+    let yarn_rc = std::fs::read_to_string(head.join(".yarnrc.yml")).unwrap();
+    write(
+        &head.join(".yarnrc.yml"),
+        &format!("{}nodeLinker: node-modules\n", yarn_rc),
+    );
+    let dest = scratch.0.join("patch.cirnodelta");
+    cirno.export_delta(&id, &base, &dest).await.unwrap();
+    cirno.restore(&base, false).await.unwrap();
+    std::fs::remove_file(&release_path).unwrap();
+
+    let error = cirno.apply_delta(&dest, &id).await.unwrap_err();
+    assert!(
+        error.to_string().contains("Failed to install dependencies"),
+        "{}",
+        error
+    );
+    assert_eq!(std::fs::read_to_string(head.join(".yarnrc.yml")).unwrap(), yarn_rc);
+    assert!(cirno.get(&id).unwrap().backups.is_empty());
+    // nothing references the release shipped by the delta
+    assert!(!release_path.exists());
+}
@@ -0,0 +1,107 @@
+//! Descriptors of exported bundles, and their validation on import.
+
+use std::path::Path;
+
+use cirno_core::bundle::{DESCRIPTOR_FILE, ExportOptions, ImportOptions, read_descriptor};
+
+use crate::common::{Scratch, add_app, env, fill_cache, fixture_meta};
+
+mod common;
+
+/// Exports the `dep-1` fixture as a directory bundle at `root/bundle`, with a dummy yarn release.
+async fn export(root: &Path) -> cirno_core::Cirno {
+    let mut cirno = env(root).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    std::fs::write(cirno.cwd.join("home/.yarn/releases/yarn-4.2.2.cjs"), "// yarn 4.2.2").unwrap();
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    std::fs::write(head.join(".node-version"), "22\n").unwrap();
+    cirno
+        .export(&id, &root.join("bundle"), &ExportOptions::default())
+        .await
+        .unwrap();
+    cirno
+}
+
+async fn import_error(cirno: &mut cirno_core::Cirno, src: &Path) -> String {
+    cirno
+        .import(src, &ImportOptions::default())
+        .await
+        .unwrap_err()
+        .to_string()
+}
+
+#[tokio::test]
+async fn describes_bundle() {
+    let scratch = Scratch::new();
+    export(&scratch.0).await;
+    let descriptor = read_descriptor(&scratch.0.join("bundle")).await.unwrap().unwrap();
+    assert_eq!(descriptor.name, "dep-1");
+    assert_eq!(descriptor.app_version.as_deref(), Some("1.0.0"));
+    assert_eq!(descriptor.yarn.version, "4.2.2");
+    assert!(descriptor.yarn.hash.starts_with("sha512."), "{}", descriptor.yarn.hash);
+    assert_eq!(descriptor.cache_key, "10c0");
+    assert_eq!(descriptor.runtime.requirement.as_deref(), Some("22"));
+    assert_eq!(descriptor.runtime.bundled, None);
+    assert!(descriptor.platform.os.is_empty() && descriptor.platform.cpu.is_empty());
+}
+
+#[tokio::test]
+async fn missing_descriptor() {
+    let scratch = Scratch::new();
+    std::fs::create_dir_all(scratch.0.join("old")).unwrap();
+    assert!(read_descriptor(&scratch.0.join("old")).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn rejects_changed_requirement() {
+    let scratch = Scratch::new();
+    let mut cirno = export(&scratch.0).await;
+    let bundle = scratch.0.join("bundle");
+    std::fs::write(bundle.join(".node-version"), "20\n").unwrap();
+    let error = import_error(&mut cirno, &bundle).await;
+    assert!(
+        error.contains("requires node 20") && error.contains("declares 22"),
+        "{}",
+        error
+    );
+
+    std::fs::remove_file(bundle.join(".node-version")).unwrap();
+    let error = import_error(&mut cirno, &bundle).await;
+    assert!(error.contains("requires node (none)"), "{}", error);
+}
+
+#[tokio::test]
+async fn rejects_changed_runtime() {
+    let scratch = Scratch::new();
+    let mut cirno = export(&scratch.0).await;
+    let bundle = scratch.0.join("bundle");
+    std::fs::create_dir_all(bundle.join(".node/22.1.0")).unwrap();
+    let error = import_error(&mut cirno, &bundle).await;
+    assert!(error.contains("descriptor declares none"), "{}", error);
+
+    // the embedded runtime must be the declared one, and satisfy the requirement
+    let path = bundle.join(DESCRIPTOR_FILE);
+    let descriptor = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(
+        &path,
+        descriptor.replace("\"bundled\": null", "\"bundled\": \"20.0.0\""),
+    )
+    .unwrap();
+    let error = import_error(&mut cirno, &bundle).await;
+    assert!(error.contains("does not embed the runtime declared"), "{}", error);
+
+    std::fs::rename(bundle.join(".node/22.1.0"), bundle.join(".node/20.0.0")).unwrap();
+    let error = import_error(&mut cirno, &bundle).await;
+    assert!(error.contains("does not satisfy its requirement: 22"), "{}", error);
+}
+
+#[tokio::test]
+async fn rejects_changed_release() {
+    let scratch = Scratch::new();
+    let mut cirno = export(&scratch.0).await;
+    let bundle = scratch.0.join("bundle");
+    std::fs::write(bundle.join(".yarn/releases/yarn-4.2.2.cjs"), "// tampered").unwrap();
+    let error = import_error(&mut cirno, &bundle).await;
+    assert!(error.contains("does not match its descriptor"), "{}", error);
+}
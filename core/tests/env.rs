@@ -0,0 +1,138 @@
+//! Environment of the processes of an application: host variables, declared variables, secrets and the variables
+//! enforced by Cirno.
+
+use std::sync::Once;
+
+use cirno_core::env::{EnvPolicy, EnvSource, Environment};
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+/// Sets the host variables read by the tests, once before any environment is built.
+fn host() {
+    static HOST: Once = Once::new();
+    HOST.call_once(|| {
+        // SAFETY: every test calls this before reading the environment, and no other thread writes it.
+        unsafe {
+            std::env::set_var("CIRNO_TEST_ALLOWED", "host");
+            std::env::set_var("CIRNO_TEST_DENIED", "host");
+            #[cfg(unix)]
+            {
+                use std::ffi::OsStr;
+                use std::os::unix::ffi::OsStrExt;
+                std::env::set_var("CIRNO_TEST_INVALID", OsStr::from_bytes(b"\xff\xfe"));
+            }
+        }
+    });
+}
+
+fn find<'a>(env: &'a Environment, name: &str) -> Option<(&'a str, EnvSource)> {
+    env.vars
+        .iter()
+        .find(|var| var.name == name)
+        .map(|var| (var.value.as_str(), var.source))
+}
+
+#[test]
+fn policy_patterns() {
+    let policy = EnvPolicy {
+        allow: vec!["PATH".to_string(), "LC_*".to_string()],
+    };
+    assert!(policy.allows("PATH"));
+    assert!(policy.allows("LC_ALL"));
+    assert!(policy.allows("LC_"));
+    assert!(!policy.allows("LC"));
+    assert!(!policy.allows("PATHEXT"));
+    assert!(!policy.allows("NODE_OPTIONS"));
+    // variable names are case-insensitive on Windows only
+    assert_eq!(policy.allows("Path"), cfg!(windows));
+}
+
+#[tokio::test]
+async fn layers() {
+    host();
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    cirno.manifest.config.env = Some(EnvPolicy {
+        allow: vec!["CIRNO_TEST_*".to_string(), "HOME".to_string()],
+    });
+    cirno
+        .set_env(&id, "CIRNO_TEST_ALLOWED", Some("app".to_string()))
+        .await
+        .unwrap();
+    cirno.set_env(&id, "DECLARED", Some("app".to_string())).await.unwrap();
+    cirno
+        .set_secret(&id, "DECLARED", Some("secret".to_string()))
+        .await
+        .unwrap();
+
+    let cwd = cirno.cwd.join("apps").join(id.to_string());
+    let env = cirno.environment(&cwd).await.unwrap();
+    assert_eq!(find(&env, "CIRNO_TEST_DENIED"), Some(("host", EnvSource::Host)));
+    assert_eq!(find(&env, "CIRNO_TEST_ALLOWED"), Some(("app", EnvSource::App)));
+    assert_eq!(find(&env, "DECLARED"), Some(("secret", EnvSource::Secret)));
+    let home = cirno.cwd.join("home").to_string_lossy().to_string();
+    assert_eq!(find(&env, "HOME"), Some((home.as_str(), EnvSource::Cirno)));
+    assert!(find(&env, "CIRNO_TEST_INVALID").is_none());
+    assert_eq!(env.vars.iter().filter(|var| var.name == "DECLARED").count(), 1);
+
+    // the variables of an application do not apply to a copy of it
+    let env = cirno.environment(&common::fixture("dep-1")).await.unwrap();
+    assert_eq!(find(&env, "CIRNO_TEST_ALLOWED"), Some(("host", EnvSource::Host)));
+    assert!(find(&env, "DECLARED").is_none());
+}
+
+#[tokio::test]
+async fn default_policy() {
+    host();
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let env = cirno
+        .environment(&cirno.cwd.join("apps").join(id.to_string()))
+        .await
+        .unwrap();
+    assert!(find(&env, "CIRNO_TEST_ALLOWED").is_none());
+    assert!(
+        env.vars
+            .iter()
+            .all(|var| var.source != EnvSource::Host || var.name != "NODE_OPTIONS")
+    );
+}
+
+#[tokio::test]
+async fn runtime_path() {
+    host();
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let cwd = cirno.cwd.join("apps").join(id.to_string());
+    std::fs::create_dir_all(cirno.cwd.join("home/.node/20.11.0/bin")).unwrap();
+    std::fs::write(cwd.join(".node-version"), "20").unwrap();
+
+    let env = cirno.environment(&cwd).await.unwrap();
+    let runtime = env.runtime.as_ref().unwrap();
+    assert_eq!(runtime.version.to_string(), "20.11.0");
+    let (path, source) = find(&env, "PATH").unwrap();
+    assert_eq!(source, EnvSource::Cirno);
+    let first = std::env::split_paths(path).next().unwrap();
+    assert_eq!(first, runtime.bin_dir());
+    assert_eq!(
+        env.vars
+            .iter()
+            .filter(|var| var.name.eq_ignore_ascii_case("PATH"))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn host_environment() {
+    host();
+    let env = Environment::host();
+    assert_eq!(find(&env, "CIRNO_TEST_DENIED"), Some(("host", EnvSource::Host)));
+    assert!(find(&env, "CIRNO_TEST_INVALID").is_none());
+    assert!(env.runtime.is_none());
+}
@@ -0,0 +1,123 @@
+//! Imports from git repositories and bundle files, and updates of the imported applications.
+
+use std::path::Path;
+use std::process::Command;
+
+use cirno_core::bundle::{ExportOptions, ImportOptions, UpdateReport};
+
+use crate::common::{Scratch, add_app, env, fill_cache, fixture_meta};
+
+mod common;
+
+/// Runs git in `dir`, and returns its output.
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=cirno", "-c", "user.email=cirno@localhost"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Exports the `dep-1` fixture into a new git repository at `root/repo`, and returns the environment.
+async fn repository(root: &Path) -> cirno_core::Cirno {
+    let mut cirno = env(root).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    std::fs::write(cirno.cwd.join("home/.yarn/releases/yarn-4.2.2.cjs"), "// yarn 4.2.2").unwrap();
+    let repo = root.join("repo");
+    cirno.export(&id, &repo, &ExportOptions::default()).await.unwrap();
+    std::fs::write(repo.join("index.js"), "v1").unwrap();
+    git(&repo, &["init", "--quiet", "--initial-branch", "main"]);
+    git(&repo, &["add", "--all"]);
+    git(&repo, &["commit", "--quiet", "--message", "v1"]);
+    git(&repo, &["tag", "v1"]);
+    cirno
+}
+
+fn import_ref(reference: &str) -> ImportOptions {
+    ImportOptions {
+        r#ref: Some(reference.to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn import_repository() {
+    let scratch = Scratch::new();
+    let mut cirno = repository(&scratch.0).await;
+    let repo = scratch.0.join("repo");
+    let commit = git(&repo, &["rev-parse", "HEAD"]);
+
+    let report = cirno.import(&repo, &ImportOptions::default()).await.unwrap();
+    let source = report.source.unwrap();
+    assert_eq!(source.repository, repo.to_string_lossy());
+    assert_eq!(source.r#ref, None);
+    assert_eq!(source.commit, commit);
+    let head = cirno.cwd.join("apps").join(report.id.to_string());
+    assert_eq!(std::fs::read_to_string(head.join("index.js")).unwrap(), "v1");
+    assert!(!head.join(".git").exists());
+    assert_eq!(cirno.get(&report.id).unwrap().source.as_ref().unwrap().commit, commit);
+}
+
+#[tokio::test]
+async fn import_bundle_file() {
+    let scratch = Scratch::new();
+    let mut cirno = repository(&scratch.0).await;
+    let repo = scratch.0.join("repo");
+    let tagged = git(&repo, &["rev-parse", "HEAD"]);
+    std::fs::write(repo.join("index.js"), "v2").unwrap();
+    git(&repo, &["commit", "--quiet", "--all", "--message", "v2"]);
+    let file = scratch.0.join("repo.bundle");
+    git(&repo, &["bundle", "create", "--quiet", file.to_str().unwrap(), "--all"]);
+
+    let report = cirno.import(&file, &import_ref("v1")).await.unwrap();
+    assert_eq!(report.source.unwrap().commit, tagged);
+    let head = cirno.cwd.join("apps").join(report.id.to_string());
+    assert_eq!(std::fs::read_to_string(head.join("index.js")).unwrap(), "v1");
+
+    let error = cirno.import(&file, &import_ref("v3")).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("Revision v3 not found"), "{:#}", error);
+    // refs only apply to git sources
+    let error = cirno.import(&head, &import_ref("v1")).await.unwrap_err().to_string();
+    assert_eq!(error, "A ref can only be imported from a git repository or bundle.");
+}
+
+#[tokio::test]
+async fn update() {
+    let scratch = Scratch::new();
+    let mut cirno = repository(&scratch.0).await;
+    let repo = scratch.0.join("repo");
+    let id = cirno.import(&repo, &import_ref("main")).await.unwrap().id;
+    assert!(matches!(
+        cirno.update(&id).await.unwrap(),
+        UpdateReport::UpToDate { .. }
+    ));
+    assert!(cirno.get(&id).unwrap().backups.is_empty());
+
+    std::fs::write(repo.join("index.js"), "v2").unwrap();
+    git(&repo, &["commit", "--quiet", "--all", "--message", "v2"]);
+    let commit = git(&repo, &["rev-parse", "HEAD"]);
+    let UpdateReport::Updated {
+        commit: updated,
+        backup,
+        ..
+    } = cirno.update(&id).await.unwrap()
+    else {
+        panic!("main has a new commit");
+    };
+    assert_eq!(updated, commit);
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    assert_eq!(std::fs::read_to_string(head.join("index.js")).unwrap(), "v2");
+    let app = cirno.get(&id).unwrap();
+    assert_eq!(app.source.as_ref().unwrap().commit, commit);
+    assert_eq!(app.backups.len(), 1);
+    assert_eq!(app.backups[0].id, backup);
+    assert_eq!(app.backups[0].r#type.as_deref(), Some("update"));
+
+    // the previous tree is kept in the backup
+    cirno.restore(&backup, false).await.unwrap();
+    assert_eq!(std::fs::read_to_string(head.join("index.js")).unwrap(), "v1");
+}
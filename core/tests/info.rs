@@ -0,0 +1,44 @@
+//! Reports of instances.
+
+use cirno_core::baka::BackupOptions;
+use serde_json::json;
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+const YARN_RC: &str = "\
+npmAuthToken: top-secret
+npmScopes:
+  acme:
+    npmRegistryServer: https://npm.acme.test
+    npmAuthIdent: user:password
+npmRegistries:
+  https://npm.example.test:
+    npmAuthToken: registry-secret
+    npmAlwaysAuth: true
+";
+
+#[tokio::test]
+async fn masks_credentials() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    std::fs::write(cirno.cwd.join("apps").join(id.to_string()).join(".yarnrc.yml"), YARN_RC).unwrap();
+    let backup = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+
+    for instance in [id, backup] {
+        let yarn_rc = cirno.info(&instance).await.unwrap().yarn_rc;
+        assert_eq!(yarn_rc["npmAuthToken"], "********");
+        assert_eq!(yarn_rc["npmScopes"]["acme"]["npmAuthIdent"], "********");
+        assert_eq!(
+            yarn_rc["npmScopes"]["acme"]["npmRegistryServer"],
+            "https://npm.acme.test"
+        );
+        assert_eq!(
+            yarn_rc["npmRegistries"]["https://npm.example.test"],
+            json!({ "npmAuthToken": "********", "npmAlwaysAuth": true })
+        );
+        assert!(!yarn_rc.to_string().contains("secret"), "{}", yarn_rc);
+    }
+}
@@ -0,0 +1,95 @@
+//! Inspection of bundles without importing them.
+
+use std::path::Path;
+
+use cirno_core::bundle::{BundleFormat, ExportOptions, inspect};
+
+use crate::common::{Scratch, add_app, env, fill_cache, fixture_meta};
+
+mod common;
+
+/// Exports the `dep-1` fixture with a dummy yarn release, as a directory or a file of `format`.
+async fn export(root: &Path, dest: &Path, format: Option<BundleFormat>) {
+    let mut cirno = env(root).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    std::fs::write(cirno.cwd.join("home/.yarn/releases/yarn-4.2.2.cjs"), "// yarn 4.2.2").unwrap();
+    let options = ExportOptions {
+        format,
+        ..Default::default()
+    };
+    cirno.export(&id, dest, &options).await.unwrap();
+}
+
+#[tokio::test]
+async fn self_contained() {
+    for format in [None, Some(BundleFormat::Zip), Some(BundleFormat::TarZst)] {
+        let scratch = Scratch::new();
+        let dest = scratch.0.join("bundle");
+        export(&scratch.0, &dest, format).await;
+        let report = inspect(&dest).await.unwrap();
+        assert_eq!(report.format, format);
+        assert_eq!(report.name, "@fixture/dep-1");
+        assert_eq!(report.package_manager, "yarn@4.2.2");
+        assert_eq!(report.lockfile_version, 8);
+        assert_eq!(report.descriptor.unwrap().name, "dep-1");
+        let names = report
+            .packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "@types/emscripten@npm:1.39.13",
+                "@yarnpkg/fslib@npm:3.1.0",
+                "@yarnpkg/libzip@npm:3.1.0",
+                "tslib@npm:2.6.3"
+            ]
+        );
+        let tslib = &report.packages[3];
+        assert_eq!(
+            tslib.cache_file.as_deref(),
+            Some(".yarn/cache/tslib-npm-2.6.3-0fd136b3be-10c0.zip")
+        );
+        assert_eq!(tslib.size, "tslib-npm-2.6.3-0fd136b3be-10c0".len() as u64);
+        assert_eq!(
+            report.cache_size,
+            report.packages.iter().map(|package| package.size).sum::<u64>()
+        );
+        assert!(report.total_size > report.cache_size);
+        assert!(report.self_contained, "{:?}", report.issues);
+    }
+}
+
+#[tokio::test]
+async fn issues() {
+    let scratch = Scratch::new();
+    let dest = scratch.0.join("bundle");
+    export(&scratch.0, &dest, None).await;
+    std::fs::remove_file(dest.join(".yarn/cache/tslib-npm-2.6.3-0fd136b3be-10c0.zip")).unwrap();
+
+    std::fs::remove_file(dest.join(".yarn/releases/yarn-4.2.2.cjs")).unwrap();
+    let yarn_rc = std::fs::read_to_string(dest.join(".yarnrc.yml")).unwrap();
+    std::fs::write(
+        dest.join(".yarnrc.yml"),
+        yarn_rc.replace("enableGlobalCache: false\n", ""),
+    )
+    .unwrap();
+
+    let report = inspect(&dest).await.unwrap();
+    assert!(!report.self_contained);
+    assert_eq!(
+        report.issues,
+        [
+            "Yarn release is missing at .yarn/releases/yarn-4.2.2.cjs.",
+            "enableGlobalCache is not disabled, so the cache files of the bundle are ignored.",
+            "Cache file of tslib@npm:2.6.3 is missing.",
+        ]
+    );
+    assert_eq!(report.packages[3].cache_file, None);
+
+    std::fs::remove_file(dest.join(".yarnrc.yml")).unwrap();
+    let error = inspect(&dest).await.unwrap_err().to_string();
+    assert_eq!(error, "Bundle has no .yarnrc.yml.");
+}
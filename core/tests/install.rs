@@ -0,0 +1,79 @@
+//! Reporting of `yarn install` runs.
+
+use std::sync::{Arc, Mutex};
+
+use cirno_core::report::{Event, Phase, Reporter};
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<Event>>);
+
+impl Reporter for Recorder {
+    fn report(&self, event: &Event) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[tokio::test]
+async fn failure_keeps_output_tail() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    std::fs::write(head.join(".yarnrc.yml"), "nodeLinker: node-modules\n").unwrap();
+    std::fs::write(
+        cirno.cwd.join("home/.yarn/releases/yarn-4.2.2.cjs"),
+        "for (let i = 0; i < 30; i++) console.log(`line ${i}`);\nconsole.error('boom');\nprocess.exit(1);\n",
+    )
+    .unwrap();
+    let recorder = Arc::new(Recorder::default());
+    cirno.set_reporter(recorder.clone());
+
+    // the output is not lost when it is not reported
+    let error = cirno.install(&head).await.unwrap_err().to_string();
+    assert!(error.contains("Exit code"), "{}", error);
+    assert!(error.contains("line 29") && error.contains("boom"), "{}", error);
+    assert!(!error.contains("line 0\n"), "{}", error);
+    assert_eq!(error.lines().count(), 21, "{}", error);
+
+    let events = recorder.0.lock().unwrap();
+    let output = events
+        .iter()
+        .filter(|event| matches!(event, Event::YarnOutput { .. }))
+        .count();
+    assert_eq!(output, 31);
+    assert!(matches!(
+        events.last(),
+        Some(Event::PhaseFinished {
+            phase: Phase::Install,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn phases_finish_on_error() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let recorder = Arc::new(Recorder::default());
+    cirno.set_reporter(recorder.clone());
+
+    // the yarn release is missing, so packing fails after its phase started
+    let dest = scratch.0.join("bundle");
+    assert!(cirno.export(&id, &dest, &Default::default()).await.is_err());
+    let events = recorder.0.lock().unwrap();
+    let started = events
+        .iter()
+        .filter(|event| matches!(event, Event::PhaseStarted { .. }))
+        .count();
+    let finished = events
+        .iter()
+        .filter(|event| matches!(event, Event::PhaseFinished { .. }))
+        .count();
+    assert!(started > 0);
+    assert_eq!(started, finished);
+}
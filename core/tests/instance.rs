@@ -0,0 +1,136 @@
+//! Creation of applications, and restoration of their backups.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+
+use cirno_core::baka::BackupOptions;
+use cirno_core::instance::CreateOptions;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+/// Release which writes an empty lockfile, as `yarn install` does in an application without dependencies.
+const RELEASE: &str = "require('fs').writeFileSync('yarn.lock', '__metadata:\\n  version: 8\\n  cacheKey: 10c0\\n')\n";
+
+/// Serves one HTTP response, and returns the registry URL along with a receiver of the requested path.
+fn serve(status: &'static str, body: Vec<u8>) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![];
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let len = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..len]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        sender.send(request.split(' ').nth(1).unwrap().to_string()).unwrap();
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(&body).unwrap();
+    });
+    (url, receiver)
+}
+
+/// Packs a release like `@yarnpkg/cli-dist`.
+fn tarball(release: &str) -> Vec<u8> {
+    let mut tar = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+    for (path, content) in [("package/package.json", "{}"), ("package/bin/yarn.js", release)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, path, content.as_bytes()).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap()
+}
+
+fn options(package_manager: &str) -> CreateOptions {
+    CreateOptions {
+        package_manager: package_manager.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn create_downloads_yarn() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let (registry, requests) = serve("200 OK", tarball(RELEASE));
+    let global = cirno.cwd.join("home/.yarnrc.yml");
+    let content = std::fs::read_to_string(&global).unwrap();
+    std::fs::write(&global, format!("{}npmRegistryServer: {}\n", content, registry)).unwrap();
+
+    let id = cirno.create("fresh", &options("yarn@4.9.1")).await.unwrap();
+    assert_eq!(requests.recv().unwrap(), "/@yarnpkg/cli-dist/-/cli-dist-4.9.1.tgz");
+    let release = cirno.cwd.join("home/.yarn/releases/yarn-4.9.1.cjs");
+    assert_eq!(std::fs::read_to_string(release).unwrap(), RELEASE);
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    assert!(head.join("yarn.lock").exists());
+    // like yarn, Cirno does not write a default configuration
+    assert!(!head.join(".yarnrc.yml").exists());
+    assert_eq!(cirno.load_meta(&id).await.unwrap().package.name, "fresh");
+
+    // installed releases are not downloaded again
+    cirno.create("again", &options("yarn@4.9.1")).await.unwrap();
+}
+
+#[tokio::test]
+async fn download_failure() {
+    let scratch = Scratch::new();
+    let cirno = env(&scratch.0).await;
+    let (registry, _requests) = serve("404 Not Found", vec![]);
+    let package_manager = cirno_core::yarn::PackageManager::yarn("yarn@4.9.1").unwrap();
+    let error = cirno
+        .download_yarn(&package_manager, Some(&registry))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("Failed to download yarn 4.9.1") && error.contains("404"),
+        "{}",
+        error
+    );
+    assert!(!cirno.cwd.join("home/.yarn/releases/yarn-4.9.1.cjs").exists());
+    assert_eq!(std::fs::read_dir(cirno.cwd.join("tmp/.cirno")).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn failed_restore_keeps_head() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let head = cirno.cwd.join("apps").join(id.to_string());
+    std::fs::write(head.join("index.js"), "v1").unwrap();
+    let backup = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+    cirno.tag(&backup, &["stable".to_string()], false).await.unwrap();
+    std::fs::write(head.join("index.js"), "v2").unwrap();
+
+    // saving the manifest fails once the head is swapped
+    let state = cirno.cwd.join("cirno-baka.br");
+    std::fs::remove_file(&state).unwrap();
+    std::fs::create_dir_all(state.join("locked")).unwrap();
+    assert!(cirno.restore(&backup, false).await.is_err());
+    assert_eq!(std::fs::read_to_string(head.join("index.js")).unwrap(), "v2");
+    let app = cirno.get(&id).unwrap();
+    assert_eq!(app.backups.len(), 1);
+    assert_eq!(app.backups[0].tags, ["stable"]);
+    assert!(app.tags.is_empty());
+    assert_eq!(std::fs::read_dir(cirno.cwd.join("tmp/.cirno")).unwrap().count(), 0);
+
+    // the backup is still in the archive
+    std::fs::remove_dir_all(&state).unwrap();
+    cirno.restore(&backup, false).await.unwrap();
+    assert_eq!(std::fs::read_to_string(head.join("index.js")).unwrap(), "v1");
+    assert!(cirno.get(&id).unwrap().backups.is_empty());
+}
@@ -0,0 +1,81 @@
+//! License expressions checked against the license policy of an environment.
+
+use cirno_core::license::{LicensePolicy, LicenseStatus};
+
+fn policy(allow: &[&str], deny: &[&str]) -> LicensePolicy {
+    LicensePolicy {
+        allow: allow.iter().map(|id| id.to_string()).collect(),
+        deny: deny.iter().map(|id| id.to_string()).collect(),
+    }
+}
+
+#[test]
+fn single_license() {
+    let policy = policy(&["MIT", "ISC"], &["GPL-3.0-only"]);
+    assert_eq!(policy.check("MIT"), LicenseStatus::Approved);
+    assert_eq!(policy.check("mit"), LicenseStatus::Approved);
+    assert_eq!(policy.check("GPL-3.0-only"), LicenseStatus::Denied);
+    assert_eq!(policy.check("BSD-3-Clause"), LicenseStatus::NotApproved);
+    assert_eq!(LicensePolicy::default().check("BSD-3-Clause"), LicenseStatus::Approved);
+}
+
+#[test]
+fn compound_expressions() {
+    let policy = policy(&["MIT", "ISC"], &["GPL-3.0-only"]);
+    assert_eq!(policy.check("MIT OR GPL-3.0-only"), LicenseStatus::Approved);
+    assert_eq!(policy.check("MIT AND GPL-3.0-only"), LicenseStatus::Denied);
+    assert_eq!(policy.check("MIT AND BSD-3-Clause"), LicenseStatus::NotApproved);
+    assert_eq!(policy.check("(MIT AND ISC) OR GPL-3.0-only"), LicenseStatus::Approved);
+    assert_eq!(policy.check("GPL-3.0-only OR BSD-3-Clause"), LicenseStatus::NotApproved);
+    assert_eq!(policy.check("(ISC OR GPL-3.0-only)"), LicenseStatus::Approved);
+}
+
+#[test]
+fn lowercase_operators() {
+    let policy = policy(&["MIT", "ISC"], &["GPL-3.0-only"]);
+    assert_eq!(policy.check("MIT or GPL-3.0-only"), LicenseStatus::Approved);
+    assert_eq!(policy.check("MIT and GPL-3.0-only"), LicenseStatus::Denied);
+    assert_eq!(policy.check("(mit and isc) Or BSD-3-Clause"), LicenseStatus::Approved);
+}
+
+#[test]
+fn exceptions() {
+    let policy = policy(&["Apache-2.0 WITH LLVM-exception"], &[]);
+    assert_eq!(policy.check("Apache-2.0 WITH LLVM-exception"), LicenseStatus::Approved);
+    assert_eq!(policy.check("Apache-2.0 with LLVM-exception"), LicenseStatus::Approved);
+    assert_eq!(policy.check("Apache-2.0"), LicenseStatus::NotApproved);
+
+    let policy = self::policy(&["Apache-2.0"], &[]);
+    assert_eq!(policy.check("Apache-2.0 WITH LLVM-exception"), LicenseStatus::Approved);
+
+    // denying a license also denies it with any exception
+    let policy = self::policy(&[], &["GPL-2.0-only"]);
+    assert_eq!(
+        policy.check("GPL-2.0-only WITH Classpath-exception-2.0"),
+        LicenseStatus::Denied
+    );
+    let policy = self::policy(&["GPL-2.0-only"], &["GPL-2.0-only WITH Classpath-exception-2.0"]);
+    assert_eq!(
+        policy.check("GPL-2.0-only WITH Classpath-exception-2.0"),
+        LicenseStatus::Denied
+    );
+}
+
+#[test]
+fn or_later_suffix() {
+    let policy = policy(&["GPL-2.0-or-later"], &[]);
+    assert_eq!(policy.check("GPL-2.0+"), LicenseStatus::Approved);
+    let policy = self::policy(&["MIT"], &["GPL-2.0"]);
+    assert_eq!(policy.check("GPL-2.0+"), LicenseStatus::Denied);
+    assert_eq!(policy.check("LGPL-2.1+ OR MIT"), LicenseStatus::Approved);
+}
+
+#[test]
+fn malformed_expressions() {
+    let policy = policy(&["MIT"], &[]);
+    // an expression which cannot be parsed is checked as a whole, and never approved by accident
+    assert_eq!(policy.check("MIT OR"), LicenseStatus::NotApproved);
+    assert_eq!(policy.check("(MIT"), LicenseStatus::NotApproved);
+    assert_eq!(policy.check("MIT WITH"), LicenseStatus::NotApproved);
+    assert_eq!(policy.check("  MIT  "), LicenseStatus::Approved);
+}
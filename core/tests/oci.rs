@@ -0,0 +1,26 @@
+//! Export of OCI image layouts.
+
+use cirno_core::bundle::{ExportOptions, OciOptions};
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+#[tokio::test]
+async fn requires_runtime() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let dest = scratch.0.join("image.oci.tar");
+    let error = cirno
+        .export_oci(&id, &dest, &ExportOptions::default(), &OciOptions::default())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("--runtime"), "{}", error);
+    assert!(!dest.exists());
+    // nothing was left in the temporary directory
+    assert_eq!(
+        std::fs::read_dir(cirno.cwd.join("tmp/.cirno")).map_or(0, Iterator::count),
+        0
+    );
+}
@@ -0,0 +1,79 @@
+//! Parsing of the `packageManager` field and verification of Yarn releases.
+
+use cirno_core::yarn::{PackageManager, PackageManagerError, ReleaseHash};
+use sha2::{Digest, Sha512};
+
+use crate::common::Scratch;
+
+mod common;
+
+#[test]
+fn parses_package_manager() {
+    let package_manager = PackageManager::yarn("yarn@4.2.2").unwrap();
+    assert_eq!(package_manager.version, "4.2.2");
+    assert_eq!(package_manager.hash, None);
+    assert_eq!(package_manager.release_name(), "yarn-4.2.2.cjs");
+
+    let package_manager = PackageManager::yarn("yarn@4.0.0-rc.53+sha256.ABCDEF").unwrap();
+    assert_eq!(package_manager.version, "4.0.0-rc.53");
+    assert_eq!(package_manager.hash.unwrap().to_string(), "sha256.abcdef");
+
+    assert!(matches!(PackageManager::yarn(""), Err(PackageManagerError::Missing)));
+    assert!(matches!(
+        PackageManager::yarn("yarn@4"),
+        Err(PackageManagerError::Invalid(_))
+    ));
+    assert!(matches!(
+        PackageManager::yarn("pnpm@9.0.0"),
+        Err(PackageManagerError::Unsupported(_))
+    ));
+}
+
+#[tokio::test]
+async fn verifies_release() {
+    let scratch = Scratch::new();
+    let content = b"console.log('yarn')";
+    std::fs::write(scratch.0.join("yarn-4.2.2.cjs"), content).unwrap();
+    let digest = hex::encode(Sha512::digest(content));
+
+    let package_manager = PackageManager::yarn(&format!("yarn@4.2.2+sha512.{}", digest)).unwrap();
+    let hash = package_manager.verify(&scratch.0).await.unwrap().unwrap();
+    assert_eq!(hash, ReleaseHash::compute("sha512", content).unwrap());
+
+    // without a hash, the release is not read at all
+    let package_manager = PackageManager::yarn("yarn@4.2.2").unwrap();
+    assert_eq!(package_manager.verify(&scratch.0).await.unwrap(), None);
+
+    let package_manager = PackageManager::yarn("yarn@4.2.2+sha512.0123").unwrap();
+    assert!(matches!(
+        package_manager.verify(&scratch.0).await,
+        Err(PackageManagerError::HashMismatch { .. })
+    ));
+    let package_manager = PackageManager::yarn("yarn@4.2.2+md5.0123").unwrap();
+    assert!(matches!(
+        package_manager.verify(&scratch.0).await,
+        Err(PackageManagerError::UnsupportedAlgorithm(_))
+    ));
+    let package_manager = PackageManager::yarn(&format!("yarn@4.3.0+sha512.{}", digest)).unwrap();
+    assert!(matches!(
+        package_manager.verify(&scratch.0).await,
+        Err(PackageManagerError::ReleaseNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn locates_release() {
+    let scratch = Scratch::new();
+    std::fs::write(scratch.0.join("yarn-4.2.2.cjs"), "").unwrap();
+    // locating a release does not check its hash
+    let package_manager = PackageManager::yarn("yarn@4.2.2+sha512.0123").unwrap();
+    assert_eq!(
+        package_manager.locate(&scratch.0).await.unwrap(),
+        scratch.0.join("yarn-4.2.2.cjs")
+    );
+    let package_manager = PackageManager::yarn("yarn@4.3.0").unwrap();
+    assert!(matches!(
+        package_manager.locate(&scratch.0).await,
+        Err(PackageManagerError::ReleaseNotFound(_))
+    ));
+}
@@ -0,0 +1,115 @@
+//! Parsing, rewriting and validation of `.yarnrc.yml` files.
+
+use std::collections::BTreeSet;
+
+use cirno_core::yarn::{NodeLinker, YARN_RC_SETTINGS, YarnRc};
+
+/// Converts a field name to the name of its setting, as `#[serde(rename_all = "camelCase")]` does.
+fn camel_case(name: &str) -> String {
+    let mut words = name.split('_');
+    let mut result = words.next().unwrap_or_default().to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            result.push(first.to_ascii_uppercase());
+            result.extend(chars);
+        }
+    }
+    result
+}
+
+#[test]
+fn settings_match_fields() {
+    // every listed setting is deserialized into a field rather than kept as an unknown setting
+    let mapping = YARN_RC_SETTINGS
+        .iter()
+        .map(|name| format!("{}: null\n", name))
+        .collect::<String>();
+    let yarn_rc: YarnRc = serde_yaml_ng::from_str(&mapping).unwrap();
+    assert!(yarn_rc.extra.is_empty(), "unknown settings: {:?}", yarn_rc.extra.keys());
+
+    // and every field is listed, which serde cannot enumerate because of the flattened unknown settings
+    let source = include_str!("../src/yarn/rc.rs");
+    let start = source.find("pub struct YarnRc {").unwrap();
+    let end = start + source[start..].find("\n}\n").unwrap();
+    let fields = source[start..end]
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("pub ")?.split_once(": Option<"))
+        .map(|(name, _)| camel_case(name))
+        .collect::<BTreeSet<_>>();
+    let settings = YARN_RC_SETTINGS
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<BTreeSet<_>>();
+    assert_eq!(fields, settings);
+}
+
+#[test]
+fn round_trip() {
+    let original = "yarnPath: .yarn/releases/yarn-4.2.2.cjs\nnodeLinker: node-modules\nplugins:\n  - path: .yarn/plugins/plugin.cjs\nenableTelemetry: false\nnpmScopes:\n  fixture:\n    npmRegistryServer: https://example.com\npackageExtensions:\n  \"debug@*\":\n    peerDependenciesMeta:\n      supports-color:\n        optional: true\nsupportedArchitectures:\n  os: [current, linux]\n";
+    let mut yarn_rc: YarnRc = serde_yaml_ng::from_str(original).unwrap();
+    assert_eq!(yarn_rc.node_linker, Some(NodeLinker::NodeModules));
+    assert!(yarn_rc.extra.contains_key("plugins"));
+
+    // settings with partial values, such as a scope without credentials, are parsed
+    let scope = &yarn_rc.npm_scopes.as_ref().unwrap()["fixture"];
+    assert_eq!(scope.npm_registry_server.as_deref(), Some("https://example.com"));
+    assert_eq!(scope.npm_auth_token, None);
+
+    // unchanged settings keep their order and unknown settings are kept
+    let output = yarn_rc.to_yaml(Some(original)).unwrap();
+    let reparsed: serde_yaml_ng::Value = serde_yaml_ng::from_str(&output).unwrap();
+    assert_eq!(
+        reparsed,
+        serde_yaml_ng::from_str::<serde_yaml_ng::Value>(original).unwrap()
+    );
+    let keys = |yaml: &str| {
+        serde_yaml_ng::from_str::<serde_yaml_ng::Mapping>(yaml)
+            .unwrap()
+            .keys()
+            .map(|key| key.as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(keys(&output), keys(original));
+
+    // new settings are appended, removed ones are dropped
+    yarn_rc.yarn_path = None;
+    yarn_rc.enable_global_cache = Some(false);
+    let output = yarn_rc.to_yaml(Some(original)).unwrap();
+    assert_eq!(
+        keys(&output),
+        [
+            "nodeLinker",
+            "plugins",
+            "enableTelemetry",
+            "npmScopes",
+            "packageExtensions",
+            "supportedArchitectures",
+            "enableGlobalCache"
+        ]
+    );
+}
+
+#[test]
+fn misspellings() {
+    let yarn_rc: YarnRc = serde_yaml_ng::from_str(
+        "enableGlobalCahce: true\nNodeLinker: pnp\nnpmRegistyServer: https://example.com\nplugins: []\nmyPluginSetting: 1\nfoo: 1\n",
+    )
+    .unwrap();
+    let mut misspellings = yarn_rc
+        .validate()
+        .into_iter()
+        .map(|misspelling| (misspelling.name, misspelling.suggestion))
+        .collect::<Vec<_>>();
+    misspellings.sort();
+    assert_eq!(
+        misspellings,
+        [
+            ("NodeLinker".to_string(), "nodeLinker"),
+            ("enableGlobalCahce".to_string(), "enableGlobalCache"),
+            ("npmRegistyServer".to_string(), "npmRegistryServer"),
+        ]
+    );
+    let misspelling = &yarn_rc.validate()[0];
+    assert!(misspelling.to_string().contains("did you mean"));
+}
@@ -0,0 +1,131 @@
+//! Resolution of instance references.
+
+use cirno_core::resolve::ResolveError;
+use cirno_core::{App, Backup, Manifest};
+use uuid::Uuid;
+
+fn id(value: &str) -> Uuid {
+    Uuid::parse_str(value).unwrap()
+}
+
+fn backup(value: &str) -> Backup {
+    Backup {
+        id: id(value),
+        r#type: None,
+        message: None,
+        created: "2025-01-01T00:00:00.000Z".to_string(),
+        tags: vec![],
+        aliases: vec![],
+    }
+}
+
+fn app(value: &str, name: &str, backups: Vec<Backup>) -> App {
+    App {
+        id: id(value),
+        name: name.to_string(),
+        created: "2025-01-01T00:00:00.000Z".to_string(),
+        backups,
+        release_hash: None,
+        env: Default::default(),
+        source: None,
+        tags: vec![],
+        aliases: vec![],
+    }
+}
+
+const HEAD: &str = "aaaa1111-0000-4000-8000-000000000000";
+const OLDEST: &str = "aaaa2222-0000-4000-8000-000000000000";
+const LATEST: &str = "bbbb1111-0000-4000-8000-000000000000";
+const SCOPED: &str = "cccc1111-0000-4000-8000-000000000000";
+const SCOPED_BACKUP: &str = "cccc2222-0000-4000-8000-000000000000";
+const TILDE: &str = "dddd1111-0000-4000-8000-000000000000";
+
+/// `web` with two backups, `@scope/web` with one, and `web~1` which looks like a backup reference.
+fn manifest() -> Manifest {
+    let mut web = app(HEAD, "web", vec![backup(OLDEST), backup(LATEST)]);
+    web.aliases.push("prod".to_string());
+    web.backups[0].tags.push("stable".to_string());
+    let mut scoped = app(SCOPED, "@scope/web", vec![backup(SCOPED_BACKUP)]);
+    scoped.backups[0].tags.push("stable".to_string());
+    Manifest {
+        version: "1".to_string(),
+        config: Default::default(),
+        apps: vec![web, scoped, app(TILDE, "web~1", vec![])],
+    }
+}
+
+fn resolve(query: &str) -> Result<Uuid, ResolveError> {
+    manifest().resolve(query)
+}
+
+#[test]
+fn full_ids() {
+    assert_eq!(resolve(HEAD).unwrap(), id(HEAD));
+    assert_eq!(resolve(LATEST).unwrap(), id(LATEST));
+    assert_eq!(resolve(&format!(" {} ", OLDEST)).unwrap(), id(OLDEST));
+    let missing = "eeee1111-0000-4000-8000-000000000000";
+    assert!(matches!(resolve(missing), Err(ResolveError::NotFound(query)) if query == missing));
+}
+
+#[test]
+fn prefixes() {
+    assert_eq!(resolve("aaaa1").unwrap(), id(HEAD));
+    assert_eq!(resolve("BBBB").unwrap(), id(LATEST));
+    assert_eq!(resolve("cccc2222-0000").unwrap(), id(SCOPED_BACKUP));
+    // prefixes shorter than the minimum are not matched
+    assert!(matches!(resolve("bbb"), Err(ResolveError::NotFound(_))));
+    assert!(matches!(resolve("ffff"), Err(ResolveError::NotFound(_))));
+    let Err(ResolveError::Ambiguous { query, candidates }) = resolve("aaaa") else {
+        panic!("aaaa is ambiguous");
+    };
+    assert_eq!(query, "aaaa");
+    let candidates = candidates.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert_eq!(candidates, [format!("{} (web)", HEAD), format!("{} (web@1)", OLDEST)]);
+}
+
+#[test]
+fn names_and_labels() {
+    assert_eq!(resolve("web").unwrap(), id(HEAD));
+    assert_eq!(resolve("@scope/web").unwrap(), id(SCOPED));
+    assert_eq!(resolve("prod").unwrap(), id(HEAD));
+    assert!(matches!(resolve("stable"), Err(ResolveError::Ambiguous { candidates, .. }) if candidates.len() == 2));
+    assert!(matches!(resolve("api"), Err(ResolveError::NotFound(_))));
+}
+
+#[test]
+fn positions() {
+    assert_eq!(resolve("web@1").unwrap(), id(OLDEST));
+    assert_eq!(resolve("web@2").unwrap(), id(LATEST));
+    assert_eq!(resolve("web~0").unwrap(), id(HEAD));
+    assert_eq!(resolve("web~2").unwrap(), id(OLDEST));
+    assert_eq!(resolve("prod~1").unwrap(), id(LATEST));
+    assert_eq!(resolve("aaaa1111~1").unwrap(), id(LATEST));
+    // names may contain `@` and `~`
+    assert_eq!(resolve("@scope/web@1").unwrap(), id(SCOPED_BACKUP));
+    assert_eq!(resolve("@scope/web~0").unwrap(), id(SCOPED));
+    assert_eq!(resolve("web~1").unwrap(), id(TILDE));
+    assert_eq!(resolve("web~1~0").unwrap(), id(TILDE));
+}
+
+#[test]
+fn out_of_range_positions() {
+    for query in [
+        "web@0",
+        "web@3",
+        "web~3",
+        "@scope/web@2",
+        "web~1@1",
+        "web@99999999999999999999999",
+    ] {
+        let Err(ResolveError::BackupNotFound { query: actual, .. }) = resolve(query) else {
+            panic!("{} is out of range", query);
+        };
+        assert_eq!(actual, query);
+    }
+    assert_eq!(
+        resolve("web@3").unwrap_err().to_string(),
+        "Application web has no instance web@3."
+    );
+    // the application part must resolve
+    assert!(matches!(resolve("api@1"), Err(ResolveError::NotFound(_))));
+}
@@ -0,0 +1,91 @@
+//! Resolution of the managed Node.js runtime of an application.
+
+use std::path::Path;
+
+use cirno_core::runtime;
+
+use crate::common::Scratch;
+
+mod common;
+
+const VERSIONS: [&str; 7] = ["16.20.2", "18.2.0", "18.19.1", "20.0.0", "20.11.0", "21.7.3", "22.1.0"];
+
+/// Resolves the runtime required by `engines.node` among [`VERSIONS`].
+async fn resolve(root: &Path, range: &str) -> Option<String> {
+    let store = root.join("store");
+    for version in VERSIONS {
+        std::fs::create_dir_all(store.join(version)).unwrap();
+    }
+    let package = serde_json::json!({ "name": "app", "engines": { "node": range } });
+    std::fs::write(root.join("package.json"), package.to_string()).unwrap();
+    runtime::resolve(&store, root)
+        .await
+        .unwrap()
+        .map(|runtime| runtime.version.to_string())
+}
+
+#[tokio::test]
+async fn bare_versions() {
+    let scratch = Scratch::new();
+    // a bare version is an exact match, not a caret requirement
+    assert_eq!(resolve(&scratch.0, "18.2.0").await.as_deref(), Some("18.2.0"));
+    assert_eq!(resolve(&scratch.0, "v18.2.0").await.as_deref(), Some("18.2.0"));
+    assert_eq!(resolve(&scratch.0, "18.3.0").await, None);
+    // partial versions match every version they cover
+    assert_eq!(resolve(&scratch.0, "18").await.as_deref(), Some("18.19.1"));
+    assert_eq!(resolve(&scratch.0, "20.0").await.as_deref(), Some("20.0.0"));
+}
+
+#[tokio::test]
+async fn x_ranges() {
+    let scratch = Scratch::new();
+    assert_eq!(resolve(&scratch.0, "*").await.as_deref(), Some("22.1.0"));
+    assert_eq!(resolve(&scratch.0, "x").await.as_deref(), Some("22.1.0"));
+    assert_eq!(resolve(&scratch.0, "").await.as_deref(), Some("22.1.0"));
+    assert_eq!(resolve(&scratch.0, "18.x").await.as_deref(), Some("18.19.1"));
+    assert_eq!(resolve(&scratch.0, "20.0.X").await.as_deref(), Some("20.0.0"));
+    assert_eq!(resolve(&scratch.0, "<21.x").await.as_deref(), Some("20.11.0"));
+}
+
+#[tokio::test]
+async fn comparators() {
+    let scratch = Scratch::new();
+    assert_eq!(resolve(&scratch.0, "^18.2").await.as_deref(), Some("18.19.1"));
+    assert_eq!(resolve(&scratch.0, "~20.0.0").await.as_deref(), Some("20.0.0"));
+    assert_eq!(resolve(&scratch.0, ">= 18 <20").await.as_deref(), Some("18.19.1"));
+    assert_eq!(resolve(&scratch.0, ">=v20.1.0 <21").await.as_deref(), Some("20.11.0"));
+    assert_eq!(resolve(&scratch.0, "=20.0.0").await.as_deref(), Some("20.0.0"));
+}
+
+#[tokio::test]
+async fn hyphen_ranges() {
+    let scratch = Scratch::new();
+    assert_eq!(resolve(&scratch.0, "18.0.0 - 20.5.0").await.as_deref(), Some("20.0.0"));
+    // a partial upper bound covers every version it matches
+    assert_eq!(resolve(&scratch.0, "16 - 20").await.as_deref(), Some("20.11.0"));
+    assert_eq!(resolve(&scratch.0, "16.x - 18.2").await.as_deref(), Some("18.2.0"));
+    assert_eq!(resolve(&scratch.0, "17 - 17.5").await, None);
+}
+
+#[tokio::test]
+async fn alternatives() {
+    let scratch = Scratch::new();
+    assert_eq!(resolve(&scratch.0, "^16 || ^18").await.as_deref(), Some("18.19.1"));
+    assert_eq!(
+        resolve(&scratch.0, "18.2.0 || >=20.1.0 <21").await.as_deref(),
+        Some("20.11.0")
+    );
+    assert_eq!(resolve(&scratch.0, "14 || 15").await, None);
+    // a range which cannot be parsed is ignored
+    assert_eq!(resolve(&scratch.0, "^18 || lts").await, None);
+}
+
+#[tokio::test]
+async fn version_files() {
+    let scratch = Scratch::new();
+    std::fs::write(scratch.0.join(".nvmrc"), "v18\n").unwrap();
+    // version files take precedence over engines.node
+    assert_eq!(resolve(&scratch.0, "22").await.as_deref(), Some("18.19.1"));
+    std::fs::write(scratch.0.join(".node-version"), "20.0.0").unwrap();
+    assert_eq!(resolve(&scratch.0, "22").await.as_deref(), Some("20.0.0"));
+}
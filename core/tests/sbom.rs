@@ -0,0 +1,101 @@
+//! Software bills of materials generated from the lockfile of an application.
+
+use cirno_core::sbom::{self, SbomFormat};
+use serde_json::Value;
+
+use crate::common::fixture_meta;
+
+mod common;
+
+fn find<'a>(items: &'a Value, key: &str, value: &str) -> &'a Value {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item[key] == value)
+        .unwrap_or_else(|| panic!("{} {} not found", key, value))
+}
+
+#[test]
+fn parses_formats() {
+    assert_eq!(
+        "cyclonedx-json".parse::<SbomFormat>().unwrap(),
+        SbomFormat::CyclonedxJson
+    );
+    assert_eq!("spdx-json".parse::<SbomFormat>().unwrap(), SbomFormat::SpdxJson);
+    assert!("spdx-tag-value".parse::<SbomFormat>().is_err());
+}
+
+#[test]
+fn cyclonedx() {
+    let meta = fixture_meta("dep-1");
+    let sbom = sbom::generate(&meta, SbomFormat::CyclonedxJson).unwrap();
+    assert_eq!(sbom["bomFormat"], "CycloneDX");
+    assert_eq!(sbom["specVersion"], "1.5");
+    assert_eq!(sbom["metadata"]["component"]["name"], "@fixture/dep-1");
+    assert_eq!(
+        find(&sbom["metadata"]["tools"]["components"], "name", "yarn")["version"],
+        "4.2.2"
+    );
+
+    // the root workspace is the subject of the document, not one of its components
+    let components = &sbom["components"];
+    assert!(
+        components
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|component| component["name"] != "@fixture/dep-1")
+    );
+    let libzip = find(components, "name", "@yarnpkg/libzip");
+    assert_eq!(libzip["type"], "library");
+    assert_eq!(libzip["version"], "3.1.0");
+    assert_eq!(libzip["purl"], "pkg:npm/%40yarnpkg/libzip@3.1.0");
+    assert_eq!(libzip["hashes"][0]["alg"], "SHA-512");
+    assert_eq!(libzip["hashes"][0]["content"].as_str().unwrap().len(), 128);
+
+    let dependencies = &sbom["dependencies"];
+    let root = find(dependencies, "ref", "@fixture/dep-1@workspace:.");
+    assert_eq!(root["dependsOn"], serde_json::json!(["@yarnpkg/libzip@npm:3.1.0"]));
+    let libzip = find(dependencies, "ref", "@yarnpkg/libzip@npm:3.1.0");
+    assert_eq!(
+        libzip["dependsOn"],
+        serde_json::json!([
+            "@types/emscripten@npm:1.39.13",
+            "@yarnpkg/fslib@npm:3.1.0",
+            "tslib@npm:2.6.3"
+        ])
+    );
+}
+
+#[test]
+fn spdx() {
+    let meta = fixture_meta("dep-1");
+    let sbom = sbom::generate(&meta, SbomFormat::SpdxJson).unwrap();
+    assert_eq!(sbom["spdxVersion"], "SPDX-2.3");
+    assert_eq!(sbom["name"], "@fixture/dep-1");
+
+    let packages = &sbom["packages"];
+    assert_eq!(packages.as_array().unwrap().len(), meta.yarn_lock.packages.len());
+    let root = find(packages, "name", "@fixture/dep-1");
+    assert!(root["checksums"].as_array().unwrap().is_empty());
+    let libzip = find(packages, "name", "@yarnpkg/libzip");
+    assert_eq!(libzip["checksums"][0]["algorithm"], "SHA512");
+    assert_eq!(
+        libzip["externalRefs"][0]["referenceLocator"],
+        "pkg:npm/%40yarnpkg/libzip@3.1.0"
+    );
+
+    let relationships = sbom["relationships"].as_array().unwrap();
+    let describes = relationships
+        .iter()
+        .filter(|relationship| relationship["relationshipType"] == "DESCRIBES")
+        .collect::<Vec<_>>();
+    assert_eq!(describes.len(), 1);
+    assert_eq!(describes[0]["relatedSpdxElement"], root["SPDXID"]);
+    assert!(relationships.iter().any(|relationship| {
+        relationship["relationshipType"] == "DEPENDS_ON"
+            && relationship["spdxElementId"] == root["SPDXID"]
+            && relationship["relatedSpdxElement"] == libzip["SPDXID"]
+    }));
+}
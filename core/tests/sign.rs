@@ -0,0 +1,137 @@
+//! Signatures of bundles, and the keys trusted by an environment.
+
+use std::path::Path;
+
+use cirno_core::sign::{
+    MANIFEST_FILE, SIGNATURE_FILE, SignatureStatus, TrustConfig, TrustPolicy, sign_dir, verify_dir,
+};
+
+use crate::common::{Scratch, env};
+
+mod common;
+
+fn write(path: &Path, content: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+/// Writes a bundle with a few files into `root`.
+fn bundle(root: &Path) {
+    write(&root.join("package.json"), "{}");
+    write(&root.join("yarn.lock"), "");
+    write(&root.join(".yarn/cache/a-0123456789-10c0.zip"), "a");
+}
+
+fn invalid(status: SignatureStatus) -> String {
+    match status {
+        SignatureStatus::Invalid { reason } => reason,
+        status => panic!("expected an invalid signature, found {:?}", status),
+    }
+}
+
+#[test]
+fn default_policy() {
+    assert_eq!(TrustConfig::default().policy, TrustPolicy::Off);
+}
+
+#[tokio::test]
+async fn sign_and_verify() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let info = cirno.generate_key("alice").await.unwrap();
+    assert!(info.private);
+    let key = cirno.load_signing_key("alice").await.unwrap();
+    let root = scratch.0.join("bundle");
+    bundle(&root);
+
+    assert_eq!(verify_dir(&root, &cirno.trust()).unwrap(), SignatureStatus::Unsigned);
+    sign_dir(&root, &key).unwrap();
+    assert!(root.join(MANIFEST_FILE).exists());
+    assert_eq!(
+        verify_dir(&root, &cirno.trust()).unwrap(),
+        SignatureStatus::Verified {
+            key: "alice".to_string()
+        }
+    );
+    // the same bundle is not trusted by another environment
+    assert_eq!(
+        verify_dir(&root, &TrustConfig::default()).unwrap(),
+        SignatureStatus::Untrusted {
+            public_key: info.public_key
+        }
+    );
+}
+
+#[tokio::test]
+async fn tampered_bundles() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    cirno.generate_key("alice").await.unwrap();
+    let key = cirno.load_signing_key("alice").await.unwrap();
+    let trust = cirno.trust();
+    let root = scratch.0.join("bundle");
+    let reset = || {
+        let _ = std::fs::remove_dir_all(&root);
+        bundle(&root);
+        sign_dir(&root, &key).unwrap();
+    };
+
+    reset();
+    write(&root.join("yarn.lock"), "# modified");
+    assert_eq!(invalid(verify_dir(&root, &trust).unwrap()), "yarn.lock was modified");
+
+    reset();
+    write(&root.join("extra.js"), "");
+    assert_eq!(invalid(verify_dir(&root, &trust).unwrap()), "extra.js is not signed");
+
+    reset();
+    std::fs::remove_file(root.join(".yarn/cache/a-0123456789-10c0.zip")).unwrap();
+    assert_eq!(
+        invalid(verify_dir(&root, &trust).unwrap()),
+        ".yarn/cache/a-0123456789-10c0.zip is missing"
+    );
+
+    // a manifest rewritten to match the tampered files no longer matches its signature
+    reset();
+    let manifest = std::fs::read_to_string(root.join(MANIFEST_FILE)).unwrap();
+    std::fs::write(root.join(MANIFEST_FILE), manifest.replace("yarn.lock", "yarn.lock2")).unwrap();
+    assert_eq!(
+        invalid(verify_dir(&root, &trust).unwrap()),
+        "signature does not match the manifest"
+    );
+
+    reset();
+    std::fs::remove_file(root.join(SIGNATURE_FILE)).unwrap();
+    assert!(invalid(verify_dir(&root, &trust).unwrap()).contains("is missing"));
+}
+
+#[tokio::test]
+async fn keys() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let info = cirno.generate_key("alice").await.unwrap();
+    assert!(cirno.generate_key("alice").await.is_err());
+    assert!(cirno.generate_key("../alice").await.is_err());
+    assert!(cirno.import_key("bob", "0123").await.is_err());
+    cirno.import_key("bob", &info.public_key.to_uppercase()).await.unwrap();
+
+    let keys = cirno.keys().await.unwrap();
+    let names = keys
+        .iter()
+        .map(|key| (key.name.as_str(), key.private))
+        .collect::<Vec<_>>();
+    assert_eq!(names, [("alice", true), ("bob", false)]);
+    // public keys are stored in lowercase hex
+    assert_eq!(keys[1].public_key, info.public_key);
+    assert!(cirno.load_signing_key("bob").await.is_err());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let path = cirno.cwd.join("keys/alice.key");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o077, 0);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(cirno.load_signing_key("alice").await.is_err());
+    }
+}
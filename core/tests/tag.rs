@@ -0,0 +1,80 @@
+//! Labels of instances which are restored or removed.
+
+use cirno_core::Cirno;
+use cirno_core::baka::BackupOptions;
+use cirno_core::instance::RemoveOptions;
+use uuid::Uuid;
+
+use crate::common::{Scratch, add_app, env};
+
+mod common;
+
+fn labels(values: &[&str]) -> Vec<String> {
+    values.iter().map(ToString::to_string).collect()
+}
+
+/// Adds an application whose head is aliased `stable`, with a backup tagged `v1`, and returns their IDs.
+async fn timeline(cirno: &mut Cirno) -> (Uuid, Uuid) {
+    let id = add_app(cirno, "dep-1", "dep-1").await;
+    let backup = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+    cirno.tag(&backup, &labels(&["v1"]), false).await.unwrap();
+    cirno.tag(&id, &labels(&["stable"]), true).await.unwrap();
+    (id, backup)
+}
+
+#[tokio::test]
+async fn restore_tagged_head() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let (id, backup) = timeline(&mut cirno).await;
+
+    let error = cirno.restore(&backup, false).await.unwrap_err().to_string();
+    assert!(
+        error.contains(&format!("Instance {} is tagged (stable)", id)),
+        "{}",
+        error
+    );
+    assert_eq!(cirno.get(&id).unwrap().backups.len(), 1);
+
+    // the labels of the backup replace those of the discarded head
+    cirno.restore(&backup, true).await.unwrap();
+    let app = cirno.get(&id).unwrap();
+    assert_eq!(app.tags, ["v1"]);
+    assert!(app.aliases.is_empty());
+    assert!(cirno.resolve("stable").is_err());
+    assert_eq!(cirno.resolve("v1").unwrap(), id);
+}
+
+#[tokio::test]
+async fn remove_tagged_head() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let (id, backup) = timeline(&mut cirno).await;
+
+    let error = cirno
+        .remove(&id, &RemoveOptions::default())
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("is tagged (stable)"), "{}", error);
+
+    let options = RemoveOptions {
+        force: true,
+        ..Default::default()
+    };
+    let report = cirno.remove(&id, &options).await.unwrap();
+    assert_eq!(report.restored, Some(backup));
+    assert!(cirno.resolve("stable").is_err());
+    assert_eq!(cirno.get(&id).unwrap().tags, ["v1"]);
+}
+
+#[tokio::test]
+async fn restore_untagged_head() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let backup = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+    cirno.tag(&backup, &labels(&["v1"]), true).await.unwrap();
+    cirno.restore(&backup, false).await.unwrap();
+    assert_eq!(cirno.get(&id).unwrap().aliases, ["v1"]);
+}
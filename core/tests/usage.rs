@@ -0,0 +1,108 @@
+//! Disk usage of environments with broken applications.
+
+use std::sync::{Arc, Mutex};
+
+use cirno_core::baka::BackupOptions;
+use cirno_core::fs::dir_size;
+use cirno_core::report::{Event, Reporter};
+
+use crate::common::{Scratch, add_app, env, fill_cache, fixture_meta};
+
+mod common;
+
+#[derive(Default)]
+struct Warnings(Mutex<Vec<String>>);
+
+impl Reporter for Warnings {
+    fn report(&self, event: &Event) {
+        if let Event::Warning { message } = event {
+            self.0.lock().unwrap().push(message.clone());
+        }
+    }
+}
+
+#[tokio::test]
+async fn broken_application() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let healthy = add_app(&mut cirno, "dep-1", "healthy").await;
+    let broken = add_app(&mut cirno, "dep-1", "broken").await;
+    let cache = fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    std::fs::remove_file(cirno.cwd.join("apps").join(broken.to_string()).join("package.json")).unwrap();
+    let warnings = Arc::new(Warnings::default());
+    cirno.set_reporter(warnings.clone());
+
+    // the other applications are still reported, without the references of the broken one
+    let usage = cirno.app_usage().await.unwrap();
+    let cache_size = cache
+        .iter()
+        .map(|path| std::fs::metadata(path).unwrap().len())
+        .sum::<u64>();
+    assert_eq!(usage[&healthy].exclusive_store, cache_size);
+    assert_eq!(usage[&healthy].shared_store, 0);
+    assert!(usage[&broken].files > 0);
+    let warnings = warnings.0.lock().unwrap().clone();
+    assert_eq!(warnings.len(), 1);
+    assert!(
+        warnings[0].contains(&format!("Failed to load instance {}", broken)),
+        "{:?}",
+        warnings
+    );
+    cirno.disk_usage().await.unwrap();
+
+    // the collection must not overlook the files of the broken application
+    assert!(cirno.gc().await.is_err());
+    assert!(cache.iter().all(|path| path.exists()));
+}
+
+#[tokio::test]
+async fn missing_directory() {
+    let scratch = Scratch::new();
+    assert_eq!(dir_size(scratch.0.join("missing")).await.unwrap(), 0);
+    std::fs::create_dir_all(scratch.0.join("dir/nested")).unwrap();
+    std::fs::write(scratch.0.join("dir/a"), "abc").unwrap();
+    std::fs::write(scratch.0.join("dir/nested/b"), "de").unwrap();
+    assert_eq!(dir_size(scratch.0.join("dir")).await.unwrap(), 5);
+}
+
+#[tokio::test]
+async fn instance_usage() {
+    let scratch = Scratch::new();
+    let mut cirno = env(&scratch.0).await;
+    let id = add_app(&mut cirno, "dep-1", "dep-1").await;
+    let cache = fill_cache(&cirno, &fixture_meta("dep-1"), "10c0");
+    let cache_size = cache
+        .iter()
+        .map(|path| std::fs::metadata(path).unwrap().len())
+        .sum::<u64>();
+    std::fs::write(
+        cirno.cwd.join("apps").join(id.to_string()).join("index.js"),
+        "x".repeat(4096),
+    )
+    .unwrap();
+    let first = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+    let second = cirno.backup(&id, &BackupOptions::default()).await.unwrap();
+    let unused = cirno.cwd.join("home/.yarn/cache/unused-npm-1.0.0-abcdef-10c0.zip");
+    std::fs::write(&unused, "unused").unwrap();
+
+    let usage = cirno.disk_usage().await.unwrap();
+    let app = &usage.apps[0];
+    // the store files are exclusive to the application, but shared by its instances
+    assert_eq!(app.total.exclusive_store, cache_size);
+    assert_eq!(app.total.shared_store, 0);
+    let ids = app.instances.iter().map(|instance| instance.id).collect::<Vec<_>>();
+    assert_eq!(ids, [first, second, id]);
+    for instance in &app.instances {
+        assert_eq!(instance.exclusive_store, 0);
+        assert_eq!(instance.shared_store, cache_size);
+    }
+    let head = &app.instances[2];
+    assert_eq!(head.backup, None);
+    assert_eq!(head.files, app.total.files);
+    assert_eq!(head.backups, 0);
+    // the archive is split between the backups, up to rounding
+    let split = app.instances.iter().map(|instance| instance.backups).sum::<u64>();
+    assert!(app.total.backups - split < 2, "{} {}", app.total.backups, split);
+    assert_eq!(app.instances[0].backup, Some(1));
+    assert_eq!(usage.environment.unreferenced, "unused".len() as u64);
+}
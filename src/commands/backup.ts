@@ -1,5 +1,5 @@
 import { CAC } from 'cac'
-import { join, resolve } from 'node:path'
+import { basename, join, resolve } from 'node:path'
 import { Cirno, loadMeta } from '../index.ts'
 import { error, success, Tar } from '../utils.ts'
 
@@ -24,7 +24,9 @@ export default (cli: CAC) => cli
       type: 'manual',
       created: new Date().toISOString(),
     })
-    tar.pack(join(cwd, 'apps', id), newId + '/')
+    // node_modules can be rebuilt from the cache, so there is no need to backup them
+    const linker = await cirno.nodeLinker(meta.yarnRc)
+    tar.pack(join(cwd, 'apps', id), newId + '/', (name) => linker !== 'pnp' && basename(name) === 'node_modules')
     tar.dump(join(cwd, 'tmp', id + '.baka'))
     await tar.finalize()
     await cirno.save()
@@ -21,6 +21,7 @@ export default (cli: CAC) => cli
     }
     cirno.state[newId] = {}
     await cirno.clone(app, id, join(cwd, 'apps', newId))
+    await cirno.install(join(cwd, 'apps', newId))
     await cirno.save()
     success(`Successfully created a cloned instance ${newId}.`)
   })
@@ -1,6 +1,6 @@
 import { CAC } from 'cac'
 import { join, resolve } from 'node:path'
-import { Cirno } from '../index.ts'
+import { Cirno, PACKAGE_MANAGER_REGEX } from '../index.ts'
 import { error, success } from '../utils.ts'
 import { mkdir, rename, writeFile } from 'node:fs/promises'
 
@@ -16,7 +16,7 @@ export default (cli: CAC) => cli
     if (!NAME_REGEX.test(name)) error('Invalid application name. See `cirno create --help` for usage.')
 
     if (!options.manager) error('Missing package manager. See `cirno create --help` for usage.')
-    const capture = /^yarn@(\d+\.\d+\.\d+)/.exec(options.manager)
+    const capture = PACKAGE_MANAGER_REGEX.exec(options.manager)
     if (!capture) error(`Unsupported package manager: ${options.manager}.`)
 
     const cwd = resolve(process.cwd(), options.cwd ?? '.')
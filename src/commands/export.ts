@@ -1,6 +1,6 @@
 import { CAC } from 'cac'
 import { join, resolve } from 'node:path'
-import { Cirno, getCacheFiles, loadMeta } from '../index.ts'
+import { checkLockfileVersion, Cirno, findCacheFile, getCacheFiles, loadMeta, PACKAGE_MANAGER_REGEX } from '../index.ts'
 import { error, loadIntoZip, success } from '../utils.ts'
 import { stringifySyml } from '@yarnpkg/parsers'
 import { ZipFS } from '@yarnpkg/libzip'
@@ -34,20 +34,20 @@ export default (cli: CAC) => cli
       const { pkg, yarnLock, yarnRc } = await loadMeta(temp)
 
       // yarnPath
-      const capture = /^yarn@(\d+\.\d+\.\d+)/.exec(pkg.packageManager)
+      const capture = PACKAGE_MANAGER_REGEX.exec(pkg.packageManager)
       if (!capture) throw new Error('Failed to detect yarn version.')
       yarnRc.yarnPath = `.yarn/releases/yarn-${capture[1]}.cjs`
       await fs.mkdir(join(temp, '.yarn/releases'), { recursive: true })
       await fs.cp(join(cwd, `home/.yarn/releases/yarn-${capture[1]}.cjs`), join(temp, yarnRc.yarnPath))
 
       // enableGlobalCache
-      const { version, cacheKey } = yarnLock.__metadata ?? {}
-      if (version !== '8') throw new Error(`Unsupported yarn.lock version: ${version}.`)
+      const { version } = yarnLock.__metadata ?? {}
+      checkLockfileVersion(version)
       await fs.mkdir(join(temp, '.yarn/cache'), { recursive: true })
-      const cache = (await cirno.loadCache())[cacheKey] ?? {}
-      for (const prefix of getCacheFiles(yarnLock)) {
-        const name = cache[prefix]
-        if (!name) throw new Error(`Cache not found: ${prefix}`)
+      const cache = await cirno.loadCache()
+      for (const file of getCacheFiles(yarnLock)) {
+        const name = findCacheFile(cache, file)
+        if (!name) throw new Error(`Cache not found: ${file.prefix}`)
         await fs.cp(join(cwd, 'home/.yarn/cache', name), join(temp, '.yarn/cache', name))
       }
       yarnRc.enableGlobalCache = 'false'
@@ -3,8 +3,8 @@ import { join, resolve } from 'node:path'
 import { fileURLToPath } from 'node:url'
 import { ZipFS } from '@yarnpkg/libzip'
 import { stringifySyml } from '@yarnpkg/parsers'
-import { Cirno, loadMeta } from '../index.ts'
-import { dumpFromZip, error, success } from '../utils.ts'
+import { BUNDLE_CACHE_REGEX, checkLockfileVersion, Cirno, loadMeta, PACKAGE_MANAGER_REGEX, verifyRelease } from '../index.ts'
+import { dumpFromZip, error, removeNodeModules, success } from '../utils.ts'
 import * as fs from 'node:fs/promises'
 
 function parseImport(src: string, cwd: string) {
@@ -51,20 +51,35 @@ export default (cli: CAC) => cli
 
       // yarnPath
       if (!pkg.packageManager) error('Missing `packageManager` in package.json.')
-      const capture = /^yarn@(\d+\.\d+\.\d+)/.exec(pkg.packageManager)
+      const capture = PACKAGE_MANAGER_REGEX.exec(pkg.packageManager)
       if (!capture) error(`Unsupported package manager: ${pkg.packageManager}.`)
+      const releasePath = join(cwd, `home/.yarn/releases/yarn-${capture[1]}.cjs`)
+      let releaseHash: string | undefined
       if (yarnRc.yarnPath) {
         const yarnPath = resolve(temp, yarnRc.yarnPath)
-        await fs.rename(yarnPath, join(cwd, `home/.yarn/releases/yarn-${capture[1]}.cjs`))
+        // verify the bundled release before it replaces the shared one
+        releaseHash = await verifyRelease(yarnPath, pkg.packageManager)
+        await fs.rename(yarnPath, releasePath)
         await fs.rm(join(temp, '.yarn/releases'), { recursive: true, force: true })
         delete yarnRc.yarnPath
       } else {
         await cirno.downloadYarn(capture[1], yarnRc.npmRegistryServer)
+        releaseHash = await verifyRelease(releasePath, pkg.packageManager)
       }
 
+      // bundled node runtime
+      const runtimes = await fs.readdir(join(temp, '.node')).catch(() => [] as string[])
+      await fs.mkdir(join(cwd, 'home/.node'), { recursive: true })
+      for (const version of runtimes) {
+        const dest = join(cwd, 'home/.node', version)
+        if (await fs.access(dest).then(() => true, () => false)) continue
+        await fs.rename(join(temp, '.node', version), dest)
+      }
+      await fs.rm(join(temp, '.node'), { recursive: true, force: true })
+
       // cacheFolder, enableGlobalCache
       const { version, cacheKey } = yarnLock.__metadata ?? {}
-      if (version !== '8') throw new Error(`Unsupported yarn.lock version: ${version}.`)
+      checkLockfileVersion(version)
       let cacheFolder: string | undefined
       if (yarnRc.enableGlobalCache !== 'true') {
         cacheFolder = resolve(temp, yarnRc.cacheFolder ?? '.yarn/cache')
@@ -73,7 +88,9 @@ export default (cli: CAC) => cli
       if (cacheFolder) {
         const files = await fs.readdir(cacheFolder)
         for (const name of files) {
-          const capture = /^(.+)-([0-9a-f]{10})-([0-9a-f]+)\.zip$/.exec(name)
+          // Slugs end with a 10-char locator hash, followed by either the 10-char checksum (local cache) or the cache
+          // key (global cache). Both are renamed to the global cache naming.
+          const capture = BUNDLE_CACHE_REGEX.exec(name)
           if (!capture) continue
           await fs.rename(join(cacheFolder, name), join(cwd, 'home/.yarn/cache', `${capture[1]}-${capture[2]}-${cacheKey}.zip`))
         }
@@ -82,6 +99,11 @@ export default (cli: CAC) => cli
       delete yarnRc.cacheFolder
       delete yarnRc.enableGlobalCache
 
+      // node_modules may contain artifacts built for another platform, so we always rebuild them
+      if (await cirno.nodeLinker(yarnRc) !== 'pnp') {
+        await removeNodeModules(temp)
+      }
+
       await fs.writeFile(temp + '/.yarnrc.yml', stringifySyml(yarnRc))
       const code = await cirno.yarn(temp, options['--'])
       if (code !== 0) error(`Failed to install dependencies. Exit code: ${code}`)
@@ -92,6 +114,7 @@ export default (cli: CAC) => cli
         name,
         created: new Date().toISOString(),
         backups: [],
+        ...releaseHash && { releaseHash },
       }
       cirno.state[id] = {}
       await fs.rename(temp, join(cwd, 'apps', id))
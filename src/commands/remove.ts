@@ -48,6 +48,9 @@ export default (cli: CAC) => cli
       tar.dump(join(cwd, 'tmp', app.id + '.baka'), !!app.backups.length)
       await tar.finalize()
     }
+    if (pack) {
+      await cirno.install(join(cwd, 'apps', app.id))
+    }
     await cirno.save()
     await cirno.gc()
     success(`Instance ${id} is successfully removed.`)
@@ -28,6 +28,7 @@ export default (cli: CAC) => cli
     }
     tar.dump(join(cwd, 'tmp', id + '.baka'), !!app.backups.length)
     await tar.finalize()
+    await cirno.install(join(cwd, 'apps', app.id))
     await cirno.save()
     success(`App ${app.id} is successfully restored to backup ${id}.`)
   })
@@ -2,9 +2,10 @@ import * as fs from 'node:fs/promises'
 import * as yaml from 'js-yaml'
 import * as zlib from 'node:zlib'
 import { error, info, Tar } from './utils.ts'
-import { join } from 'node:path'
+import { basename, join } from 'node:path'
 import { fork } from 'node:child_process'
 import { promisify } from 'node:util'
+import { createHash } from 'node:crypto'
 import { parseSyml, stringifySyml } from '@yarnpkg/parsers'
 import { Readable } from 'node:stream'
 import { finished } from 'node:stream/promises'
@@ -55,6 +56,7 @@ export interface App {
   name: string
   backups: Backup[]
   created: string
+  releaseHash?: string
 }
 
 export interface Config {}
@@ -79,14 +81,68 @@ export async function loadMeta(cwd: string): Promise<Meta> {
   return { pkg, yarnRc, yarnLock }
 }
 
+/** `yarn@<version>[+<algorithm>.<hex>]` as written by Corepack, where the version may carry a prerelease tag. */
+export const PACKAGE_MANAGER_REGEX = /^yarn@(\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?)(?:\+(\w+)\.([0-9a-fA-F]+))?$/
+
+/**
+ * Verify a yarn release against the hash of a `packageManager` field.
+ * Returns the verified hash, or `undefined` if the field does not declare one.
+ * Releases are verified when they are imported, rather than on each yarn command.
+ */
+export async function verifyRelease(path: string, packageManager: string) {
+  const capture = PACKAGE_MANAGER_REGEX.exec(packageManager)
+  if (!capture?.[2]) return
+  const [, version, algorithm, expected] = capture
+  const actual = createHash(algorithm).update(await fs.readFile(path)).digest('hex')
+  if (actual !== expected.toLowerCase()) {
+    throw new Error(`Hash mismatch for yarn ${version}: expected ${algorithm}.${expected}, found ${algorithm}.${actual}.`)
+  }
+  return `${algorithm}.${actual}`
+}
+
+/**
+ * Cache file of a bundle. Slugs end with a 10-char locator hash, followed by either the 10-char checksum (local cache)
+ * or the cache key (global cache). Shared with the Rust import, see `tests/fixtures/bundle-cache-names.json`.
+ */
+export const BUNDLE_CACHE_REGEX = /^(.+)-([0-9a-f]{10})-([0-9a-f]+)\.zip$/
+
+/** Lockfile versions supported by Cirno: 6 is written by Yarn 3, 7 by the release candidates of Yarn 4, and 8 by Yarn 4. */
+export const SUPPORTED_LOCKFILE_VERSIONS = ['6', '7', '8']
+
+export function checkLockfileVersion(version?: string) {
+  if (!version || !SUPPORTED_LOCKFILE_VERSIONS.includes(`${version}`)) {
+    throw new Error(`Unsupported yarn.lock version: ${version} (supported: 6 to 8).`)
+  }
+}
+
+export interface CacheFile {
+  prefix: string
+  /**
+   * Keys under which the cache may store the file, in order of preference: the cache key of the lockfile, as named by
+   * the global cache, then the first 10 characters of the checksum, as named by a local cache. The checksums of Yarn 4
+   * are prefixed with their cache key (eg. `10c0/<hex>`), unlike those of Yarn 3.
+   */
+  keys: string[]
+}
+
 export function getCacheFiles(yarnLock: YarnLock) {
   return Object.entries(yarnLock).map(([key, value]) => {
     if (key === '__metadata') return
     const locator = tryParseLocator(value.resolution, true)
     if (!locator) throw new Error(`Failed to parse resolution: ${value.resolution}`)
     if (locator.reference.startsWith('workspace:')) return
-    return slugifyLocator(locator)
-  }).filter(Boolean) as string[]
+    const keys = [`${yarnLock.__metadata.cacheKey}`]
+    const checksum = value.checksum?.split('/').pop()
+    if (checksum) keys.push(checksum.slice(0, 10))
+    return { prefix: slugifyLocator(locator), keys }
+  }).filter(Boolean) as CacheFile[]
+}
+
+export function findCacheFile(cache: Record<string, Record<string, string>>, { prefix, keys }: CacheFile) {
+  for (const key of keys) {
+    const name = cache[key]?.[prefix]
+    if (name) return name
+  }
 }
 
 const ENTRY_FILE = 'cirno.yml'
@@ -176,9 +232,24 @@ export class Cirno {
     await fs.writeFile(join(this.cwd, STATE_FILE), await compress(Buffer.from(JSON.stringify(this.state))))
   }
 
+  async nodeLinker(yarnRc: YarnRc) {
+    if (yarnRc.nodeLinker) return yarnRc.nodeLinker
+    const globalRc = parseSyml(await fs.readFile(join(this.cwd, 'home/.yarnrc.yml'), 'utf8')) as YarnRc
+    return globalRc.nodeLinker ?? 'pnp'
+  }
+
+  /**
+   * Copy an instance to `dest`. The `node_modules` folders of applications using
+   * the `node-modules` or `pnpm` linker are not copied. Use `install` to rebuild them.
+   */
   async clone(app: App, id: string, dest: string) {
     if (app.id === id) {
-      await fs.cp(join(this.cwd, 'apps', id), dest, { recursive: true })
+      const { yarnRc } = await loadMeta(join(this.cwd, 'apps', id))
+      const linker = await this.nodeLinker(yarnRc)
+      await fs.cp(join(this.cwd, 'apps', id), dest, {
+        recursive: true,
+        filter: (src) => linker === 'pnp' || basename(src) !== 'node_modules',
+      })
     } else {
       const tar = new Tar(join(this.cwd, 'baka', id + '.tar.br'))
       tar.load()
@@ -223,7 +294,7 @@ export class Cirno {
 
   async yarn(cwd: string, args: string[]) {
     const pkgMeta: Package = JSON.parse(await fs.readFile(join(cwd, '/package.json'), 'utf8'))
-    const capture = /^yarn@(\d+\.\d+\.\d+)/.exec(pkgMeta.packageManager)
+    const capture = PACKAGE_MANAGER_REGEX.exec(pkgMeta.packageManager)
     if (!capture) throw new Error('Failed to detect yarn version.')
     const env: Record<string, string | undefined> = { ...process.env }
 
@@ -262,22 +333,32 @@ export class Cirno {
     })
   }
 
+  /**
+   * Rebuild the `node_modules` folders of an application if it does not use PnP.
+   */
+  async install(cwd: string) {
+    const { yarnRc } = await loadMeta(cwd)
+    if (await this.nodeLinker(yarnRc) === 'pnp') return
+    const code = await this.yarn(cwd, [])
+    if (code !== 0) throw new Error(`Failed to install dependencies. Exit code: ${code}`)
+  }
+
   async gc() {
     const cache = await this.loadCache()
     const releases = new Set(await fs.readdir(join(this.cwd, 'home/.yarn/releases')))
     await Promise.all(Object.keys(this.state).map(async (id) => {
       const { pkg, yarnLock } = await loadMeta(join(this.cwd, 'apps', id))
-      const capture = /^yarn@(\d+\.\d+\.\d+)/.exec(pkg.packageManager)
+      const capture = PACKAGE_MANAGER_REGEX.exec(pkg.packageManager)
       if (capture) releases.delete(`yarn-${capture[1]}.cjs`)
-      for (const prefix of getCacheFiles(yarnLock)) {
-        delete cache[yarnLock.__metadata.cacheKey]?.[prefix]
+      for (const { prefix, keys } of getCacheFiles(yarnLock)) {
+        for (const key of keys) delete cache[key]?.[prefix]
       }
     }))
     for (const { pkg, yarnLock } of Object.values(this.state).map(x => Object.values(x)).flat()) {
-      const capture = /^yarn@(\d+\.\d+\.\d+)/.exec(pkg.packageManager)
+      const capture = PACKAGE_MANAGER_REGEX.exec(pkg.packageManager)
       if (capture) releases.delete(`yarn-${capture[1]}.cjs`)
-      for (const prefix of getCacheFiles(yarnLock)) {
-        delete cache[yarnLock.__metadata.cacheKey]?.[prefix]
+      for (const { prefix, keys } of getCacheFiles(yarnLock)) {
+        for (const key of keys) delete cache[key]?.[prefix]
       }
     }
     for (const name of releases) {
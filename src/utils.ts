@@ -59,6 +59,18 @@ export async function loadIntoZip(zip: ZipFS, root: string, base = '/') {
   }))
 }
 
+export async function removeNodeModules(root: string) {
+  const dirents = await fs.readdir(root, { withFileTypes: true })
+  await Promise.all(dirents.map(async (dirent) => {
+    if (!dirent.isDirectory()) return
+    if (dirent.name === 'node_modules') {
+      await fs.rm(join(root, dirent.name), { recursive: true, force: true })
+    } else {
+      await removeNodeModules(join(root, dirent.name))
+    }
+  }))
+}
+
 export class Tar {
   private callback?: () => Promise<void>
   private packs = [tarStream.pack()]
@@ -85,12 +97,13 @@ export class Tar {
     this.readables.push(extract)
   }
 
-  pack(root: string, base = '') {
+  pack(root: string, base = '', ignore?: (name: string) => boolean) {
     const extract = tarStream.extract()
     extract.on('entry', (header, stream, callback) => {
       stream.pipe(this.packs[0].entry(header, callback))
     })
     tarFs.pack(root, {
+      ignore,
       map: (header) => {
         header.name = join(base, header.name)
         return header
@@ -5,12 +5,12 @@ function execute(cwd: URL, command: string) {
   execSync(command, { cwd, stdio: 'inherit' })
 }
 
-function prepare(cwd: URL) {
+function prepare(cwd: URL, linker: string) {
   execute(cwd, 'yarn set version self --yarn-path')
   execute(cwd, 'yarn config set enableGlobalCache false')
   execute(cwd, 'yarn config set enableTelemetry false')
   execute(cwd, 'yarn config set enableTips false')
-  execute(cwd, 'yarn config set nodeLinker pnp')
+  execute(cwd, `yarn config set nodeLinker ${linker}`)
   execute(cwd, 'yarn')
 }
 
@@ -19,5 +19,7 @@ const dirents = readdirSync(baseURL, { withFileTypes: true })
 for (const dirent of dirents) {
   if (!dirent.isDirectory()) continue
   if (existsSync(new URL(`./${dirent.name}/.yarnrc.yml`, baseURL))) continue
-  prepare(new URL(`./${dirent.name}`, baseURL))
+  // fixtures named `linker-<linker>` use the corresponding node linker
+  const linker = dirent.name.startsWith('linker-') ? dirent.name.slice(7) : 'pnp'
+  prepare(new URL(`./${dirent.name}`, baseURL), linker)
 }
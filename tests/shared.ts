@@ -1,9 +1,14 @@
 import { mkdir, readdir, readFile } from 'node:fs/promises'
 import { fork } from 'node:child_process'
+import { createReadStream } from 'node:fs'
 import { fileURLToPath } from 'node:url'
+import { createBrotliDecompress } from 'node:zlib'
+import { PortablePath } from '@yarnpkg/fslib'
+import { ZipFS } from '@yarnpkg/libzip'
 import { beforeAll, expect, it } from 'vitest'
 import { v5 } from 'uuid'
 import { isMatch } from 'micromatch'
+import * as tarStream from 'tar-stream'
 
 const root = fileURLToPath(new URL('../temp', import.meta.url))
 
@@ -99,6 +104,29 @@ export function useExport(name: string): Arg {
   return { value: `../exports/${name}`, pretty: `/exports/${name}` }
 }
 
+/**
+ * List the entries of a `.tar.br` archive, such as the backups of an application.
+ */
+export async function listTar(path: string) {
+  const names: string[] = []
+  const extract = createReadStream(path).pipe(createBrotliDecompress()).pipe(tarStream.extract())
+  for await (const entry of extract) {
+    names.push(entry.header.name)
+    entry.resume()
+  }
+  return names
+}
+
+/**
+ * List the files of a zip bundle.
+ */
+export function listZip(path: string) {
+  const zip = new ZipFS(path as PortablePath, { readOnly: true })
+  const names = zip.getAllFiles().map((name) => name.slice(1))
+  zip.discardAndClose()
+  return names
+}
+
 export interface StepOptions {
   silent?: boolean
   code?: number
@@ -142,6 +170,14 @@ export class CirnoTestContext {
     return { value: uuid, pretty: `<#${instCount}>` }
   }
 
+  /**
+   * Add a step which checks the files of the environment, in addition to the snapshots of the commands.
+   */
+  check(name: string, callback: (cwd: string) => void | Promise<void>) {
+    stepCount += 1
+    it(`step ${stepCount}: ${name}`, () => callback(this.root + '/data'))
+  }
+
   pass(args: (string | Arg)[], options: StepOptions = {}) {
     return this.test(args, { ...options, code: 0 })
   }
@@ -0,0 +1,18 @@
+import { readFileSync } from 'node:fs'
+import { expect, it } from 'vitest'
+import { BUNDLE_CACHE_REGEX } from '../../src/index.ts'
+
+interface BundleCacheName {
+  name: string
+  slug?: string
+  hash?: string
+  key?: string
+}
+
+// the test vector is shared with the Rust import
+const names: BundleCacheName[] = JSON.parse(readFileSync(new URL('../fixtures/bundle-cache-names.json', import.meta.url), 'utf8'))
+
+it.each(names)('bundle cache name $name', ({ name, slug, hash, key }) => {
+  const capture = BUNDLE_CACHE_REGEX.exec(name)
+  expect(capture ? capture.slice(1) : undefined).toEqual(slug ? [slug, hash, key] : undefined)
+})
@@ -0,0 +1,48 @@
+import { existsSync } from 'node:fs'
+import { join } from 'node:path'
+import { expect } from 'vitest'
+import { listTar, listZip, makeEnv, useExport, useFixture } from '../shared'
+
+const hasNodeModules = (names: string[]) => names.some((name) => name.split('/').includes('node_modules'))
+
+for (const linker of ['node-modules', 'pnpm']) {
+  makeEnv(`linker-${linker}`, (ctx) => {
+    const isInstalled = (cwd: string, id: string) => {
+      return existsSync(join(cwd, 'apps', id, 'node_modules/@yarnpkg/libzip/package.json'))
+    }
+
+    ctx.pass(['init'], { silent: true })
+    const uuid1 = ctx.pass(['import', useFixture(`linker-${linker}`)], { silent: true })
+    ctx.check('node_modules are installed on import', (cwd) => {
+      expect(isInstalled(cwd, uuid1.value)).toBe(true)
+    })
+
+    // node_modules are excluded from backups and rebuilt on restore
+    const uuid2 = ctx.pass(['backup', uuid1], { silent: true })
+    ctx.check('node_modules are excluded from backups', async (cwd) => {
+      const names = await listTar(join(cwd, 'baka', uuid1.value + '.tar.br'))
+      expect(names.some((name) => name.startsWith(uuid2.value + '/'))).toBe(true)
+      expect(hasNodeModules(names)).toBe(false)
+    })
+    const uuid3 = ctx.pass(['clone', uuid1], { silent: true })
+    ctx.check('node_modules are rebuilt on clone', (cwd) => {
+      expect(isInstalled(cwd, uuid3.value)).toBe(true)
+    })
+    ctx.pass(['restore', uuid2], { silent: true })
+    ctx.check('node_modules are rebuilt on restore', (cwd) => {
+      expect(isInstalled(cwd, uuid1.value)).toBe(true)
+    })
+
+    // node_modules are excluded from bundles and rebuilt on import
+    ctx.pass(['export', uuid1, useExport(`linker-${linker}.zip`)], { silent: true })
+    ctx.check('node_modules are excluded from bundles', (cwd) => {
+      const names = listZip(join(cwd, '..', 'exports', `linker-${linker}.zip`))
+      expect(names).toContain('package.json')
+      expect(hasNodeModules(names)).toBe(false)
+    })
+    const uuid4 = ctx.pass(['import', useExport(`linker-${linker}.zip`)], { silent: true })
+    ctx.check('node_modules are rebuilt on import', (cwd) => {
+      expect(isInstalled(cwd, uuid4.value)).toBe(true)
+    })
+  })
+}